@@ -0,0 +1,415 @@
+//! A synchronous counterpart to [`RemoteZip`](crate::RemoteZip) for use outside of async code.
+//!
+//! Every [`RemoteZip`] drives the asynchronous implementation on its own single threaded runtime, so the
+//! same range requests are made and no runtime has to be set up by the caller. Like other blocking APIs
+//! built on tokio, these methods must not be called from within an async context.
+
+use std::{
+    io::{Read, Write},
+    path::Path,
+    pin::Pin,
+    sync::Arc,
+    task::{Context, Poll},
+};
+
+use bytes::{Buf, Bytes};
+use futures_util::{StreamExt, stream::BoxStream};
+use netzip_parser::{CentralDirectoryRecord, CompressionMethod, LocalFile};
+use tokio::{io::AsyncWrite, runtime::Runtime};
+
+use crate::{DirectoryNode, EntryResult, EntrySelector, Error, RemoteZipBuilder, TransferStats};
+
+/// A remote ZIP archive accessed with blocking calls. See [`crate::RemoteZip`] for the asynchronous version.
+pub struct RemoteZip {
+    inner: crate::RemoteZip,
+    runtime: Arc<Runtime>,
+}
+
+impl RemoteZip {
+    /// Creates a new RemoteZip instance by fetching and parsing the ZIP directory structure from a remote URL.
+    ///
+    /// # Arguments
+    ///
+    /// * `url` - The URL of the remote ZIP file to access
+    ///
+    /// # Returns
+    ///
+    /// A Result containing either the initialized RemoteZip instance or an Error
+    pub fn get(url: &str) -> Result<Self, Error> {
+        crate::RemoteZip::builder(url).build_blocking()
+    }
+
+    /// Creates a new RemoteZip instance by fetching and parsing the ZIP directory structure from a remote URL
+    /// using the provided HTTP client.
+    ///
+    /// # Arguments
+    ///
+    /// * `url` - The URL of the remote ZIP file to access
+    /// * `http_client` - The reqwest HTTP client to use for making requests
+    ///
+    /// # Returns
+    ///
+    /// A Result containing either the initialized RemoteZip instance or an Error
+    pub fn get_using(url: &str, http_client: reqwest::Client) -> Result<Self, Error> {
+        crate::RemoteZip::builder(url)
+            .client(http_client)
+            .build_blocking()
+    }
+
+    /// Returns a builder to configure the RemoteZip, finished with [`RemoteZipBuilder::build_blocking`].
+    ///
+    /// # Arguments
+    ///
+    /// * `url` - The URL of the remote ZIP file to access
+    pub fn builder(url: &str) -> RemoteZipBuilder {
+        crate::RemoteZip::builder(url)
+    }
+
+    pub(crate) fn load(builder: RemoteZipBuilder) -> Result<Self, Error> {
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .map_err(Error::RuntimeUnavailable)?;
+        let inner = runtime.block_on(builder.build())?;
+
+        Ok(Self {
+            inner,
+            runtime: Arc::new(runtime),
+        })
+    }
+
+    /// Returns the counters of the data transferred for this archive so far.
+    pub fn stats(&self) -> TransferStats {
+        self.inner.stats()
+    }
+
+    /// Returns the token cancelling all operations of this RemoteZip. Cancelling it from another thread
+    /// makes a running call return early. See [`crate::RemoteZip::cancellation_token`].
+    pub fn cancellation_token(&self) -> &crate::CancellationToken {
+        self.inner.cancellation_token()
+    }
+
+    /// Sets or clears the deadline for operations of this RemoteZip. See [`crate::RemoteZip::set_deadline`].
+    pub fn set_deadline(&self, deadline: Option<std::time::Instant>) {
+        self.inner.set_deadline(deadline);
+    }
+
+    /// Returns a reference to the central directory records of the ZIP file.
+    pub fn records(&self) -> &Vec<CentralDirectoryRecord> {
+        self.inner.records()
+    }
+
+    /// Builds a directory tree of the entries in the ZIP file. See [`crate::RemoteZip::tree`].
+    pub fn tree(&self) -> DirectoryNode<'_> {
+        self.inner.tree()
+    }
+
+    /// Returns the file entries matching the selector. See [`crate::RemoteZip::select`].
+    pub fn select(&self, selector: &EntrySelector) -> Vec<&CentralDirectoryRecord> {
+        self.inner.select(selector)
+    }
+
+    /// Downloads and decompresses the specified files from the remote ZIP.
+    ///
+    /// # Arguments
+    ///
+    /// * `paths` - A vector of file paths/names to download from the ZIP
+    ///
+    /// # Returns
+    ///
+    /// A Result containing either a vector of tuples with (LocalFile metadata, file contents as bytes)
+    /// or an Error if any file could not be downloaded or decompressed
+    pub fn download_files(&self, paths: Vec<String>) -> Result<Vec<(LocalFile, Vec<u8>)>, Error> {
+        self.runtime.block_on(self.inner.download_files(paths))
+    }
+
+    /// Downloads and decompresses the specified files from the remote ZIP, reporting the outcome of each
+    /// file separately.
+    ///
+    /// # Arguments
+    ///
+    /// * `paths` - A vector of file paths/names to download from the ZIP
+    ///
+    /// # Returns
+    ///
+    /// A vector of tuples with (requested path, EntryResult), in the order the paths were given
+    pub fn download_entries(&self, paths: Vec<String>) -> Vec<(String, EntryResult)> {
+        self.runtime.block_on(self.inner.download_entries(paths))
+    }
+
+    /// Downloads and decompresses a single file from the remote ZIP into a writer, streaming the data so the
+    /// file never has to be held in memory as a whole.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - The file path/name inside the ZIP
+    /// * `writer` - The writer to write the decompressed contents to
+    ///
+    /// # Returns
+    ///
+    /// A Result containing either the LocalFile metadata of the entry or an Error if the file could not be
+    /// found, downloaded, decompressed or written. The writer may have received partial data on error.
+    pub fn download_to_writer<W: Write>(
+        &self,
+        path: &str,
+        writer: &mut W,
+    ) -> Result<LocalFile, Error> {
+        let mut writer = SyncWriter(writer);
+        self.runtime
+            .block_on(self.inner.download_to_writer(path, &mut writer))
+    }
+
+    /// Downloads and decompresses a single file from the remote ZIP to a path on disk, without leaving a
+    /// partially written file behind on failure.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - The file path/name inside the ZIP
+    /// * `destination` - The path on disk to write the decompressed contents to
+    ///
+    /// # Returns
+    ///
+    /// A Result containing either the LocalFile metadata of the entry or an Error if the file could not be
+    /// found, downloaded, decompressed or written
+    pub fn download_to_path(
+        &self,
+        path: &str,
+        destination: impl AsRef<Path>,
+    ) -> Result<LocalFile, Error> {
+        self.runtime
+            .block_on(self.inner.download_to_path(path, destination))
+    }
+
+    /// Builds a new archive containing only the given entries and writes it to a path on disk, copying their
+    /// compressed data without recompressing it. See [`crate::RemoteZip::subset_to_writer`].
+    ///
+    /// # Arguments
+    ///
+    /// * `paths` - The file paths/names of the entries to copy, in the order they are written
+    /// * `destination` - The path on disk to write the new archive to
+    ///
+    /// # Returns
+    ///
+    /// A Result containing either the central directory records of the new archive or an Error if an entry
+    /// could not be found, downloaded or written
+    pub fn subset_to_path(
+        &self,
+        paths: Vec<String>,
+        destination: impl AsRef<Path>,
+    ) -> Result<Vec<CentralDirectoryRecord>, Error> {
+        self.runtime
+            .block_on(self.inner.subset_to_path(paths, destination))
+    }
+
+    /// Opens a file of the remote ZIP for sequential reading. The data is fetched and decompressed as it
+    /// is read.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - The file path/name inside the ZIP
+    ///
+    /// # Returns
+    ///
+    /// A Result containing either an EntryReader implementing `std::io::Read`, or an Error if the file could
+    /// not be found or uses an unsupported compression method
+    pub fn open(&self, path: &str) -> Result<EntryReader, Error> {
+        let cd_record = self.inner.find_record(path)?;
+        if let CompressionMethod::Unsupported(id) = cd_record.compression_method {
+            return Err(Error::UnsupportCompression(id));
+        }
+
+        let (lfh, body) = self.runtime.block_on(self.inner.open_body(cd_record))?;
+        let body = BodyReader {
+            runtime: self.runtime.clone(),
+            body,
+            chunk: Bytes::new(),
+            remaining: self.inner.entry_extent(cd_record)?.compressed_size,
+            url: self.inner.source.url().into(),
+        };
+
+        Ok(EntryReader {
+            decoder: match lfh.compression_method {
+                CompressionMethod::Stored => Decoder::Stored(body),
+                _ => Decoder::Deflate(Box::new(flate2::read::DeflateDecoder::new(body))),
+            },
+            local_file: lfh,
+        })
+    }
+
+    /// Opens a ZIP archive contained in a file of this archive. See [`crate::RemoteZip::open_nested_with_limit`].
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - The file path/name of the inner archive inside the ZIP
+    /// * `buffer_limit` - The largest uncompressed size in bytes of a compressed inner archive to buffer
+    ///
+    /// # Returns
+    ///
+    /// A Result containing either the RemoteZip of the inner archive or an Error
+    pub fn open_nested_with_limit(&self, path: &str, buffer_limit: u64) -> Result<Self, Error> {
+        let inner = self
+            .runtime
+            .block_on(self.inner.open_nested_with_limit(path, buffer_limit))?;
+
+        Ok(Self {
+            inner,
+            runtime: self.runtime.clone(),
+        })
+    }
+
+    /// Opens a ZIP archive contained in a file of this archive. See [`crate::RemoteZip::open_nested`].
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - The file path/name of the inner archive inside the ZIP
+    ///
+    /// # Returns
+    ///
+    /// A Result containing either the RemoteZip of the inner archive or an Error
+    pub fn open_nested(&self, path: &str) -> Result<Self, Error> {
+        self.open_nested_with_limit(path, crate::DEFAULT_NESTED_BUFFER_LIMIT)
+    }
+}
+
+/// Reads the decompressed contents of a file in a remote ZIP. Obtained from [`RemoteZip::open`].
+///
+/// Errors from fetching the data are returned as `std::io::Error`s wrapping the underlying [`Error`].
+pub struct EntryReader {
+    local_file: LocalFile,
+    decoder: Decoder,
+}
+
+enum Decoder {
+    Stored(BodyReader),
+    Deflate(Box<flate2::read::DeflateDecoder<BodyReader>>),
+}
+
+impl EntryReader {
+    /// Returns the local file header of the entry being read.
+    pub fn local_file(&self) -> &LocalFile {
+        &self.local_file
+    }
+}
+
+impl Read for EntryReader {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        match &mut self.decoder {
+            Decoder::Stored(body) => body.read(buf),
+            Decoder::Deflate(decoder) => decoder.read(buf),
+        }
+    }
+}
+
+/// Reads exactly the expected number of compressed bytes from a body stream.
+struct BodyReader {
+    runtime: Arc<Runtime>,
+    body: BoxStream<'static, Result<Bytes, Error>>,
+    chunk: Bytes,
+    remaining: u64,
+    url: String,
+}
+
+impl Read for BodyReader {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        while self.chunk.is_empty() {
+            if self.remaining == 0 {
+                return Ok(0);
+            }
+
+            let expected = self.remaining;
+            let chunk = match self.runtime.block_on(self.body.next()) {
+                Some(chunk) => chunk.map_err(std::io::Error::other)?,
+                None => Bytes::new(),
+            };
+            if chunk.is_empty() || chunk.len() as u64 > expected {
+                let received = if chunk.is_empty() {
+                    0
+                } else {
+                    chunk.len() as u64
+                };
+                return Err(std::io::Error::other(Error::IncompleteResponse(
+                    self.url.clone(),
+                    expected,
+                    received,
+                )));
+            }
+
+            self.remaining -= chunk.len() as u64;
+            self.chunk = chunk;
+        }
+
+        let length = buf.len().min(self.chunk.len());
+        buf[..length].copy_from_slice(&self.chunk[..length]);
+        self.chunk.advance(length);
+
+        Ok(length)
+    }
+}
+
+/// Adapts a blocking writer for the asynchronous download code, which only ever runs on the blocking
+/// runtime's own thread.
+struct SyncWriter<'a, W>(&'a mut W);
+
+impl<W: Write> AsyncWrite for SyncWriter<'_, W> {
+    fn poll_write(
+        mut self: Pin<&mut Self>,
+        _cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<std::io::Result<usize>> {
+        Poll::Ready(self.0.write(buf))
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<std::io::Result<()>> {
+        Poll::Ready(self.0.flush())
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<std::io::Result<()>> {
+        self.poll_flush(cx)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn body_reader(chunks: Vec<&'static [u8]>, length: u64) -> BodyReader {
+        let chunks = chunks.into_iter().map(|x| Ok(Bytes::from_static(x)));
+        BodyReader {
+            runtime: Arc::new(
+                tokio::runtime::Builder::new_current_thread()
+                    .build()
+                    .unwrap(),
+            ),
+            body: futures_util::stream::iter(chunks).boxed(),
+            chunk: Bytes::new(),
+            remaining: length,
+            url: "http://example.com/a.zip".into(),
+        }
+    }
+
+    #[test]
+    fn body_reader_checks_length() {
+        let mut data = Vec::new();
+        body_reader(vec![b"abc", b"de"], 5)
+            .read_to_end(&mut data)
+            .unwrap();
+        assert_eq!(data, b"abcde");
+
+        let mut data = Vec::new();
+        let error = body_reader(vec![b"abc"], 5)
+            .read_to_end(&mut data)
+            .unwrap_err();
+        assert!(matches!(
+            error.into_inner().unwrap().downcast_ref(),
+            Some(Error::IncompleteResponse(_, 2, 0))
+        ));
+
+        let error = body_reader(vec![b"abc", b"def"], 5)
+            .read_to_end(&mut data)
+            .unwrap_err();
+        assert!(matches!(
+            error.into_inner().unwrap().downcast_ref(),
+            Some(Error::IncompleteResponse(_, 2, 3))
+        ));
+    }
+}
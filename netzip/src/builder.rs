@@ -0,0 +1,248 @@
+#[cfg(feature = "serde")]
+use crate::DirectorySnapshot;
+use std::{
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use futures_util::stream::BoxStream;
+use netzip_parser::CentralDirectoryRecord;
+
+use crate::{
+    CancellationToken, Error, ProgressListener, RangeCache, RateLimiter, RemoteZip,
+    cancel::Cancellation,
+    directory,
+    progress::ProgressTracker,
+    source::{FileSource, GuardedSource, HttpSource, MirrorSource, SharedSource, SplitSource},
+    stats::StatsRecorder,
+};
+
+/// Configures and creates a [`RemoteZip`]. Obtained from [`RemoteZip::builder`].
+#[derive(Clone)]
+pub struct RemoteZipBuilder {
+    url: String,
+    segments: Vec<String>,
+    mirrors: Vec<String>,
+    mirror_timeout: Option<Duration>,
+    client: Option<reqwest::Client>,
+    cache: Option<RangeCache>,
+    rate_limit: Option<RateLimiter>,
+    progress: Option<Arc<dyn ProgressListener>>,
+    cancellation_token: CancellationToken,
+    deadline: Option<Instant>,
+}
+
+impl std::fmt::Debug for RemoteZipBuilder {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("RemoteZipBuilder")
+            .field("url", &self.url)
+            .field("segments", &self.segments)
+            .field("mirrors", &self.mirrors)
+            .field("mirror_timeout", &self.mirror_timeout)
+            .field("client", &self.client)
+            .field("cache", &self.cache)
+            .field("rate_limit", &self.rate_limit)
+            .field("progress", &self.progress.is_some())
+            .field("cancellation_token", &self.cancellation_token)
+            .field("deadline", &self.deadline)
+            .finish()
+    }
+}
+
+impl RemoteZipBuilder {
+    pub(crate) fn new(url: &str) -> Self {
+        Self {
+            url: url.into(),
+            segments: Vec::new(),
+            mirrors: Vec::new(),
+            mirror_timeout: None,
+            client: None,
+            cache: None,
+            rate_limit: None,
+            progress: None,
+            cancellation_token: CancellationToken::new(),
+            deadline: None,
+        }
+    }
+
+    /// Sets the reqwest HTTP client to use for making requests. A default client is used otherwise.
+    pub fn client(mut self, client: reqwest::Client) -> Self {
+        self.client = Some(client);
+        self
+    }
+
+    /// Reads the archive as a split archive, such as one written by `zip -s`, whose earlier segments are at these
+    /// URLs or local paths. The segments are given in disk order, e.g. `archive.z01` and `archive.z02`, and the
+    /// builder's URL is the last segment, `archive.zip`, which holds the central directory. Entries may cross
+    /// from one segment into the next. Loading fails with [`Error::SegmentCountMismatch`] unless exactly
+    /// one segment per disk is given.
+    pub fn segments(mut self, urls: impl IntoIterator<Item = impl Into<String>>) -> Self {
+        self.segments.extend(urls.into_iter().map(Into::into));
+        self
+    }
+
+    /// Adds mirrors serving identical copies of the archive. Range requests are spread across the archive's URL
+    /// and all mirrors, and a request failing on one of them is retried on the next. On first use, each mirror
+    /// is checked to serve the same archive as the first one to respond, normally the archive's own URL, by
+    /// comparing the length and end of central directory. Mirrors that do not are never used again. Mirrors
+    /// may be URLs or local paths. For split archives, mirrors serve the last segment only.
+    pub fn mirrors(mut self, urls: impl IntoIterator<Item = impl Into<String>>) -> Self {
+        self.mirrors.extend(urls.into_iter().map(Into::into));
+        self
+    }
+
+    /// Sets how long to wait for a mirror, both for a response and for each chunk of a download, before trying
+    /// the next one, so a slow mirror does not hold up requests. Only applies if mirrors were added. Without a
+    /// timeout, only failing requests are retried.
+    pub fn mirror_timeout(mut self, timeout: Duration) -> Self {
+        self.mirror_timeout = Some(timeout);
+        self
+    }
+
+    /// Sets a persistent cache to serve byte ranges from, which may be shared with other instances.
+    pub fn cache(mut self, cache: RangeCache) -> Self {
+        self.cache = Some(cache);
+        self
+    }
+
+    /// Sets a rate limiter that range response bodies are received through, which may be shared with other
+    /// instances to limit them together.
+    pub fn rate_limit(mut self, limiter: RateLimiter) -> Self {
+        self.rate_limit = Some(limiter);
+        self
+    }
+
+    /// Sets a listener to receive progress updates from fetching the directory and downloading entries.
+    pub fn progress(mut self, listener: impl ProgressListener + 'static) -> Self {
+        self.progress = Some(Arc::new(listener));
+        self
+    }
+
+    /// Sets a token that cancels all operations of the RemoteZip, including fetching the directory. A new token
+    /// is created otherwise, see [`RemoteZip::cancellation_token`].
+    pub fn cancellation_token(mut self, token: CancellationToken) -> Self {
+        self.cancellation_token = token;
+        self
+    }
+
+    /// Sets a point in time after which all operations of the RemoteZip fail, including fetching the
+    /// directory. See [`RemoteZip::set_deadline`].
+    pub fn deadline(mut self, deadline: Instant) -> Self {
+        self.deadline = Some(deadline);
+        self
+    }
+
+    /// Fetches and parses the ZIP directory structure using the configured options.
+    ///
+    /// # Returns
+    ///
+    /// A Result containing either the initialized RemoteZip instance or an Error
+    pub async fn build(self) -> Result<RemoteZip, Error> {
+        let progress = self.progress.clone();
+        let cancellation = self.cancellation();
+        RemoteZip::load(self.source(), cancellation, progress).await
+    }
+
+    /// Fetches the end of central directory, then streams the records of the central directory as they are
+    /// downloaded and parsed, instead of waiting for the whole directory. Memory use stays bounded regardless of
+    /// the directory's size, which suits listing archives with millions of entries. Progress is reported for
+    /// the directory stage and the configured cancellation applies.
+    ///
+    /// # Returns
+    ///
+    /// A Result containing either a stream of the central directory records in archive order, which ends after
+    /// the first error, or an Error if the end of central directory could not be found
+    pub async fn stream_records(
+        self,
+    ) -> Result<BoxStream<'static, Result<CentralDirectoryRecord, Error>>, Error> {
+        let progress = self.progress.clone();
+        let cancellation = self.cancellation();
+        let source: SharedSource = Arc::new(GuardedSource::new(self.source(), cancellation));
+
+        let cde = directory::find_end(&source).await?;
+        let tracker = ProgressTracker::directory(progress, cde.directory_size as u64);
+        directory::stream_records(source, &cde, tracker).await
+    }
+
+    /// Fetches and parses the ZIP directory structure using the configured options, returning a RemoteZip
+    /// with a synchronous API. Must not be called from within an async context.
+    ///
+    /// # Returns
+    ///
+    /// A Result containing either the initialized blocking RemoteZip instance or an Error
+    #[cfg(feature = "blocking")]
+    pub fn build_blocking(self) -> Result<crate::blocking::RemoteZip, Error> {
+        crate::blocking::RemoteZip::load(self)
+    }
+
+    /// Creates the RemoteZip from a previously saved directory snapshot without any network access. The
+    /// archive is revalidated against the snapshot on the first range request.
+    ///
+    /// # Arguments
+    ///
+    /// * `snapshot` - The snapshot to restore, which may have been taken under a different URL
+    ///
+    /// # Returns
+    ///
+    /// The restored RemoteZip instance
+    #[cfg(feature = "serde")]
+    pub fn build_from_snapshot(self, snapshot: DirectorySnapshot) -> RemoteZip {
+        let progress = self.progress.clone();
+        let cancellation = self.cancellation();
+        let source = self.source();
+        source.expect_info(snapshot.info);
+
+        RemoteZip::from_parts(source, snapshot.records, cancellation, progress)
+    }
+
+    fn cancellation(&self) -> Arc<Cancellation> {
+        Arc::new(Cancellation::new(
+            self.cancellation_token.clone(),
+            self.deadline,
+        ))
+    }
+
+    fn source(self) -> SharedSource {
+        let stats = Arc::<StatsRecorder>::default();
+        let client = self.client.clone().unwrap_or_default();
+        let source_for = |url: &str| -> SharedSource {
+            match FileSource::path_for(url) {
+                Some(path) => Arc::new(FileSource::new(path).with_stats(stats.clone())),
+                None => Arc::new(
+                    HttpSource::new(
+                        url,
+                        client.clone(),
+                        self.cache.clone(),
+                        self.rate_limit.clone(),
+                    )
+                    .with_stats(stats.clone()),
+                ),
+            }
+        };
+
+        let last_segment = if self.mirrors.is_empty() {
+            source_for(&self.url)
+        } else {
+            let sources = std::iter::once(&self.url)
+                .chain(&self.mirrors)
+                .map(|x| source_for(x))
+                .collect();
+            Arc::new(MirrorSource::new(
+                sources,
+                self.mirror_timeout,
+                stats.clone(),
+            ))
+        };
+        if self.segments.is_empty() {
+            return last_segment;
+        }
+
+        let segments = self
+            .segments
+            .iter()
+            .map(|x| source_for(x))
+            .chain(std::iter::once(last_segment))
+            .collect();
+        Arc::new(SplitSource::new(segments, stats))
+    }
+}
@@ -0,0 +1,371 @@
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+    time::{Duration, SystemTime},
+};
+
+use bytes::Bytes;
+
+use crate::Error;
+
+/// The default size of the blocks a [`RangeCache`] stores.
+pub const DEFAULT_CACHE_BLOCK_SIZE: u64 = 64 * 1024;
+
+/// The subdirectory of the cache directory blocks are stored in, so nothing else in the directory is touched.
+const CACHE_SUBDIRECTORY: &str = "netzip-ranges";
+
+/// How old a temporary block file has to be before it is considered left over from an interrupted write rather
+/// than being written by another process sharing the cache.
+const STALE_TEMP_AGE: Duration = Duration::from_secs(60 * 60);
+
+/// A persistent on-disk cache of archive byte ranges, shared by any number of RemoteZip instances.
+///
+/// Data is stored in fixed size blocks keyed by the archive URL and its validators (ETag or Last-Modified
+/// header, plus length), so a changed archive never serves stale blocks. Archives without validators are
+/// not cached. Once the cache grows past its size limit, the least recently used blocks are evicted. Blocks
+/// are kept in a `netzip-ranges` subdirectory, so the cache directory may be shared with other files.
+///
+/// Cloning a RangeCache is cheap and the clones share their state.
+#[derive(Debug, Clone)]
+pub struct RangeCache {
+    inner: Arc<CacheInner>,
+}
+
+#[derive(Debug)]
+struct CacheInner {
+    directory: PathBuf,
+    max_size: u64,
+    block_size: u64,
+    state: Mutex<CacheState>,
+}
+
+#[derive(Debug, Default)]
+struct CacheState {
+    blocks: HashMap<PathBuf, CachedBlock>,
+    total_size: u64,
+    clock: u64,
+}
+
+#[derive(Debug)]
+struct CachedBlock {
+    size: u64,
+    last_used: u64,
+}
+
+impl RangeCache {
+    /// Opens or creates a cache in the given directory, using [`DEFAULT_CACHE_BLOCK_SIZE`].
+    ///
+    /// # Arguments
+    ///
+    /// * `directory` - The directory to store cached blocks in, created if missing
+    /// * `max_size` - The total size in bytes the cache may grow to before evicting blocks
+    ///
+    /// # Returns
+    ///
+    /// A Result containing either the RangeCache or an Error if the directory could not be read or created
+    pub fn open(directory: impl AsRef<Path>, max_size: u64) -> Result<Self, Error> {
+        Self::open_with_block_size(directory, max_size, DEFAULT_CACHE_BLOCK_SIZE)
+    }
+
+    /// Opens or creates a cache in the given directory with a custom block size. Blocks stored with a
+    /// different block size are kept apart and eventually evicted.
+    ///
+    /// # Arguments
+    ///
+    /// * `directory` - The directory to store cached blocks in, created if missing
+    /// * `max_size` - The total size in bytes the cache may grow to before evicting blocks
+    /// * `block_size` - The size in bytes of the blocks ranges are split into
+    ///
+    /// # Returns
+    ///
+    /// A Result containing either the RangeCache or an Error if the directory could not be read or created
+    pub fn open_with_block_size(
+        directory: impl AsRef<Path>,
+        max_size: u64,
+        block_size: u64,
+    ) -> Result<Self, Error> {
+        let directory = directory.as_ref().join(CACHE_SUBDIRECTORY);
+        let io_error = |e| Error::IoError(directory.display().to_string(), e);
+        std::fs::create_dir_all(&directory).map_err(io_error)?;
+
+        // Rebuild the LRU order from the modification times, which are bumped on every cache hit. Only files
+        // named like the ones the cache writes are considered.
+        let mut found = Vec::new();
+        for archive_dir in std::fs::read_dir(&directory).map_err(io_error)? {
+            let archive_dir = archive_dir.map_err(io_error)?;
+            if !is_key(&archive_dir.file_name().to_string_lossy()) {
+                continue;
+            }
+            let Ok(blocks) = std::fs::read_dir(archive_dir.path()) else {
+                continue;
+            };
+
+            for block in blocks.flatten() {
+                let Ok(metadata) = block.metadata() else {
+                    continue;
+                };
+                let modified = metadata.modified().unwrap_or(SystemTime::UNIX_EPOCH);
+                match BlockFile::parse(&block.file_name().to_string_lossy()) {
+                    Some(BlockFile::Block) => found.push((modified, block.path(), metadata.len())),
+                    // Leftover temporary file from an interrupted write, unless it may still be written
+                    Some(BlockFile::Temporary)
+                        if modified.elapsed().is_ok_and(|x| x > STALE_TEMP_AGE) =>
+                    {
+                        let _ = std::fs::remove_file(block.path());
+                    }
+                    _ => {}
+                }
+            }
+        }
+        found.sort();
+
+        let mut state = CacheState::default();
+        for (_, path, size) in found {
+            state.clock += 1;
+            state.total_size += size;
+            state.blocks.insert(
+                path,
+                CachedBlock {
+                    size,
+                    last_used: state.clock,
+                },
+            );
+        }
+
+        let cache = Self {
+            inner: Arc::new(CacheInner {
+                directory,
+                max_size,
+                block_size: block_size.max(1),
+                state: Mutex::new(state),
+            }),
+        };
+        cache.evict();
+
+        Ok(cache)
+    }
+
+    /// Returns the size of the blocks ranges are split into.
+    pub fn block_size(&self) -> u64 {
+        self.inner.block_size
+    }
+
+    /// Returns the total size in bytes of all cached blocks.
+    pub fn size(&self) -> u64 {
+        self.inner.state.lock().unwrap().total_size
+    }
+
+    /// Removes every cached block.
+    ///
+    /// # Returns
+    ///
+    /// A Result indicating whether the cache directory could be cleared
+    pub fn clear(&self) -> Result<(), Error> {
+        let mut state = self.inner.state.lock().unwrap();
+        for path in state.blocks.keys() {
+            let _ = std::fs::remove_file(path);
+        }
+        *state = CacheState::default();
+
+        Ok(())
+    }
+
+    /// Reads a block if it is cached with the expected size.
+    pub(crate) async fn get(&self, key: &str, block: u64, expected_size: u64) -> Option<Bytes> {
+        let path = self.block_path(key, block);
+        let data = tokio::fs::read(&path).await.ok()?;
+        if data.len() as u64 != expected_size {
+            return None;
+        }
+
+        self.touch(&path, data.len() as u64);
+        if let Ok(file) = std::fs::File::options().write(true).open(&path) {
+            let _ = file.set_modified(SystemTime::now());
+        }
+
+        Some(Bytes::from(data))
+    }
+
+    /// Stores a block, evicting old blocks if the cache grows too large. Failures are ignored, as the
+    /// cache only ever saves requests.
+    pub(crate) async fn put(&self, key: &str, block: u64, data: &[u8]) {
+        let path = self.block_path(key, block);
+        let temp_path = path.with_extension(format!("{}.tmp", std::process::id()));
+        let written = async {
+            tokio::fs::create_dir_all(self.inner.directory.join(key)).await?;
+            tokio::fs::write(&temp_path, data).await?;
+            tokio::fs::rename(&temp_path, &path).await
+        }
+        .await;
+
+        if written.is_ok() {
+            self.touch(&path, data.len() as u64);
+            self.evict();
+        } else {
+            let _ = tokio::fs::remove_file(&temp_path).await;
+        }
+    }
+
+    /// Builds the key under which blocks of an archive are stored.
+    pub(crate) fn key(&self, url: &str, validator: &str, length: u64) -> String {
+        // FNV-1a, which unlike the standard library's hasher is guaranteed to be stable across releases
+        let mut hash: u64 = 0xcbf29ce484222325;
+        let identity = format!("{url}\n{validator}\n{length}\n{}", self.inner.block_size);
+        for byte in identity.bytes() {
+            hash ^= byte as u64;
+            hash = hash.wrapping_mul(0x100000001b3);
+        }
+
+        format!("{hash:016x}")
+    }
+
+    fn block_path(&self, key: &str, block: u64) -> PathBuf {
+        self.inner.directory.join(key).join(format!("{block}.blk"))
+    }
+
+    fn touch(&self, path: &Path, size: u64) {
+        let mut state = self.inner.state.lock().unwrap();
+        state.clock += 1;
+        let clock = state.clock;
+        let previous = state.blocks.insert(
+            path.to_path_buf(),
+            CachedBlock {
+                size,
+                last_used: clock,
+            },
+        );
+        state.total_size = state.total_size - previous.map_or(0, |x| x.size) + size;
+    }
+
+    fn evict(&self) {
+        let mut state = self.inner.state.lock().unwrap();
+        if state.total_size <= self.inner.max_size {
+            return;
+        }
+
+        let mut by_age: Vec<(u64, PathBuf)> = state
+            .blocks
+            .iter()
+            .map(|(path, block)| (block.last_used, path.clone()))
+            .collect();
+        by_age.sort();
+
+        for (_, path) in by_age {
+            if state.total_size <= self.inner.max_size {
+                break;
+            }
+            if let Some(block) = state.blocks.remove(&path) {
+                state.total_size -= block.size;
+                let _ = std::fs::remove_file(&path);
+            }
+        }
+    }
+}
+
+/// The kinds of files the cache writes into an archive's directory.
+enum BlockFile {
+    /// A stored block, named `<block>.blk`
+    Block,
+    /// A block being written, named `<block>.<pid>.tmp`
+    Temporary,
+}
+
+impl BlockFile {
+    fn parse(name: &str) -> Option<Self> {
+        let is_number = |x: &str| !x.is_empty() && x.bytes().all(|x| x.is_ascii_digit());
+        match name.split('.').collect::<Vec<_>>()[..] {
+            [block, "blk"] if is_number(block) => Some(Self::Block),
+            [block, pid, "tmp"] if is_number(block) && is_number(pid) => Some(Self::Temporary),
+            _ => None,
+        }
+    }
+}
+
+/// Returns whether a directory name is a key as built by [`RangeCache::key`].
+fn is_key(name: &str) -> bool {
+    name.len() == 16 && name.bytes().all(|x| x.is_ascii_hexdigit())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let path = std::env::temp_dir().join(format!("netzip-cache-{name}-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&path);
+        path
+    }
+
+    #[tokio::test]
+    async fn stores_and_evicts_blocks() {
+        let directory = temp_dir("evict");
+        let cache = RangeCache::open_with_block_size(&directory, 10, 4).unwrap();
+        let key = cache.key("http://example.com/a.zip", "\"etag\"", 100);
+
+        cache.put(&key, 0, b"abcd").await;
+        cache.put(&key, 1, b"efgh").await;
+        assert_eq!(cache.get(&key, 0, 4).await.as_deref(), Some(&b"abcd"[..]));
+        assert_eq!(cache.get(&key, 1, 3).await, None);
+
+        // Block 1 is now the least recently used and has to make room
+        cache.put(&key, 2, b"ijkl").await;
+        assert_eq!(cache.size(), 8);
+        assert_eq!(cache.get(&key, 1, 4).await, None);
+        assert!(cache.get(&key, 0, 4).await.is_some());
+
+        let reopened = RangeCache::open_with_block_size(&directory, 10, 4).unwrap();
+        assert_eq!(reopened.size(), 8);
+        assert!(reopened.get(&key, 2, 4).await.is_some());
+
+        reopened.clear().unwrap();
+        assert_eq!(reopened.size(), 0);
+        let _ = std::fs::remove_dir_all(&directory);
+    }
+
+    #[tokio::test]
+    async fn leaves_foreign_files_alone() {
+        let directory = temp_dir("foreign");
+        let cache = RangeCache::open_with_block_size(&directory, 100, 4).unwrap();
+        let key = cache.key("http://example.com/a.zip", "\"etag\"", 100);
+        cache.put(&key, 0, b"abcd").await;
+
+        let blocks = directory.join(CACHE_SUBDIRECTORY).join(&key);
+        let foreign = [
+            directory.join("notes.txt"),
+            directory.join("photos").join("1.blk"),
+            blocks.join("readme.md"),
+        ];
+        std::fs::create_dir_all(directory.join("photos")).unwrap();
+        for path in &foreign {
+            std::fs::write(path, b"keep").unwrap();
+        }
+        let fresh_temp = blocks.join("1.4242.tmp");
+        let stale_temp = blocks.join("2.4242.tmp");
+        std::fs::write(&fresh_temp, b"ef").unwrap();
+        std::fs::File::create(&stale_temp)
+            .unwrap()
+            .set_modified(SystemTime::now() - 2 * STALE_TEMP_AGE)
+            .unwrap();
+
+        let reopened = RangeCache::open_with_block_size(&directory, 100, 4).unwrap();
+        assert_eq!(reopened.size(), 4);
+        assert!(foreign.iter().all(|x| x.exists()));
+        assert!(fresh_temp.exists());
+        assert!(!stale_temp.exists());
+
+        RangeCache::open_with_block_size(&directory, 0, 4).unwrap();
+        assert!(foreign.iter().all(|x| x.exists()));
+        let _ = std::fs::remove_dir_all(&directory);
+    }
+
+    #[test]
+    fn keys_depend_on_validators() {
+        let cache = RangeCache::open(temp_dir("keys"), 0).unwrap();
+        let a = cache.key("http://example.com/a.zip", "\"1\"", 100);
+
+        assert_eq!(a, cache.key("http://example.com/a.zip", "\"1\"", 100));
+        assert_ne!(a, cache.key("http://example.com/a.zip", "\"2\"", 100));
+        assert_ne!(a, cache.key("http://example.com/a.zip", "\"1\"", 101));
+    }
+}
@@ -0,0 +1,97 @@
+use std::{future::Future, sync::Mutex, time::Instant};
+
+use tokio_util::sync::CancellationToken;
+
+use crate::Error;
+
+/// The cancellation token and deadline bounding every operation of a RemoteZip.
+#[derive(Debug, Default)]
+pub(crate) struct Cancellation {
+    token: CancellationToken,
+    deadline: Mutex<Option<Instant>>,
+}
+
+impl Cancellation {
+    pub(crate) fn new(token: CancellationToken, deadline: Option<Instant>) -> Self {
+        Self {
+            token,
+            deadline: Mutex::new(deadline),
+        }
+    }
+
+    pub(crate) fn token(&self) -> &CancellationToken {
+        &self.token
+    }
+
+    pub(crate) fn deadline(&self) -> Option<Instant> {
+        *self.deadline.lock().unwrap()
+    }
+
+    pub(crate) fn set_deadline(&self, deadline: Option<Instant>) {
+        *self.deadline.lock().unwrap() = deadline;
+    }
+
+    /// Creates the cancellation of an archive opened from this one, which is cancelled along with it and
+    /// starts out with the same deadline.
+    pub(crate) fn child(&self) -> Self {
+        Self::new(self.token.child_token(), self.deadline())
+    }
+
+    /// Runs the future to completion, unless the token is cancelled or the deadline passes first. Both are
+    /// checked before the future is polled, so no work is started once either happened.
+    pub(crate) async fn run<F: Future>(&self, url: &str, future: F) -> Result<F::Output, Error> {
+        let deadline = self.deadline();
+        if deadline.is_some_and(|x| x <= Instant::now()) {
+            return Err(Error::DeadlineExceeded(url.into()));
+        }
+
+        let expired = async {
+            match deadline {
+                Some(deadline) => tokio::time::sleep_until(deadline.into()).await,
+                None => std::future::pending().await,
+            }
+        };
+
+        tokio::select! {
+            biased;
+            _ = self.token.cancelled() => Err(Error::Cancelled(url.into())),
+            _ = expired => Err(Error::DeadlineExceeded(url.into())),
+            output = future => Ok(output),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn stops_before_work_starts() {
+        let cancellation = Cancellation::default();
+        assert_eq!(cancellation.run("a.zip", async { 1 }).await.unwrap(), 1);
+
+        cancellation.set_deadline(Some(Instant::now()));
+        assert!(matches!(
+            cancellation.run("a.zip", async { 1 }).await,
+            Err(Error::DeadlineExceeded(_))
+        ));
+
+        cancellation.set_deadline(Some(Instant::now() + Duration::from_millis(20)));
+        assert!(matches!(
+            cancellation
+                .run("a.zip", std::future::pending::<()>())
+                .await,
+            Err(Error::DeadlineExceeded(_))
+        ));
+
+        let child = cancellation.child();
+        child.set_deadline(None);
+        cancellation.token().cancel();
+        assert!(matches!(
+            child.run("a.zip!/b.zip", async { 1 }).await,
+            Err(Error::Cancelled(_))
+        ));
+    }
+}
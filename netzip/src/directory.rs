@@ -0,0 +1,322 @@
+use std::collections::VecDeque;
+
+use bytes::{Buf, Bytes, BytesMut};
+use futures_util::{
+    StreamExt,
+    stream::{self, BoxStream},
+};
+use netzip_parser::{CentralDirectoryEnd, CentralDirectoryRecord, ZipError};
+
+use crate::{Error, progress::ProgressTracker, source::SharedSource};
+
+/// Fetches and parses the end of central directory record, searching through a possible archive comment if it
+/// is not at the very end. Fails if the archive spans a different number of disks than the source has segments,
+/// as disks would otherwise be mapped to the wrong segments.
+pub(crate) async fn find_end(source: &SharedSource) -> Result<CentralDirectoryEnd, Error> {
+    let min_cde_bytes = source
+        .fetch_tail(netzip_parser::EOCD_MIN_SIZE as u64)
+        .await?;
+
+    let cde = if let Ok(min_out) = CentralDirectoryEnd::parse(&min_cde_bytes) {
+        min_out
+    } else {
+        // There might be a comment, retry with an offset and search for the EOCD
+        let cde_haystack = source
+            .fetch_tail((netzip_parser::EOCD_MIN_SIZE + 1024) as u64)
+            .await?;
+
+        CentralDirectoryEnd::find_and_parse(&cde_haystack).map_err(|e| {
+            event!(debug, error = %e, "Failed to find end of central directory");
+            Error::ParserError(source.url().into(), e)
+        })?
+    };
+    let disks = cde.disk_number as usize + 1;
+    if disks != source.disk_count() {
+        return Err(Error::SegmentCountMismatch(
+            source.url().into(),
+            disks,
+            source.disk_count(),
+        ));
+    }
+    event!(
+        debug,
+        entries = cde.record_count_total,
+        offset = cde.central_directory_offset,
+        size = cde.directory_size,
+        "Found end of central directory"
+    );
+
+    Ok(cde)
+}
+
+/// Returns the offset of the central directory described by `cde`, which is relative to the disk it starts on.
+pub(crate) async fn directory_offset(
+    source: &SharedSource,
+    cde: &CentralDirectoryEnd,
+) -> Result<u64, Error> {
+    Ok(source.disk_offset(cde.disk_start).await? + cde.central_directory_offset as u64)
+}
+
+/// Downloads the central directory described by `cde`, reporting progress as its bytes arrive.
+pub(crate) async fn fetch_directory(
+    source: &SharedSource,
+    cde: &CentralDirectoryEnd,
+    progress: &mut ProgressTracker,
+) -> Result<Bytes, Error> {
+    let length = cde.directory_size as u64;
+    let mut body = source
+        .fetch_stream(directory_offset(source, cde).await?, length)
+        .await?;
+
+    let mut directory = BytesMut::with_capacity(length as usize);
+    while let Some(chunk) = body.next().await {
+        let chunk = chunk?;
+        directory.extend_from_slice(&chunk);
+        if directory.len() as u64 > length {
+            break;
+        }
+        progress.advance(chunk.len() as u64, 0);
+    }
+
+    if directory.len() as u64 != length {
+        return Err(Error::IncompleteResponse(
+            source.url().into(),
+            length,
+            directory.len() as u64,
+        ));
+    }
+
+    Ok(directory.freeze())
+}
+
+/// Streams the records of the central directory described by `cde`, parsing each one as soon as its bytes
+/// have arrived. Only the records not yet consumed and a partial record are held in memory.
+pub(crate) async fn stream_records(
+    source: SharedSource,
+    cde: &CentralDirectoryEnd,
+    progress: ProgressTracker,
+) -> Result<BoxStream<'static, Result<CentralDirectoryRecord, Error>>, Error> {
+    let body = source
+        .fetch_stream(
+            directory_offset(&source, cde).await?,
+            cde.directory_size as u64,
+        )
+        .await?;
+
+    Ok(parse_stream(
+        source.url().into(),
+        body,
+        cde.clone(),
+        progress,
+    ))
+}
+
+/// Parses the records of the central directory described by `cde` from a stream of its bytes.
+fn parse_stream(
+    url: String,
+    body: BoxStream<'static, Result<Bytes, Error>>,
+    cde: CentralDirectoryEnd,
+    progress: ProgressTracker,
+) -> BoxStream<'static, Result<CentralDirectoryRecord, Error>> {
+    let parser = StreamingParser {
+        url,
+        body,
+        length: cde.directory_size as u64,
+        cde,
+        received: 0,
+        buffer: BytesMut::new(),
+        parsed: VecDeque::new(),
+        record_count: 0,
+        error: None,
+        finished: false,
+        progress,
+    };
+
+    stream::unfold(parser, |mut parser| async move {
+        parser.next().await.map(|x| (x, parser))
+    })
+    .boxed()
+}
+
+/// The state of a central directory being parsed from a response body.
+struct StreamingParser {
+    url: String,
+    body: BoxStream<'static, Result<Bytes, Error>>,
+    length: u64,
+    cde: CentralDirectoryEnd,
+    received: u64,
+    buffer: BytesMut,
+    parsed: VecDeque<CentralDirectoryRecord>,
+    record_count: usize,
+    error: Option<Error>,
+    finished: bool,
+    progress: ProgressTracker,
+}
+
+impl StreamingParser {
+    /// Returns the next record, reading more of the body as needed. The stream ends after the first error.
+    async fn next(&mut self) -> Option<Result<CentralDirectoryRecord, Error>> {
+        loop {
+            if let Some(record) = self.parsed.pop_front() {
+                return Some(Ok(record));
+            }
+            if let Some(e) = self.error.take() {
+                self.finished = true;
+                return Some(Err(e));
+            }
+            if self.finished {
+                return None;
+            }
+
+            match self.body.next().await {
+                Some(Ok(chunk)) => {
+                    self.received += chunk.len() as u64;
+                    self.progress.advance(chunk.len() as u64, 0);
+                    self.buffer.extend_from_slice(&chunk);
+                    if let Err(e) = self.parse_buffered() {
+                        self.error = Some(e);
+                    }
+                }
+                Some(Err(e)) => self.error = Some(e),
+                None => self.finish(),
+            }
+        }
+    }
+
+    /// Parses all complete records in the buffer, leaving a partial record at its end in place.
+    fn parse_buffered(&mut self) -> Result<(), Error> {
+        while self.buffer.len() >= netzip_parser::CDR_MIN_SIZE {
+            let record_length = CentralDirectoryRecord::record_length(&self.buffer)
+                .map_err(|e| self.tail_error(e))?;
+            if self.buffer.len() < record_length {
+                break;
+            }
+
+            let record = CentralDirectoryRecord::parse(&self.buffer[..record_length], false)
+                .map_err(|e| self.tail_error(e))?;
+            self.buffer.advance(record_length);
+            self.parsed.push_back(record);
+            self.record_count += 1;
+        }
+
+        Ok(())
+    }
+
+    /// Reports a record at the start of the buffer failing to parse, which is padding rather than a damaged
+    /// record if it does not look like one.
+    fn tail_error(&self, e: ZipError) -> Error {
+        let e = self
+            .cde
+            .directory_tail_error(self.record_count, &self.buffer, e);
+        Error::ParserError(self.url.clone(), e)
+    }
+
+    fn finish(&mut self) {
+        if self.received != self.length {
+            self.error = Some(Error::IncompleteResponse(
+                self.url.clone(),
+                self.length,
+                self.received,
+            ));
+        } else if !self.buffer.is_empty() {
+            // The same error parsing the rest as a whole directory would report
+            let e = CentralDirectoryRecord::parse(&self.buffer, true)
+                .err()
+                .unwrap_or(ZipError::MissingData("CDR Variable Length Fields"));
+            self.error = Some(self.tail_error(e));
+        } else if let Err(e) = self.cde.check_record_count(self.record_count) {
+            self.error = Some(Error::ParserError(self.url.clone(), e));
+        } else {
+            event!(
+                debug,
+                entries = self.record_count,
+                "Parsed central directory"
+            );
+            self.progress.directory_parsed(self.record_count);
+            self.finished = true;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use futures_util::TryStreamExt;
+
+    use super::*;
+    use crate::source::MemorySource;
+
+    #[tokio::test]
+    async fn parses_records_split_across_chunks() {
+        let archive = Bytes::from_static(include_bytes!("../tests/fixtures/basic.zip"));
+        let source: SharedSource = Arc::new(MemorySource::new("basic.zip".into(), archive.clone()));
+        let cde = find_end(&source).await.unwrap();
+        let start = cde.central_directory_offset as usize;
+        let directory = archive.slice(start..start + cde.directory_size as usize);
+        let expected = CentralDirectoryRecord::parse_many(&directory).unwrap();
+
+        let progress = || ProgressTracker::directory(None, directory.len() as u64);
+        let chunks: Vec<_> = directory.chunks(7).map(Bytes::copy_from_slice).collect();
+        let body = stream::iter(chunks.clone().into_iter().map(Ok)).boxed();
+        let records: Vec<_> = parse_stream("basic.zip".into(), body, cde.clone(), progress())
+            .try_collect()
+            .await
+            .unwrap();
+        assert_eq!(records, expected);
+
+        let mut truncated = chunks;
+        truncated.pop();
+        let truncated = stream::iter(truncated.into_iter().map(Ok)).boxed();
+        let results: Vec<_> = parse_stream("basic.zip".into(), truncated, cde.clone(), progress())
+            .collect()
+            .await;
+        assert_eq!(
+            results.iter().filter(|x| x.is_ok()).count(),
+            expected.len() - 1
+        );
+        assert!(matches!(
+            results.last(),
+            Some(Err(Error::IncompleteResponse(..)))
+        ));
+
+        let miscounted = CentralDirectoryEnd {
+            record_count_total: cde.record_count_total + 1,
+            ..cde.clone()
+        };
+        let body = stream::iter([Ok(directory.clone())]).boxed();
+        let results: Vec<_> = parse_stream("basic.zip".into(), body, miscounted, progress())
+            .collect()
+            .await;
+        assert!(matches!(
+            results.last(),
+            Some(Err(Error::ParserError(
+                _,
+                ZipError::RecordCountMismatch(..)
+            )))
+        ));
+
+        // Padding in place of the last record, shorter and longer than a record's fixed part, is reported
+        // like parsing the whole directory at once does
+        let last_record = expected.last().unwrap().to_bytes().unwrap().len();
+        for padding in [8, 100] {
+            let mut padded = directory[..directory.len() - last_record].to_vec();
+            padded.resize(padded.len() + padding, 0);
+            let cde = CentralDirectoryEnd {
+                directory_size: padded.len() as u32,
+                ..cde.clone()
+            };
+            let expected = CentralDirectoryRecord::parse_directory(&padded, &cde).unwrap_err();
+            assert_eq!(expected, ZipError::ExtraneousData("Central Directory"));
+
+            let chunks: Vec<_> = padded.chunks(7).map(Bytes::copy_from_slice).collect();
+            let body = stream::iter(chunks.into_iter().map(Ok)).boxed();
+            let results: Vec<_> = parse_stream("basic.zip".into(), body, cde, progress())
+                .collect()
+                .await;
+            assert!(
+                matches!(results.last(), Some(Err(Error::ParserError(_, e))) if *e == expected)
+            );
+        }
+    }
+}
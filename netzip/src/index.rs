@@ -0,0 +1,565 @@
+use std::{
+    io::{Read, SeekFrom, Write},
+    path::Path,
+    pin::Pin,
+    sync::Arc,
+    task::{Context, Poll, ready},
+};
+
+use bytes::Bytes;
+use flate2::{Compression, read::DeflateDecoder, write::DeflateEncoder};
+use futures_util::future::BoxFuture;
+use tokio::io::{AsyncRead, AsyncSeek, ReadBuf};
+
+use crate::{
+    Error,
+    inflate::{Inflater, Status},
+    source::SharedSource,
+};
+
+const INDEX_MAGIC: [u8; 4] = *b"NZDI";
+const INDEX_VERSION: u8 = 1;
+/// The size of the history back-references may refer to, and so the largest window an access point needs. Deflate64
+/// doubles it to 64 KiB.
+const DEFLATE_WINDOW_SIZE: u64 = 32 * 1024;
+
+/// The default distance in uncompressed bytes between access points of a [`DeflateIndex`].
+pub const DEFAULT_INDEX_SPAN: u64 = 4 * 1024 * 1024;
+
+/// A position inside a Deflate stream from which decoding can be resumed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AccessPoint {
+    /// Offset in bits from the start of the entry's compressed data
+    pub bit_offset: u64,
+    /// Offset in bytes from the start of the entry's uncompressed data
+    pub uncompressed_offset: u64,
+    /// The uncompressed data preceding the access point, which back-references may refer to
+    pub window: Vec<u8>,
+}
+
+/// An index of access points into a Deflate compressed entry, in the style of zlib's zran example.
+///
+/// Building the index requires decompressing the entry once. Afterwards, reads at any uncompressed offset
+/// only need the compressed span starting at the nearest preceding access point. The index can be saved
+/// to disk and loaded again, and remembers the size and CRC-32 of the entry it was built for.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DeflateIndex {
+    deflate64: bool,
+    compressed_size: u64,
+    uncompressed_size: u64,
+    crc32: u32,
+    points: Vec<AccessPoint>,
+}
+
+impl DeflateIndex {
+    /// Returns the access points of the index, ordered by offset. The first point is always at offset 0.
+    pub fn points(&self) -> &[AccessPoint] {
+        &self.points
+    }
+
+    /// Returns the uncompressed size of the indexed entry.
+    pub fn uncompressed_size(&self) -> u64 {
+        self.uncompressed_size
+    }
+
+    /// Writes the index in a compact binary format, compressing the windows.
+    ///
+    /// # Arguments
+    ///
+    /// * `writer` - The writer to serialize the index to
+    ///
+    /// # Returns
+    ///
+    /// A Result indicating whether the index was written successfully
+    pub fn write_to<W: Write>(&self, mut writer: W) -> std::io::Result<()> {
+        writer.write_all(&INDEX_MAGIC)?;
+        writer.write_all(&[INDEX_VERSION, self.deflate64 as u8])?;
+        writer.write_all(&self.compressed_size.to_le_bytes())?;
+        writer.write_all(&self.uncompressed_size.to_le_bytes())?;
+        writer.write_all(&self.crc32.to_le_bytes())?;
+        writer.write_all(&(self.points.len() as u64).to_le_bytes())?;
+
+        for point in &self.points {
+            let mut encoder = DeflateEncoder::new(Vec::new(), Compression::default());
+            encoder.write_all(&point.window)?;
+            let window = encoder.finish()?;
+
+            writer.write_all(&point.bit_offset.to_le_bytes())?;
+            writer.write_all(&point.uncompressed_offset.to_le_bytes())?;
+            writer.write_all(&(window.len() as u64).to_le_bytes())?;
+            writer.write_all(&window)?;
+        }
+
+        writer.flush()
+    }
+
+    /// Reads an index previously written with [`DeflateIndex::write_to`].
+    ///
+    /// # Arguments
+    ///
+    /// * `reader` - The reader to deserialize the index from
+    ///
+    /// # Returns
+    ///
+    /// A Result containing either the DeflateIndex or an Error if the data is not a valid index
+    pub fn read_from<R: Read>(mut reader: R) -> Result<Self, Error> {
+        let invalid = |e: std::io::Error| Error::InvalidIndex(e.to_string());
+
+        let mut header = [0u8; 6];
+        reader.read_exact(&mut header).map_err(invalid)?;
+        if header[..4] != INDEX_MAGIC || header[4] != INDEX_VERSION {
+            return Err(Error::InvalidIndex("unrecognized header".into()));
+        }
+
+        let compressed_size = read_u64(&mut reader).map_err(invalid)?;
+        let uncompressed_size = read_u64(&mut reader).map_err(invalid)?;
+        let mut crc32 = [0u8; 4];
+        reader.read_exact(&mut crc32).map_err(invalid)?;
+        let point_count = read_u64(&mut reader).map_err(invalid)?;
+        let deflate64 = header[5] != 0;
+        let max_window = DEFLATE_WINDOW_SIZE << deflate64 as u8;
+
+        let mut points = Vec::new();
+        for _ in 0..point_count {
+            let bit_offset = read_u64(&mut reader).map_err(invalid)?;
+            let uncompressed_offset = read_u64(&mut reader).map_err(invalid)?;
+            let window_length = read_u64(&mut reader).map_err(invalid)?;
+
+            // Windows are inflated one byte past the limit, so an oversized window is rejected without
+            // decompressing all of it
+            let mut window = Vec::new();
+            DeflateDecoder::new((&mut reader).take(window_length))
+                .take(max_window + 1)
+                .read_to_end(&mut window)
+                .map_err(invalid)?;
+            if window.len() as u64 > max_window {
+                return Err(Error::InvalidIndex(format!(
+                    "window larger than {max_window} bytes"
+                )));
+            }
+
+            points.push(AccessPoint {
+                bit_offset,
+                uncompressed_offset,
+                window,
+            });
+        }
+
+        let index = Self {
+            deflate64,
+            compressed_size,
+            uncompressed_size,
+            crc32: u32::from_le_bytes(crc32),
+            points,
+        };
+        if index
+            .points
+            .first()
+            .is_none_or(|x| x.uncompressed_offset != 0)
+            || !index.points.is_sorted_by_key(|x| x.uncompressed_offset)
+            || !index.points.is_sorted_by_key(|x| x.bit_offset)
+            || index.points.last().is_some_and(|x| {
+                x.uncompressed_offset > uncompressed_size || x.bit_offset > compressed_size * 8
+            })
+        {
+            return Err(Error::InvalidIndex(
+                "access points out of order or range".into(),
+            ));
+        }
+
+        Ok(index)
+    }
+
+    /// Saves the index to a file.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - The path of the file to create or overwrite
+    ///
+    /// # Returns
+    ///
+    /// A Result indicating whether the index was saved successfully
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<(), Error> {
+        let path = path.as_ref();
+        std::fs::File::create(path)
+            .and_then(|x| self.write_to(std::io::BufWriter::new(x)))
+            .map_err(|e| Error::IoError(path.display().to_string(), e))
+    }
+
+    /// Loads an index from a file.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - The path of a file written by [`DeflateIndex::save`]
+    ///
+    /// # Returns
+    ///
+    /// A Result containing either the DeflateIndex or an Error if the file could not be read or is invalid
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, Error> {
+        let path = path.as_ref();
+        let file =
+            std::fs::File::open(path).map_err(|e| Error::IoError(path.display().to_string(), e))?;
+        Self::read_from(std::io::BufReader::new(file))
+    }
+
+    /// Checks whether the index was built for an entry with the given properties.
+    pub(crate) fn matches(&self, compressed_size: u64, uncompressed_size: u64, crc32: u32) -> bool {
+        self.compressed_size == compressed_size
+            && self.uncompressed_size == uncompressed_size
+            && self.crc32 == crc32
+    }
+}
+
+fn read_u64<R: Read>(reader: &mut R) -> std::io::Result<u64> {
+    let mut buf = [0u8; 8];
+    reader.read_exact(&mut buf)?;
+    Ok(u64::from_le_bytes(buf))
+}
+
+/// Incrementally builds a [`DeflateIndex`] from the compressed data of an entry.
+pub(crate) struct IndexBuilder {
+    inflater: Inflater,
+    span: u64,
+    crc: flate2::Crc,
+    points: Vec<AccessPoint>,
+    scratch: Vec<u8>,
+    finished: bool,
+}
+
+impl IndexBuilder {
+    pub(crate) fn new(deflate64: bool, span: u64) -> Self {
+        Self {
+            inflater: Inflater::new(deflate64),
+            span: span.max(1),
+            crc: flate2::Crc::new(),
+            points: vec![AccessPoint {
+                bit_offset: 0,
+                uncompressed_offset: 0,
+                window: Vec::new(),
+            }],
+            scratch: Vec::new(),
+            finished: false,
+        }
+    }
+
+    /// Decodes the next chunk of compressed data, recording access points at block boundaries.
+    pub(crate) fn feed(&mut self, chunk: &[u8]) -> Result<(), &'static str> {
+        if self.finished {
+            return Ok(());
+        }
+
+        self.inflater.feed(chunk);
+        loop {
+            self.scratch.clear();
+            let status = self.inflater.inflate(&mut self.scratch)?;
+            self.crc.update(&self.scratch);
+
+            match status {
+                Status::NeedInput => return Ok(()),
+                Status::Finished => {
+                    self.finished = true;
+                    return Ok(());
+                }
+                Status::BlockBoundary => {
+                    let last_offset = self.points.last().map_or(0, |x| x.uncompressed_offset);
+                    if self.inflater.total_out() - last_offset >= self.span {
+                        self.points.push(AccessPoint {
+                            bit_offset: self.inflater.bit_offset(),
+                            uncompressed_offset: self.inflater.total_out(),
+                            window: self.inflater.window().to_vec(),
+                        });
+                    }
+                }
+            }
+        }
+    }
+
+    /// Completes the index, verifying the decoded data against the entry's size and CRC-32.
+    pub(crate) fn finish(
+        self,
+        compressed_size: u64,
+        uncompressed_size: u64,
+        crc32: u32,
+    ) -> Result<DeflateIndex, &'static str> {
+        if !self.finished {
+            return Err("compressed data ended unexpectedly");
+        }
+        if self.inflater.total_out() != uncompressed_size || self.crc.sum() != crc32 {
+            return Err("decompressed data does not match the entry's size and CRC-32");
+        }
+
+        Ok(DeflateIndex {
+            deflate64: self.inflater.window_size() > 32768,
+            compressed_size,
+            uncompressed_size,
+            crc32,
+            points: self.points,
+        })
+    }
+}
+
+/// A seekable reader over the contents of a Deflate compressed entry of a remote archive.
+///
+/// Reading at a position decodes the span between the preceding and following access points of the
+/// entry's [`DeflateIndex`], fetching only the compressed bytes of that span.
+/// Obtained from [`RemoteZip::open_indexed`](crate::RemoteZip::open_indexed).
+pub struct IndexedEntryReader {
+    source: SharedSource,
+    data_offset: u64,
+    index: Arc<DeflateIndex>,
+    position: u64,
+    buffer: Bytes,
+    buffer_start: u64,
+    pending: Option<(usize, BoxFuture<'static, Result<Bytes, Error>>)>,
+}
+
+impl IndexedEntryReader {
+    pub(crate) fn new(source: SharedSource, data_offset: u64, index: Arc<DeflateIndex>) -> Self {
+        Self {
+            source,
+            data_offset,
+            index,
+            position: 0,
+            buffer: Bytes::new(),
+            buffer_start: 0,
+            pending: None,
+        }
+    }
+
+    /// Returns the uncompressed length of the entry in bytes.
+    pub fn len(&self) -> u64 {
+        self.index.uncompressed_size
+    }
+
+    /// Returns true if the entry is empty.
+    pub fn is_empty(&self) -> bool {
+        self.index.uncompressed_size == 0
+    }
+
+    /// Returns the current read position within the uncompressed entry.
+    pub fn position(&self) -> u64 {
+        self.position
+    }
+
+    fn buffered(&self) -> Option<&[u8]> {
+        let buffer_end = self.buffer_start + self.buffer.len() as u64;
+        if self.position >= self.buffer_start && self.position < buffer_end {
+            Some(&self.buffer[(self.position - self.buffer_start) as usize..])
+        } else {
+            None
+        }
+    }
+
+    /// Fetches and decodes the span of uncompressed data starting at the given access point.
+    fn decode_span(&self, point_index: usize) -> BoxFuture<'static, Result<Bytes, Error>> {
+        let source = self.source.clone();
+        let data_offset = self.data_offset;
+        let index = self.index.clone();
+
+        Box::pin(async move {
+            let point = &index.points[point_index];
+            let (fetch_end, span_end) = match index.points.get(point_index + 1) {
+                Some(next) => (next.bit_offset.div_ceil(8), next.uncompressed_offset),
+                None => (index.compressed_size, index.uncompressed_size),
+            };
+            let fetch_start = point.bit_offset / 8;
+
+            let compressed = source
+                .fetch(data_offset + fetch_start, fetch_end - fetch_start)
+                .await?;
+
+            let mut inflater = Inflater::resume(
+                index.deflate64,
+                point.bit_offset,
+                point.uncompressed_offset,
+                point.window.clone(),
+            );
+            inflater.feed(&compressed);
+
+            let mut out = Vec::with_capacity((span_end - point.uncompressed_offset) as usize);
+            while inflater.total_out() < span_end {
+                let status = inflater
+                    .inflate(&mut out)
+                    .map_err(|e| Error::DecompressionError(source.url().into(), e.into()))?;
+                if status != Status::BlockBoundary {
+                    break;
+                }
+            }
+            if inflater.total_out() < span_end {
+                return Err(Error::DecompressionError(
+                    source.url().into(),
+                    "compressed span ended before the next access point".into(),
+                ));
+            }
+
+            out.truncate((span_end - point.uncompressed_offset) as usize);
+            Ok(Bytes::from(out))
+        })
+    }
+}
+
+impl AsyncRead for IndexedEntryReader {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<std::io::Result<()>> {
+        let this = self.get_mut();
+        if this.position >= this.index.uncompressed_size || buf.remaining() == 0 {
+            return Poll::Ready(Ok(()));
+        }
+
+        if this.buffered().is_none() {
+            let point_index = this
+                .index
+                .points
+                .partition_point(|x| x.uncompressed_offset <= this.position)
+                - 1;
+            if this
+                .pending
+                .as_ref()
+                .is_some_and(|(pending_index, _)| *pending_index != point_index)
+            {
+                this.pending = None;
+            }
+
+            if this.pending.is_none() {
+                this.pending = Some((point_index, this.decode_span(point_index)));
+            }
+
+            let Some((_, decode)) = &mut this.pending else {
+                unreachable!("A pending decode was just created");
+            };
+            let result = ready!(decode.as_mut().poll(cx));
+            this.buffer_start = this.index.points[point_index].uncompressed_offset;
+            this.pending = None;
+            this.buffer = result.map_err(std::io::Error::other)?;
+        }
+
+        let available = this.buffered().unwrap_or_default();
+        let count = available.len().min(buf.remaining());
+        buf.put_slice(&available[..count]);
+        this.position += count as u64;
+
+        Poll::Ready(Ok(()))
+    }
+}
+
+impl AsyncSeek for IndexedEntryReader {
+    fn start_seek(self: Pin<&mut Self>, position: SeekFrom) -> std::io::Result<()> {
+        let this = self.get_mut();
+        let new_position = match position {
+            SeekFrom::Start(x) => Some(x),
+            SeekFrom::End(x) => this.index.uncompressed_size.checked_add_signed(x),
+            SeekFrom::Current(x) => this.position.checked_add_signed(x),
+        };
+
+        match new_position {
+            Some(x) => {
+                this.position = x;
+                Ok(())
+            }
+            None => Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                "Invalid seek to a negative or overflowing position",
+            )),
+        }
+    }
+
+    fn poll_complete(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<std::io::Result<u64>> {
+        Poll::Ready(Ok(self.position))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use flate2::write::DeflateEncoder;
+
+    use super::*;
+
+    #[test]
+    fn index_round_trip() {
+        let mut state = 0x2545F491u32;
+        let data: Vec<u8> = (0..300_000)
+            .map(|_| {
+                state ^= state << 13;
+                state ^= state >> 17;
+                state ^= state << 5;
+                b'a' + (state % 16) as u8
+            })
+            .collect();
+        let mut encoder = DeflateEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(&data).unwrap();
+        let compressed = encoder.finish().unwrap();
+        let mut crc = flate2::Crc::new();
+        crc.update(&data);
+
+        let mut builder = IndexBuilder::new(false, 16 * 1024);
+        for chunk in compressed.chunks(4096) {
+            builder.feed(chunk).unwrap();
+        }
+        let index = builder
+            .finish(compressed.len() as u64, data.len() as u64, crc.sum())
+            .unwrap();
+        assert!(index.points().len() > 2);
+
+        let mut serialized = Vec::new();
+        index.write_to(&mut serialized).unwrap();
+        assert_eq!(DeflateIndex::read_from(&serialized[..]).unwrap(), index);
+
+        let point = &index.points()[2];
+        let mut inflater = Inflater::resume(
+            false,
+            point.bit_offset,
+            point.uncompressed_offset,
+            point.window.clone(),
+        );
+        inflater.feed(&compressed[(point.bit_offset / 8) as usize..]);
+        let mut out = Vec::new();
+        while inflater.inflate(&mut out).unwrap() != Status::Finished {}
+        assert_eq!(out, &data[point.uncompressed_offset as usize..]);
+    }
+
+    #[test]
+    fn rejects_mismatched_data() {
+        let mut encoder = DeflateEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(b"hello").unwrap();
+        let compressed = encoder.finish().unwrap();
+
+        let mut builder = IndexBuilder::new(false, 1024);
+        builder.feed(&compressed).unwrap();
+        assert!(builder.finish(compressed.len() as u64, 5, 0).is_err());
+        assert!(DeflateIndex::read_from(&b"NZDI\x02"[..]).is_err());
+    }
+
+    #[test]
+    fn rejects_oversized_windows() {
+        let index = |deflate64, window_size| DeflateIndex {
+            deflate64,
+            compressed_size: 1,
+            uncompressed_size: 1,
+            crc32: 0,
+            points: vec![AccessPoint {
+                bit_offset: 0,
+                uncompressed_offset: 0,
+                window: vec![0; window_size],
+            }],
+        };
+        let serialize = |index: DeflateIndex| {
+            let mut serialized = Vec::new();
+            index.write_to(&mut serialized).unwrap();
+            serialized
+        };
+
+        assert!(DeflateIndex::read_from(&serialize(index(false, 32 * 1024))[..]).is_ok());
+        assert!(DeflateIndex::read_from(&serialize(index(true, 64 * 1024))[..]).is_ok());
+        assert!(matches!(
+            DeflateIndex::read_from(&serialize(index(false, 32 * 1024 + 1))[..]),
+            Err(Error::InvalidIndex(_))
+        ));
+        assert!(matches!(
+            DeflateIndex::read_from(&serialize(index(true, 10 * 1024 * 1024))[..]),
+            Err(Error::InvalidIndex(_))
+        ));
+    }
+}
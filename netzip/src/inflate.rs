@@ -0,0 +1,465 @@
+//! A small incremental Deflate/Deflate64 decoder that can be suspended at block boundaries and resumed
+//! from an arbitrary bit offset with a preloaded window, which zlib-based decoders do not expose.
+
+const MAX_BITS: usize = 15;
+const CODE_LENGTH_ORDER: [usize; 19] = [
+    16, 17, 18, 0, 8, 7, 9, 6, 10, 5, 11, 4, 12, 3, 13, 2, 14, 1, 15,
+];
+const LENGTH_BASE: [u16; 29] = [
+    3, 4, 5, 6, 7, 8, 9, 10, 11, 13, 15, 17, 19, 23, 27, 31, 35, 43, 51, 59, 67, 83, 99, 115, 131,
+    163, 195, 227, 258,
+];
+const LENGTH_EXTRA: [u8; 29] = [
+    0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 1, 1, 2, 2, 2, 2, 3, 3, 3, 3, 4, 4, 4, 4, 5, 5, 5, 5, 0,
+];
+const DISTANCE_BASE: [u32; 32] = [
+    1, 2, 3, 4, 5, 7, 9, 13, 17, 25, 33, 49, 65, 97, 129, 193, 257, 385, 513, 769, 1025, 1537,
+    2049, 3073, 4097, 6145, 8193, 12289, 16385, 24577, 32769, 49153,
+];
+const DISTANCE_EXTRA: [u8; 32] = [
+    0, 0, 0, 0, 1, 1, 2, 2, 3, 3, 4, 4, 5, 5, 6, 6, 7, 7, 8, 8, 9, 9, 10, 10, 11, 11, 12, 12, 13,
+    13, 14, 14,
+];
+
+/// Why decoding stopped.
+#[derive(Debug, PartialEq, Eq)]
+pub(crate) enum Status {
+    /// All buffered input was consumed; feed more to continue.
+    NeedInput,
+    /// The decoder is positioned at the start of a block.
+    BlockBoundary,
+    /// The final block has been decoded.
+    Finished,
+}
+
+enum Halt {
+    NeedInput,
+    Invalid(&'static str),
+}
+
+impl From<&'static str> for Halt {
+    fn from(value: &'static str) -> Self {
+        Self::Invalid(value)
+    }
+}
+
+/// A canonical Huffman code, decoded bit by bit in the style of zlib's puff.
+struct Huffman {
+    counts: [u16; MAX_BITS + 1],
+    symbols: Vec<u16>,
+}
+
+impl Huffman {
+    fn new(lengths: &[u8]) -> Result<Self, &'static str> {
+        let mut counts = [0u16; MAX_BITS + 1];
+        for &length in lengths {
+            counts[length as usize] += 1;
+        }
+
+        let mut left: i32 = 1;
+        for count in &counts[1..] {
+            left = (left << 1) - *count as i32;
+            if left < 0 {
+                return Err("over-subscribed Huffman code");
+            }
+        }
+
+        let mut offsets = [0u16; MAX_BITS + 2];
+        for length in 1..=MAX_BITS {
+            offsets[length + 1] = offsets[length] + counts[length];
+        }
+
+        let mut symbols = vec![0; offsets[MAX_BITS + 1] as usize];
+        for (symbol, &length) in lengths.iter().enumerate() {
+            if length != 0 {
+                symbols[offsets[length as usize] as usize] = symbol as u16;
+                offsets[length as usize] += 1;
+            }
+        }
+
+        counts[0] = 0;
+        Ok(Self { counts, symbols })
+    }
+
+    fn decode(&self, bits: &mut Bits) -> Result<u16, Halt> {
+        let mut code: i32 = 0;
+        let mut first: i32 = 0;
+        let mut index: i32 = 0;
+        for length in 1..=MAX_BITS {
+            code |= bits.take(1)? as i32;
+            let count = self.counts[length] as i32;
+            if code - count < first {
+                return Ok(self.symbols[(index + code - first) as usize]);
+            }
+            index += count;
+            first = (first + count) << 1;
+            code <<= 1;
+        }
+
+        Err(Halt::Invalid("invalid Huffman code"))
+    }
+}
+
+/// A cursor over buffered input that can be abandoned to roll back a partially decoded unit.
+struct Bits<'a> {
+    input: &'a [u8],
+    position: u64,
+}
+
+impl Bits<'_> {
+    fn take(&mut self, count: u8) -> Result<u32, Halt> {
+        let mut value = 0u32;
+        for i in 0..count {
+            let byte = *self
+                .input
+                .get((self.position / 8) as usize)
+                .ok_or(Halt::NeedInput)?;
+            value |= (((byte >> (self.position % 8)) & 1) as u32) << i;
+            self.position += 1;
+        }
+
+        Ok(value)
+    }
+}
+
+enum BlockState {
+    Header,
+    Stored(u16),
+    Codes(Huffman, Huffman),
+    Done,
+}
+
+pub(crate) struct Inflater {
+    deflate64: bool,
+    input: Vec<u8>,
+    /// Absolute byte offset of `input[0]` within the compressed stream
+    input_start: u64,
+    /// Bit position within `input`
+    position: u64,
+    state: BlockState,
+    last_block: bool,
+    window: Vec<u8>,
+    total_out: u64,
+}
+
+impl Inflater {
+    /// Creates a decoder positioned at the start of a compressed stream.
+    pub(crate) fn new(deflate64: bool) -> Self {
+        Self::resume(deflate64, 0, 0, Vec::new())
+    }
+
+    /// Creates a decoder positioned at an absolute bit offset that lies on a block boundary, with the
+    /// window of output preceding it. Input fed to it must start at byte `bit_offset / 8`.
+    pub(crate) fn resume(
+        deflate64: bool,
+        bit_offset: u64,
+        total_out: u64,
+        window: Vec<u8>,
+    ) -> Self {
+        Self {
+            deflate64,
+            input: Vec::new(),
+            input_start: bit_offset / 8,
+            position: bit_offset % 8,
+            state: BlockState::Header,
+            last_block: false,
+            window,
+            total_out,
+        }
+    }
+
+    pub(crate) fn window_size(&self) -> usize {
+        if self.deflate64 { 65536 } else { 32768 }
+    }
+
+    /// Absolute bit offset of the next unread bit of the compressed stream.
+    pub(crate) fn bit_offset(&self) -> u64 {
+        self.input_start * 8 + self.position
+    }
+
+    /// Number of bytes produced since the start of the stream.
+    pub(crate) fn total_out(&self) -> u64 {
+        self.total_out
+    }
+
+    /// Returns the most recent output, as much as back-references may reach.
+    pub(crate) fn window(&self) -> &[u8] {
+        &self.window[self.window.len().saturating_sub(self.window_size())..]
+    }
+
+    /// Appends compressed input.
+    pub(crate) fn feed(&mut self, input: &[u8]) {
+        let consumed = (self.position / 8) as usize;
+        if consumed > 0 && consumed >= self.input.len() / 2 {
+            self.input.drain(..consumed);
+            self.input_start += consumed as u64;
+            self.position -= consumed as u64 * 8;
+        }
+
+        self.input.extend_from_slice(input);
+    }
+
+    /// Decodes as much buffered input as possible, appending the output to `out`.
+    pub(crate) fn inflate(&mut self, out: &mut Vec<u8>) -> Result<Status, &'static str> {
+        let result = loop {
+            let step = match &self.state {
+                BlockState::Done => break Ok(Status::Finished),
+                BlockState::Header => self.read_header(),
+                BlockState::Stored(_) => self.copy_stored(out),
+                BlockState::Codes(..) => self.decode_symbol(out),
+            };
+
+            match step {
+                Ok(true) => break Ok(Status::BlockBoundary),
+                Ok(false) => {}
+                Err(Halt::NeedInput) => break Ok(Status::NeedInput),
+                Err(Halt::Invalid(e)) => break Err(e),
+            }
+        };
+
+        let limit = self.window_size() * 2;
+        if self.window.len() > limit {
+            self.window.drain(..self.window.len() - self.window_size());
+        }
+
+        result
+    }
+
+    /// Parses a block header, or marks the stream as done after the final block.
+    fn read_header(&mut self) -> Result<bool, Halt> {
+        if self.last_block {
+            self.state = BlockState::Done;
+            return Ok(false);
+        }
+
+        let mut bits = Bits {
+            input: &self.input,
+            position: self.position,
+        };
+        let last_block = bits.take(1)? == 1;
+        let state = match bits.take(2)? {
+            0 => {
+                bits.position = bits.position.div_ceil(8) * 8;
+                let length = bits.take(16)?;
+                let complement = bits.take(16)?;
+                if length != !complement & 0xFFFF {
+                    return Err("stored block length mismatch".into());
+                }
+                BlockState::Stored(length as u16)
+            }
+            1 => {
+                let mut lengths = [0u8; 320];
+                lengths[..144].fill(8);
+                lengths[144..256].fill(9);
+                lengths[256..280].fill(7);
+                lengths[280..288].fill(8);
+                lengths[288..].fill(5);
+                BlockState::Codes(
+                    Huffman::new(&lengths[..288])?,
+                    Huffman::new(&lengths[288..])?,
+                )
+            }
+            2 => {
+                let literal_count = bits.take(5)? as usize + 257;
+                let distance_count = bits.take(5)? as usize + 1;
+                let code_length_count = bits.take(4)? as usize + 4;
+                if literal_count > 286 || (distance_count > 30 && !self.deflate64) {
+                    return Err("too many length or distance codes".into());
+                }
+
+                let mut code_lengths = [0u8; 19];
+                for &index in &CODE_LENGTH_ORDER[..code_length_count] {
+                    code_lengths[index] = bits.take(3)? as u8;
+                }
+                let code_length_code = Huffman::new(&code_lengths)?;
+
+                let mut lengths = vec![0u8; literal_count + distance_count];
+                let mut index = 0;
+                while index < lengths.len() {
+                    let symbol = code_length_code.decode(&mut bits)?;
+                    let (value, repeat) = match symbol {
+                        0..=15 => (symbol as u8, 1),
+                        16 => {
+                            let previous = *lengths[..index]
+                                .last()
+                                .ok_or("repeat with no previous length")?;
+                            (previous, 3 + bits.take(2)? as usize)
+                        }
+                        17 => (0, 3 + bits.take(3)? as usize),
+                        _ => (0, 11 + bits.take(7)? as usize),
+                    };
+                    if index + repeat > lengths.len() {
+                        return Err("too many code lengths".into());
+                    }
+                    lengths[index..index + repeat].fill(value);
+                    index += repeat;
+                }
+
+                if lengths[256] == 0 {
+                    return Err("missing end-of-block code".into());
+                }
+
+                BlockState::Codes(
+                    Huffman::new(&lengths[..literal_count])?,
+                    Huffman::new(&lengths[literal_count..])?,
+                )
+            }
+            _ => return Err("invalid block type".into()),
+        };
+
+        self.position = bits.position;
+        self.last_block = last_block;
+        self.state = state;
+        Ok(false)
+    }
+
+    /// Copies as much of a stored block as is buffered. Returns true once the block is complete.
+    fn copy_stored(&mut self, out: &mut Vec<u8>) -> Result<bool, Halt> {
+        let BlockState::Stored(remaining) = &mut self.state else {
+            unreachable!("copy_stored called outside of a stored block");
+        };
+
+        let start = (self.position / 8) as usize;
+        let count = (*remaining as usize).min(self.input.len() - start);
+        let data = &self.input[start..start + count];
+        out.extend_from_slice(data);
+        self.window.extend_from_slice(data);
+        self.total_out += count as u64;
+        self.position += count as u64 * 8;
+        *remaining -= count as u16;
+
+        if *remaining == 0 {
+            self.state = BlockState::Header;
+            Ok(true)
+        } else {
+            Err(Halt::NeedInput)
+        }
+    }
+
+    /// Decodes one literal, back-reference or end-of-block symbol. Returns true at the end of the block.
+    fn decode_symbol(&mut self, out: &mut Vec<u8>) -> Result<bool, Halt> {
+        let BlockState::Codes(literals, distances) = &self.state else {
+            unreachable!("decode_symbol called outside of a compressed block");
+        };
+
+        let mut bits = Bits {
+            input: &self.input,
+            position: self.position,
+        };
+        let symbol = literals.decode(&mut bits)? as usize;
+        match symbol {
+            0..=255 => {
+                self.position = bits.position;
+                out.push(symbol as u8);
+                self.window.push(symbol as u8);
+                self.total_out += 1;
+                Ok(false)
+            }
+            256 => {
+                self.position = bits.position;
+                self.state = BlockState::Header;
+                Ok(true)
+            }
+            257..=285 => {
+                let index = symbol - 257;
+                let length = if index == 28 && self.deflate64 {
+                    3 + bits.take(16)? as usize
+                } else {
+                    LENGTH_BASE[index] as usize + bits.take(LENGTH_EXTRA[index])? as usize
+                };
+
+                let code = distances.decode(&mut bits)? as usize;
+                if code >= 32 || (code >= 30 && !self.deflate64) {
+                    return Err("invalid distance code".into());
+                }
+                let distance =
+                    DISTANCE_BASE[code] as usize + bits.take(DISTANCE_EXTRA[code])? as usize;
+                if distance > self.window.len() {
+                    return Err("distance too far back".into());
+                }
+
+                self.position = bits.position;
+                let start = self.window.len() - distance;
+                for i in 0..length {
+                    let byte = self.window[start + i];
+                    self.window.push(byte);
+                    out.push(byte);
+                }
+                self.total_out += length as u64;
+                Ok(false)
+            }
+            _ => Err("invalid literal/length code".into()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+
+    use flate2::{Compression, write::DeflateEncoder};
+
+    use super::*;
+
+    fn sample() -> Vec<u8> {
+        (0..200_000u32)
+            .map(|x| (x.wrapping_mul(2654435761) >> 28) as u8 + b'a')
+            .collect()
+    }
+
+    fn compress(data: &[u8], level: u32) -> Vec<u8> {
+        let mut encoder = DeflateEncoder::new(Vec::new(), Compression::new(level));
+        encoder.write_all(data).unwrap();
+        encoder.finish().unwrap()
+    }
+
+    #[test]
+    fn inflates_in_small_chunks() {
+        let data = sample();
+        for level in [0, 1, 6, 9] {
+            let compressed = compress(&data, level);
+            let mut inflater = Inflater::new(false);
+            let mut out = Vec::new();
+            for chunk in compressed.chunks(7) {
+                inflater.feed(chunk);
+                while inflater.inflate(&mut out).unwrap() == Status::BlockBoundary {}
+            }
+            assert_eq!(inflater.inflate(&mut out).unwrap(), Status::Finished);
+            assert_eq!(out, data);
+        }
+    }
+
+    #[test]
+    fn resumes_from_block_boundary() {
+        let data = sample();
+        let compressed = compress(&data, 6);
+
+        let mut inflater = Inflater::new(false);
+        inflater.feed(&compressed);
+        let mut out = Vec::new();
+        let mut boundary = None;
+        while inflater.inflate(&mut out).unwrap() == Status::BlockBoundary {
+            if boundary.is_none() && inflater.total_out() > 50_000 {
+                boundary = Some((
+                    inflater.bit_offset(),
+                    inflater.total_out(),
+                    inflater.window().to_vec(),
+                ));
+            }
+        }
+        let (bit_offset, total_out, window) = boundary.expect("Sample should span several blocks");
+
+        let mut resumed = Inflater::resume(false, bit_offset, total_out, window);
+        resumed.feed(&compressed[(bit_offset / 8) as usize..]);
+        let mut tail = Vec::new();
+        while resumed.inflate(&mut tail).unwrap() == Status::BlockBoundary {}
+        assert_eq!(tail, &data[total_out as usize..]);
+    }
+
+    #[test]
+    fn rejects_garbage() {
+        let mut inflater = Inflater::new(false);
+        inflater.feed(&[0xFF; 64]);
+        assert!(inflater.inflate(&mut Vec::new()).is_err());
+    }
+}
@@ -1,9 +1,70 @@
-use std::io::Read;
+use std::{
+    io::Write,
+    path::{Path, PathBuf},
+    pin::{Pin, pin},
+    sync::Arc,
+    sync::atomic::{AtomicUsize, Ordering},
+    task::{Context, Poll},
+    time::Instant,
+};
 
 use bytes::Bytes;
-use flate2::bufread::DeflateDecoder;
-use netzip_parser::{CentralDirectoryEnd, CentralDirectoryRecord, LocalFile, ZipError};
+use flate2::write::DeflateDecoder;
+use futures_util::{StreamExt, stream::BoxStream};
+use netzip_parser::{CentralDirectoryRecord, LocalFile, Zip64ExtraField, ZipError};
 use thiserror::Error;
+use tokio::io::{AsyncWrite, AsyncWriteExt};
+
+/// Emits a `tracing` event at the given level when the `tracing` feature is enabled, and nothing otherwise.
+macro_rules! event {
+    ($level:ident, $($arg:tt)+) => {
+        #[cfg(feature = "tracing")]
+        tracing::$level!($($arg)+)
+    };
+}
+
+#[cfg(feature = "blocking")]
+pub mod blocking;
+mod builder;
+mod cache;
+mod cancel;
+mod directory;
+mod index;
+mod inflate;
+mod progress;
+mod rate_limit;
+mod reader;
+mod selection;
+#[cfg(feature = "serde")]
+mod snapshot;
+mod source;
+mod stats;
+mod subset;
+#[cfg(feature = "test-support")]
+pub mod test_support;
+mod tree;
+
+pub use builder::RemoteZipBuilder;
+pub use cache::{DEFAULT_CACHE_BLOCK_SIZE, RangeCache};
+pub use index::{AccessPoint, DEFAULT_INDEX_SPAN, DeflateIndex, IndexedEntryReader};
+pub use progress::{Progress, ProgressListener, ProgressStage};
+pub use rate_limit::RateLimiter;
+pub use reader::{DEFAULT_READ_AHEAD, StoredEntryReader};
+pub use selection::{EntrySelector, Pattern};
+#[cfg(feature = "serde")]
+pub use snapshot::DirectorySnapshot;
+pub use stats::TransferStats;
+pub use tokio_util::sync::CancellationToken;
+pub use tree::{DirectoryNode, TreeEntry};
+
+use cancel::Cancellation;
+use index::IndexBuilder;
+use progress::ProgressTracker;
+use source::{EntrySource, GuardedSource, MemorySource, SharedSource};
+
+/// The default limit on the uncompressed size of compressed inner archives opened with
+/// [`RemoteZip::open_nested`], which have to be held in memory.
+pub const DEFAULT_NESTED_BUFFER_LIMIT: u64 = 256 * 1024 * 1024;
 
 #[derive(Debug, Error)]
 pub enum Error {
@@ -15,12 +76,64 @@ pub enum Error {
     DecompressionError(String, String),
     #[error("Unable to decompress file with compression type {0}")]
     UnsupportCompression(u16),
+    #[error("Invalid pattern '{0}': {1}")]
+    InvalidPattern(String, String),
+    #[error("No entry named '{0}' in archive")]
+    EntryNotFound(String),
+    #[error("Server at '{0}' does not support range requests")]
+    RangeUnsupported(String),
+    #[error("Unexpected response from '{0}': {1}")]
+    UnexpectedResponse(String, String),
+    #[error("Expected {1} bytes in response from '{0}', but received {2}")]
+    IncompleteResponse(String, u64, u64),
+    #[error("Entry '{0}' is compressed and cannot be read at arbitrary offsets")]
+    NotSeekable(String),
+    #[error("Entry '{0}' exceeds the limit of {1} bytes for buffered access")]
+    EntryTooLarge(String, u64),
+    #[error("Invalid Deflate index: {0}")]
+    InvalidIndex(String),
+    #[error("Archive at '{0}' changed while it was being read")]
+    ArchiveChanged(String),
+    #[error("Invalid directory snapshot: {0}")]
+    InvalidSnapshot(String),
+    #[error("Operation on '{0}' was cancelled")]
+    Cancelled(String),
+    #[error("Deadline exceeded while accessing '{0}'")]
+    DeadlineExceeded(String),
+    #[error("Mirror '{0}' does not serve the same archive as the other mirrors")]
+    MirrorMismatch(String),
+    #[error("Timed out waiting for a response from '{0}'")]
+    TimedOut(String),
+    #[error("Disk {1} of split archive '{0}' is missing, all segments of the archive are needed")]
+    MissingDisk(String, u16),
+    #[error("Split archive '{0}' spans {1} disks, but {2} segments were given")]
+    SegmentCountMismatch(String, usize, usize),
+    /// Only returned by the blocking API, but always present so enabling the `blocking` feature does not change
+    /// the set of variants.
+    #[error("Unable to start the runtime for blocking calls: {0}")]
+    RuntimeUnavailable(std::io::Error),
+    #[error("Error encountered while accessing '{0}': {1}")]
+    IoError(String, std::io::Error),
+}
+
+/// The outcome of downloading a single entry with [`RemoteZip::download_entries`].
+#[derive(Debug)]
+pub enum EntryResult {
+    /// The entry was downloaded and decompressed successfully.
+    Downloaded(LocalFile, Vec<u8>),
+    /// No entry with the requested path exists in the archive.
+    Missing,
+    /// The entry uses a compression method that cannot be decompressed.
+    Unsupported(u16),
+    /// The entry could not be downloaded, parsed or decompressed.
+    Failed(Error),
 }
 
 pub struct RemoteZip {
-    url: String,
-    http_client: reqwest::Client,
+    source: SharedSource,
     central_directory: Vec<CentralDirectoryRecord>,
+    cancellation: Arc<Cancellation>,
+    progress: Option<Arc<dyn ProgressListener>>,
 }
 
 impl RemoteZip {
@@ -35,48 +148,107 @@ impl RemoteZip {
     /// # Returns
     ///
     /// A Result containing either the initialized RemoteZip instance or an Error
+    #[cfg_attr(feature = "tracing", tracing::instrument(skip(http_client)))]
     pub async fn get_using(url: &str, http_client: reqwest::Client) -> Result<Self, Error> {
-        let min_cde_bytes = ranged_request(
-            url,
-            &format!("bytes=-{}", netzip_parser::EOCD_MIN_SIZE),
-            http_client.clone(),
-        )
-        .await?;
-
-        let cde = if let Ok(min_out) = CentralDirectoryEnd::parse(&min_cde_bytes) {
-            min_out
-        } else {
-            // There might be a comment, retry with an offset and search for the EOCD
-            let cde_haystack = ranged_request(
-                url,
-                &format!("bytes=-{}", netzip_parser::EOCD_MIN_SIZE + 1024),
-                http_client.clone(),
-            )
-            .await?;
+        Self::builder(url).client(http_client).build().await
+    }
 
-            CentralDirectoryEnd::find_and_parse(&cde_haystack)
-                .map_err(|e| Error::ParserError(url.into(), e))?
-        };
+    /// Creates a builder for a RemoteZip instance, for configuring options such as the HTTP client or a cache
+    /// before the ZIP directory structure is fetched.
+    ///
+    /// # Arguments
+    ///
+    /// * `url` - The URL of the remote ZIP file to access
+    ///
+    /// # Returns
+    ///
+    /// A RemoteZipBuilder with default options
+    pub fn builder(url: &str) -> RemoteZipBuilder {
+        RemoteZipBuilder::new(url)
+    }
 
-        let cd_bytes = ranged_request(
-            url,
-            &format!(
-                "bytes={}-{}",
-                cde.central_directory_offset,
-                cde.central_directory_offset + cde.directory_size
-            ),
-            http_client.clone(),
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(
+            name = "directory",
+            level = "debug",
+            skip_all,
+            fields(url = source.url())
         )
-        .await?;
+    )]
+    async fn load(
+        source: SharedSource,
+        cancellation: Arc<Cancellation>,
+        progress: Option<Arc<dyn ProgressListener>>,
+    ) -> Result<Self, Error> {
+        let mut zip = Self::from_parts(source, Vec::new(), cancellation, progress);
+        let source = &zip.source;
+        let url = source.url().to_string();
+        let cde = directory::find_end(source).await?;
 
-        let cd_records = CentralDirectoryRecord::parse_many(&cd_bytes)
-            .map_err(|e| Error::ParserError(url.into(), e))?;
+        let mut tracker =
+            ProgressTracker::directory(zip.progress.clone(), cde.directory_size as u64);
+        let cd_bytes = directory::fetch_directory(source, &cde, &mut tracker).await?;
 
-        Ok(Self {
-            url: url.into(),
-            central_directory: cd_records,
-            http_client,
-        })
+        let cd_records = CentralDirectoryRecord::parse_directory(&cd_bytes, &cde).map_err(|e| {
+            event!(debug, error = %e, "Failed to parse central directory");
+            Error::ParserError(url, e)
+        })?;
+        event!(
+            debug,
+            entries = cd_records.len(),
+            "Parsed central directory"
+        );
+
+        tracker.directory_parsed(cd_records.len());
+
+        zip.central_directory = cd_records;
+        Ok(zip)
+    }
+
+    /// Assembles a RemoteZip, guarding all reads from the source with the cancellation.
+    fn from_parts(
+        source: SharedSource,
+        central_directory: Vec<CentralDirectoryRecord>,
+        cancellation: Arc<Cancellation>,
+        progress: Option<Arc<dyn ProgressListener>>,
+    ) -> Self {
+        Self {
+            source: Arc::new(GuardedSource::new(source, cancellation.clone())),
+            central_directory,
+            cancellation,
+            progress,
+        }
+    }
+
+    /// Captures the parsed central directory and the archive's validators, so that an equivalent RemoteZip can
+    /// later be restored with [`RemoteZip::from_snapshot`] without fetching and parsing the directory again.
+    ///
+    /// # Returns
+    ///
+    /// A DirectorySnapshot of this instance
+    #[cfg(feature = "serde")]
+    pub fn snapshot(&self) -> DirectorySnapshot {
+        DirectorySnapshot {
+            url: self.source.url().into(),
+            info: self.source.info(),
+            records: self.central_directory.clone(),
+        }
+    }
+
+    /// Restores a RemoteZip instance from a directory snapshot without any network access, using a default
+    /// HTTP client. Use [`RemoteZipBuilder::build_from_snapshot`] to configure other options.
+    ///
+    /// # Arguments
+    ///
+    /// * `snapshot` - The snapshot to restore
+    ///
+    /// # Returns
+    ///
+    /// The restored RemoteZip instance
+    #[cfg(feature = "serde")]
+    pub fn from_snapshot(snapshot: DirectorySnapshot) -> Self {
+        Self::builder(&snapshot.url.clone()).build_from_snapshot(snapshot)
     }
 
     /// Creates a new RemoteZip instance by fetching and parsing the ZIP directory structure from a remote URL
@@ -102,6 +274,42 @@ impl RemoteZip {
         &self.central_directory
     }
 
+    /// Returns the counters of the data transferred for this archive so far.
+    pub fn stats(&self) -> TransferStats {
+        self.source.stats().snapshot()
+    }
+
+    /// Resets the transfer counters of this archive to zero.
+    pub fn reset_stats(&self) {
+        self.source.stats().reset();
+    }
+
+    /// Returns the token cancelling all operations of this RemoteZip, including readers handed out by it and
+    /// archives opened with [`RemoteZip::open_nested`]. Once the token is cancelled, running and later
+    /// operations fail with [`Error::Cancelled`] without sending further requests.
+    ///
+    /// # Returns
+    ///
+    /// A reference to the CancellationToken, which may be cloned and cancelled from any task or thread
+    pub fn cancellation_token(&self) -> &CancellationToken {
+        self.cancellation.token()
+    }
+
+    /// Returns the deadline after which operations of this RemoteZip fail, if one is set.
+    pub fn deadline(&self) -> Option<Instant> {
+        self.cancellation.deadline()
+    }
+
+    /// Sets or clears the point in time after which running and later operations of this RemoteZip fail with
+    /// [`Error::DeadlineExceeded`]. Can be changed between operations to cap the work done for each of them.
+    ///
+    /// # Arguments
+    ///
+    /// * `deadline` - The new deadline, or None to let operations run for as long as they take
+    pub fn set_deadline(&self, deadline: Option<Instant>) {
+        self.cancellation.set_deadline(deadline);
+    }
+
     /// Returns a mutable reference to the central directory records of the ZIP file.
     ///
     /// # Returns
@@ -111,6 +319,55 @@ impl RemoteZip {
         &mut self.central_directory
     }
 
+    /// Arranges the central directory records into a directory tree, synthesizing directories
+    /// that have no explicit entry in the archive.
+    ///
+    /// # Returns
+    ///
+    /// The root DirectoryNode of the archive
+    pub fn tree(&self) -> DirectoryNode<'_> {
+        DirectoryNode::from_records(&self.central_directory)
+    }
+
+    /// Returns the file records whose paths are selected by the given selector.
+    ///
+    /// # Arguments
+    ///
+    /// * `selector` - The include/exclude patterns to match entry paths against
+    ///
+    /// # Returns
+    ///
+    /// A vector of references to the matching CentralDirectoryRecord entries, in archive order
+    pub fn select(&self, selector: &EntrySelector) -> Vec<&CentralDirectoryRecord> {
+        self.central_directory
+            .iter()
+            .filter(|x| selector.matches_record(x))
+            .collect()
+    }
+
+    /// Downloads and decompresses all files selected by the given selector.
+    ///
+    /// # Arguments
+    ///
+    /// * `selector` - The include/exclude patterns to match entry paths against
+    ///
+    /// # Returns
+    ///
+    /// A Result containing either a vector of tuples with (LocalFile metadata, file contents as bytes)
+    /// or an Error if any file could not be downloaded or decompressed
+    pub async fn download_selected(
+        &self,
+        selector: &EntrySelector,
+    ) -> Result<Vec<(LocalFile, Vec<u8>)>, Error> {
+        let paths = self
+            .select(selector)
+            .into_iter()
+            .map(|x| x.file_name.clone())
+            .collect();
+
+        self.download_files(paths).await
+    }
+
     /// Downloads and decompresses the specified files from the remote ZIP.
     ///
     /// # Arguments
@@ -120,7 +377,7 @@ impl RemoteZip {
     /// # Returns
     ///
     /// A Result containing either a vector of tuples with (LocalFile metadata, file contents as bytes)
-    /// or an Error if any file could not be downloaded or decompressed
+    /// or an Error if any file could not be downloaded or decompressed, or the operation was cancelled
     pub async fn download_files(
         &self,
         paths: Vec<String>,
@@ -131,88 +388,560 @@ impl RemoteZip {
             .filter(|x| paths.contains(&x.file_name))
             .collect();
 
+        let mut progress = self.track_entries(needed_cd_records.iter().copied());
         let mut out = Vec::new();
-
         for cd_record in needed_cd_records {
-            let lfh_end_offset = cd_record.file_header_offset
-                + netzip_parser::LFH_MIN_SIZE as u32
-                + cd_record.extra_field_length as u32
-                + cd_record.file_name_length as u32
-                + cd_record.file_comment_length as u32;
-            let lfh_bytes = &self
-                .ranged_request(&format!(
-                    "bytes={}-{}",
-                    cd_record.file_header_offset, lfh_end_offset
-                ))
+            out.push(self.download_record(cd_record, &mut progress).await?);
+        }
+
+        Ok(out)
+    }
+
+    /// Downloads and decompresses the specified files from the remote ZIP, reporting the outcome of each
+    /// file separately so that one failing entry does not discard the others. If the operation is cancelled
+    /// or its deadline passes, the files downloaded so far are kept and the remaining ones are reported as
+    /// failed with [`Error::Cancelled`] or [`Error::DeadlineExceeded`] without being requested.
+    ///
+    /// # Arguments
+    ///
+    /// * `paths` - A vector of file paths/names to download from the ZIP
+    ///
+    /// # Returns
+    ///
+    /// A vector of tuples with (requested path, EntryResult), in the order the paths were given
+    pub async fn download_entries(&self, paths: Vec<String>) -> Vec<(String, EntryResult)> {
+        let mut progress =
+            self.track_entries(paths.iter().filter_map(|x| self.find_record(x).ok()));
+        let mut out = Vec::with_capacity(paths.len());
+        for path in paths {
+            let result = match self.central_directory.iter().find(|x| x.file_name == path) {
+                None => EntryResult::Missing,
+                Some(cd_record) => match self.download_record(cd_record, &mut progress).await {
+                    Ok((lfh, data)) => EntryResult::Downloaded(lfh, data),
+                    Err(Error::UnsupportCompression(id)) => EntryResult::Unsupported(id),
+                    Err(e) => EntryResult::Failed(e),
+                },
+            };
+            out.push((path, result));
+        }
+
+        out
+    }
+
+    /// Downloads and decompresses a single file from the remote ZIP into an asynchronous writer, streaming
+    /// the data so the file never has to be held in memory as a whole.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - The file path/name inside the ZIP
+    /// * `writer` - The writer to write the decompressed contents to
+    ///
+    /// # Returns
+    ///
+    /// A Result containing either the LocalFile metadata of the entry or an Error if the file could not be
+    /// found, downloaded, decompressed or written. The writer may have received partial data on error.
+    pub async fn download_to_writer<W: AsyncWrite + Unpin>(
+        &self,
+        path: &str,
+        writer: &mut W,
+    ) -> Result<LocalFile, Error> {
+        let cd_record = self.find_record(path)?;
+        self.write_record(cd_record, writer, &mut self.track_entries([cd_record]))
+            .await
+    }
+
+    /// Downloads and decompresses a single file from the remote ZIP to a path on disk. The data is written to
+    /// a temporary file in the destination directory which is only renamed to the destination once complete,
+    /// so no partially written file is left behind on failure or cancellation.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - The file path/name inside the ZIP
+    /// * `destination` - The path on disk to write the decompressed contents to
+    ///
+    /// # Returns
+    ///
+    /// A Result containing either the LocalFile metadata of the entry or an Error if the file could not be
+    /// found, downloaded, decompressed or written
+    pub async fn download_to_path(
+        &self,
+        path: &str,
+        destination: impl AsRef<Path>,
+    ) -> Result<LocalFile, Error> {
+        let cd_record = self.find_record(path)?;
+        let destination = destination.as_ref();
+        let temp_path = temp_path_for(destination);
+        let io_error = |e| Error::IoError(destination.display().to_string(), e);
+
+        let result = async {
+            let mut file = tokio::fs::File::create(&temp_path)
+                .await
+                .map_err(io_error)?;
+            let mut progress = self.track_entries([cd_record]);
+            let lfh = self
+                .write_record(cd_record, &mut file, &mut progress)
                 .await?;
+            file.sync_all().await.map_err(io_error)?;
+            tokio::fs::rename(&temp_path, destination)
+                .await
+                .map_err(io_error)?;
+            Ok(lfh)
+        }
+        .await;
 
-            let lfh = LocalFile::parse(&lfh_bytes)
-                .map_err(|e| Error::ParserError(self.url.clone(), e))?;
-
-            match lfh.compression_method {
-                netzip_parser::CompressionMethod::Deflate
-                | netzip_parser::CompressionMethod::Deflate64 => {
-                    let compressed_data: &[u8] = &self
-                        .ranged_request(&format!(
-                            "bytes={}-{}",
-                            lfh_end_offset,
-                            lfh_end_offset + lfh.compressed_size
-                        ))
-                        .await?;
-
-                    let mut decoder = DeflateDecoder::new(compressed_data);
-                    let mut decoded = Vec::with_capacity(lfh.uncompressed_size as usize);
-                    decoder
-                        .read_to_end(&mut decoded)
-                        .map_err(|e| Error::DecompressionError(self.url.clone(), e.to_string()))?;
-
-                    out.push((lfh, decoded));
-                }
-                netzip_parser::CompressionMethod::Stored => {
-                    let data = &self
-                        .ranged_request(&format!(
-                            "bytes={}-{}",
-                            lfh_end_offset,
-                            lfh_end_offset + lfh.uncompressed_size
-                        ))
-                        .await?;
-
-                    out.push((lfh, data.to_vec()));
+        if result.is_err() {
+            let _ = tokio::fs::remove_file(&temp_path).await;
+        }
+
+        result
+    }
+
+    /// Opens a stored (uncompressed) file of the remote ZIP for random access. Seeking within the returned
+    /// reader is free, and reads only fetch the byte ranges around the current position.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - The file path/name inside the ZIP
+    ///
+    /// # Returns
+    ///
+    /// A Result containing either a StoredEntryReader implementing `AsyncRead` and `AsyncSeek`, or an Error
+    /// if the file could not be found or is not stored uncompressed
+    pub async fn open_stored(&self, path: &str) -> Result<StoredEntryReader, Error> {
+        let cd_record = self.find_record(path)?;
+        if cd_record.compression_method != netzip_parser::CompressionMethod::Stored {
+            return Err(Error::NotSeekable(path.into()));
+        }
+
+        let (_, data_offset) = self.fetch_local_header(cd_record).await?;
+
+        Ok(StoredEntryReader::new(
+            self.source.clone(),
+            data_offset,
+            cd_record.uncompressed_size as u64,
+        ))
+    }
+
+    /// Builds an index of access points into a Deflate compressed file by downloading and decompressing it
+    /// once, so that it can later be read at arbitrary offsets with [`RemoteZip::open_indexed`].
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - The file path/name inside the ZIP
+    /// * `span` - The approximate distance in uncompressed bytes between access points, see [`DEFAULT_INDEX_SPAN`]
+    ///
+    /// # Returns
+    ///
+    /// A Result containing either the DeflateIndex or an Error if the file could not be found, is not Deflate
+    /// compressed, or could not be downloaded or decompressed
+    pub async fn build_deflate_index(&self, path: &str, span: u64) -> Result<DeflateIndex, Error> {
+        let cd_record = self.find_record(path)?;
+        let (lfh, data_offset) = self.fetch_local_header(cd_record).await?;
+        let deflate64 = match lfh.compression_method {
+            netzip_parser::CompressionMethod::Deflate => false,
+            netzip_parser::CompressionMethod::Deflate64 => true,
+            _ => {
+                return Err(Error::InvalidIndex(format!(
+                    "Entry '{path}' is not Deflate compressed"
+                )));
+            }
+        };
+        let decompression_error =
+            |e: &str| Error::DecompressionError(self.source.url().into(), e.into());
+
+        let length = cd_record.compressed_size as u64;
+        let mut builder = IndexBuilder::new(deflate64, span);
+        let mut body = pin!(self.source.fetch_stream(data_offset, length).await?);
+        while let Some(chunk) = body.next().await {
+            builder.feed(&chunk?).map_err(decompression_error)?;
+        }
+
+        builder
+            .finish(length, cd_record.uncompressed_size as u64, cd_record.crc32)
+            .map_err(decompression_error)
+    }
+
+    /// Opens a Deflate compressed file of the remote ZIP for random access using a previously built index.
+    /// Reads only fetch and decompress the compressed span around the current position.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - The file path/name inside the ZIP
+    /// * `index` - The index built for this file by [`RemoteZip::build_deflate_index`]
+    ///
+    /// # Returns
+    ///
+    /// A Result containing either an IndexedEntryReader implementing `AsyncRead` and `AsyncSeek`, or an Error
+    /// if the file could not be found or the index was built for different data
+    pub async fn open_indexed(
+        &self,
+        path: &str,
+        index: impl Into<Arc<DeflateIndex>>,
+    ) -> Result<IndexedEntryReader, Error> {
+        let cd_record = self.find_record(path)?;
+        let index = index.into();
+        if !index.matches(
+            cd_record.compressed_size as u64,
+            cd_record.uncompressed_size as u64,
+            cd_record.crc32,
+        ) {
+            return Err(Error::InvalidIndex(format!(
+                "Index does not match entry '{path}'"
+            )));
+        }
+
+        let (_, data_offset) = self.fetch_local_header(cd_record).await?;
+
+        Ok(IndexedEntryReader::new(
+            self.source.clone(),
+            data_offset,
+            index,
+        ))
+    }
+
+    /// Opens a ZIP archive contained in a file of this archive, such as a `.zip` or `.jar` inside a release
+    /// bundle. Compressed inner archives larger than [`DEFAULT_NESTED_BUFFER_LIMIT`] are rejected, see
+    /// [`RemoteZip::open_nested_with_limit`].
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - The file path/name of the inner archive inside the ZIP
+    ///
+    /// # Returns
+    ///
+    /// A Result containing either the RemoteZip of the inner archive or an Error
+    pub async fn open_nested(&self, path: &str) -> Result<RemoteZip, Error> {
+        self.open_nested_with_limit(path, DEFAULT_NESTED_BUFFER_LIMIT)
+            .await
+    }
+
+    /// Opens a ZIP archive contained in a file of this archive. A stored (uncompressed) inner archive is read
+    /// with range requests into the outer archive only. A compressed inner archive can not be read at
+    /// arbitrary offsets, so it is downloaded and decompressed into memory up to the given size.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - The file path/name of the inner archive inside the ZIP
+    /// * `buffer_limit` - The largest uncompressed size in bytes of a compressed inner archive to buffer
+    ///
+    /// # Returns
+    ///
+    /// A Result containing either the RemoteZip of the inner archive or an Error if the file could not be
+    /// found, is compressed and larger than the limit, or is not a valid ZIP archive
+    pub async fn open_nested_with_limit(
+        &self,
+        path: &str,
+        buffer_limit: u64,
+    ) -> Result<RemoteZip, Error> {
+        let cd_record = self.find_record(path)?;
+        let source: SharedSource = match cd_record.compression_method {
+            netzip_parser::CompressionMethod::Stored => {
+                let (_, data_offset) = self.fetch_local_header(cd_record).await?;
+                Arc::new(EntrySource::new(
+                    self.source.clone(),
+                    path,
+                    data_offset,
+                    cd_record.compressed_size as u64,
+                ))
+            }
+            _ => {
+                if cd_record.uncompressed_size as u64 > buffer_limit {
+                    return Err(Error::EntryTooLarge(path.into(), buffer_limit));
                 }
-                netzip_parser::CompressionMethod::Unsupported(unsupported_id) => {
-                    return Err(Error::UnsupportCompression(unsupported_id));
+
+                // The declared size may be wrong, so the limit is enforced while decompressing as well
+                let mut buffer =
+                    LimitedBuffer::new(cd_record.uncompressed_size as u64, buffer_limit);
+                let result = self
+                    .write_record(cd_record, &mut buffer, &mut self.track_entries([cd_record]))
+                    .await;
+                if buffer.exceeded {
+                    return Err(Error::EntryTooLarge(path.into(), buffer_limit));
                 }
+                result?;
+                Arc::new(MemorySource::new(
+                    format!("{}!/{}", self.source.url(), path),
+                    buffer.data.into(),
+                ))
             }
+        };
+
+        Self::load(
+            source,
+            Arc::new(self.cancellation.child()),
+            self.progress.clone(),
+        )
+        .await
+    }
+
+    fn find_record(&self, path: &str) -> Result<&CentralDirectoryRecord, Error> {
+        self.central_directory
+            .iter()
+            .find(|x| x.file_name == path)
+            .ok_or_else(|| Error::EntryNotFound(path.into()))
+    }
+
+    fn track_entries<'a>(
+        &self,
+        records: impl IntoIterator<Item = &'a CentralDirectoryRecord>,
+    ) -> ProgressTracker {
+        ProgressTracker::entries(self.progress.clone(), records)
+    }
+
+    async fn download_record(
+        &self,
+        cd_record: &CentralDirectoryRecord,
+        progress: &mut ProgressTracker,
+    ) -> Result<(LocalFile, Vec<u8>), Error> {
+        let mut decoded = Vec::with_capacity(cd_record.uncompressed_size as usize);
+        let lfh = self.write_record(cd_record, &mut decoded, progress).await?;
+
+        Ok((lfh, decoded))
+    }
+
+    /// Streams the data of an entry into the writer, decompressing it on the fly and reporting progress.
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(
+            name = "entry",
+            level = "debug",
+            skip_all,
+            fields(
+                entry = %cd_record.file_name,
+                method = ?cd_record.compression_method,
+                compressed_size = cd_record.compressed_size,
+                uncompressed_size = cd_record.uncompressed_size,
+            )
+        )
+    )]
+    async fn write_record<W: AsyncWrite + Unpin>(
+        &self,
+        cd_record: &CentralDirectoryRecord,
+        writer: &mut W,
+        progress: &mut ProgressTracker,
+    ) -> Result<LocalFile, Error> {
+        progress.start_entry(&cd_record.file_name);
+        let result = self.stream_record(cd_record, writer, progress).await;
+        progress.finish_entry();
+
+        #[cfg(feature = "tracing")]
+        match &result {
+            Ok(_) => tracing::debug!("Decoded entry"),
+            Err(e) => tracing::debug!(error = %e, "Failed to decode entry"),
         }
 
-        return Ok(out);
+        result
     }
 
-    async fn ranged_request(&self, range_string: &str) -> Result<Bytes, Error> {
-        self.http_client
-            .get(&self.url)
-            .header("Range", range_string)
-            .send()
-            .await
-            .map_err(|e| Error::NetworkError(self.url.clone(), e))?
-            .bytes()
-            .await
-            .map_err(|e| Error::NetworkError(self.url.clone(), e))
+    async fn stream_record<W: AsyncWrite + Unpin>(
+        &self,
+        cd_record: &CentralDirectoryRecord,
+        writer: &mut W,
+        progress: &mut ProgressTracker,
+    ) -> Result<LocalFile, Error> {
+        let (lfh, body) = self.open_body(cd_record).await?;
+
+        let mut decoder = match lfh.compression_method {
+            netzip_parser::CompressionMethod::Deflate
+            | netzip_parser::CompressionMethod::Deflate64 => Some(DeflateDecoder::new(Vec::new())),
+            netzip_parser::CompressionMethod::Stored => None,
+            netzip_parser::CompressionMethod::Unsupported(unsupported_id) => {
+                return Err(Error::UnsupportCompression(unsupported_id));
+            }
+        };
+        let decompression_error =
+            |e: std::io::Error| Error::DecompressionError(self.source.url().into(), e.to_string());
+        let write_error = |e| Error::IoError(cd_record.file_name.clone(), e);
+
+        let length = self.entry_extent(cd_record)?.compressed_size;
+        let mut received = 0;
+        let mut body = pin!(body);
+        while let Some(chunk) = body.next().await {
+            let chunk = chunk?;
+            received += chunk.len() as u64;
+            if received > length {
+                return Err(Error::IncompleteResponse(
+                    self.source.url().into(),
+                    length,
+                    received,
+                ));
+            }
+
+            let decompressed = match &mut decoder {
+                Some(decoder) => {
+                    decoder.write_all(&chunk).map_err(decompression_error)?;
+                    writer
+                        .write_all(decoder.get_ref())
+                        .await
+                        .map_err(write_error)?;
+                    let decompressed = decoder.get_ref().len();
+                    decoder.get_mut().clear();
+                    decompressed
+                }
+                None => {
+                    writer.write_all(&chunk).await.map_err(write_error)?;
+                    chunk.len()
+                }
+            };
+            progress.advance(chunk.len() as u64, decompressed as u64);
+        }
+
+        if received != length {
+            return Err(Error::IncompleteResponse(
+                self.source.url().into(),
+                length,
+                received,
+            ));
+        }
+
+        if let Some(decoder) = decoder {
+            let remaining = decoder.finish().map_err(decompression_error)?;
+            writer.write_all(&remaining).await.map_err(write_error)?;
+            progress.advance(0, remaining.len() as u64);
+        }
+        writer.flush().await.map_err(write_error)?;
+
+        Ok(lfh)
+    }
+
+    /// Fetches the local header of an entry and opens a stream over its compressed data. The caller is
+    /// responsible for checking the total length of the streamed body.
+    async fn open_body(
+        &self,
+        cd_record: &CentralDirectoryRecord,
+    ) -> Result<(LocalFile, BoxStream<'static, Result<Bytes, Error>>), Error> {
+        let (lfh, data_offset) = self.fetch_local_header(cd_record).await?;
+        let body = self
+            .source
+            .fetch_stream(data_offset, self.entry_extent(cd_record)?.compressed_size)
+            .await?;
+
+        Ok((lfh, body))
+    }
+
+    /// Fetches and parses the local file header of an entry, returning it along with the absolute offset
+    /// of the entry's data. The local extra field may differ in length from the central one, in which case
+    /// the header is fetched a second time with the correct length.
+    async fn fetch_local_header(
+        &self,
+        cd_record: &CentralDirectoryRecord,
+    ) -> Result<(LocalFile, u64), Error> {
+        let header_offset = self.source.disk_offset(cd_record.disk_number).await?
+            + self.entry_extent(cd_record)?.header_offset;
+        let mut lfh_bytes = self
+            .source
+            .fetch(
+                header_offset,
+                (netzip_parser::LFH_MIN_SIZE
+                    + cd_record.file_name_length as usize
+                    + cd_record.extra_field_length as usize) as u64,
+            )
+            .await?;
+
+        let header_length = LocalFile::header_length(&lfh_bytes)
+            .map_err(|e| Error::ParserError(self.source.url().into(), e))?;
+        if header_length > lfh_bytes.len() {
+            self.source.stats().record_wasted(lfh_bytes.len() as u64);
+            lfh_bytes = self
+                .source
+                .fetch(header_offset, header_length as u64)
+                .await?;
+        }
+        self.source
+            .stats()
+            .record_wasted((lfh_bytes.len() - header_length) as u64);
+
+        let lfh = LocalFile::parse(&lfh_bytes[..header_length])
+            .map_err(|e| Error::ParserError(self.source.url().into(), e))?;
+
+        Ok((lfh, header_offset + header_length as u64))
+    }
+
+    /// Resolves the sizes and local header offset of an entry, reading them from its ZIP64 extra field where
+    /// the central record only holds placeholders.
+    fn entry_extent(&self, cd_record: &CentralDirectoryRecord) -> Result<EntryExtent, Error> {
+        let zip64 = Zip64ExtraField::from_record(cd_record)
+            .map_err(|e| Error::ParserError(self.source.url().into(), e))?
+            .unwrap_or_default();
+
+        Ok(EntryExtent {
+            compressed_size: zip64
+                .compressed_size
+                .unwrap_or(cd_record.compressed_size as u64),
+            uncompressed_size: zip64
+                .uncompressed_size
+                .unwrap_or(cd_record.uncompressed_size as u64),
+            header_offset: zip64
+                .file_header_offset
+                .unwrap_or(cd_record.file_header_offset as u64),
+        })
     }
 }
 
-async fn ranged_request(
-    url: &str,
-    range_string: &str,
-    client: reqwest::Client,
-) -> Result<Bytes, Error> {
-    client
-        .get(url)
-        .header("Range", range_string)
-        .send()
-        .await
-        .map_err(|e| Error::NetworkError(url.into(), e))?
-        .bytes()
-        .await
-        .map_err(|e| Error::NetworkError(url.into(), e))
+/// The sizes and local header offset of an entry, which may exceed the 32 bit fields of its central record.
+#[derive(Debug, Clone, Copy)]
+struct EntryExtent {
+    compressed_size: u64,
+    uncompressed_size: u64,
+    header_offset: u64,
+}
+
+/// An in-memory writer that fails once more than `limit` bytes are written to it, bounding the memory used by
+/// entries whose declared size cannot be trusted.
+struct LimitedBuffer {
+    data: Vec<u8>,
+    limit: u64,
+    exceeded: bool,
+}
+
+impl LimitedBuffer {
+    fn new(expected_size: u64, limit: u64) -> Self {
+        Self {
+            data: Vec::with_capacity(expected_size.min(limit) as usize),
+            limit,
+            exceeded: false,
+        }
+    }
+}
+
+impl AsyncWrite for LimitedBuffer {
+    fn poll_write(
+        mut self: Pin<&mut Self>,
+        _cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<std::io::Result<usize>> {
+        if (self.data.len() + buf.len()) as u64 > self.limit {
+            self.exceeded = true;
+            return Poll::Ready(Err(std::io::Error::other(format!(
+                "more than {} bytes written to a limited buffer",
+                self.limit
+            ))));
+        }
+
+        self.data.extend_from_slice(buf);
+        Poll::Ready(Ok(buf.len()))
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<std::io::Result<()>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<std::io::Result<()>> {
+        Poll::Ready(Ok(()))
+    }
+}
+
+/// Returns a path next to `destination` to write to before atomically renaming it into place.
+fn temp_path_for(destination: &Path) -> PathBuf {
+    static COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+    let file_name = destination
+        .file_name()
+        .map(|x| x.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    destination.with_file_name(format!(
+        ".{file_name}.{}-{}.netzip-tmp",
+        std::process::id(),
+        COUNTER.fetch_add(1, Ordering::Relaxed)
+    ))
 }
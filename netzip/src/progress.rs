@@ -0,0 +1,125 @@
+use std::sync::Arc;
+
+use netzip_parser::CentralDirectoryRecord;
+
+/// The part of an operation a [`Progress`] update refers to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ProgressStage {
+    /// The central directory is being fetched and parsed.
+    #[default]
+    Directory,
+    /// Entries are being downloaded and decompressed.
+    Entries,
+}
+
+/// The state of a running RemoteZip operation, passed to a [`ProgressListener`].
+///
+/// Totals are taken from the central directory before any entry data is fetched, so they can be used to
+/// draw progress bars and estimate the remaining time.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct Progress {
+    /// The part of the operation that is running.
+    pub stage: ProgressStage,
+    /// The path of the entry currently being downloaded, if any.
+    pub entry: Option<String>,
+    /// The number of entries completed, successfully or not.
+    pub entries_done: usize,
+    /// The number of entries the operation will process.
+    pub entries_total: usize,
+    /// The number of compressed bytes downloaded so far.
+    pub bytes_downloaded: u64,
+    /// The number of compressed bytes the operation will download. For the directory stage, this is the
+    /// size of the central directory.
+    pub bytes_total: u64,
+    /// The number of bytes produced by decompression so far.
+    pub bytes_decompressed: u64,
+    /// The number of bytes decompression will produce in total.
+    pub bytes_decompressed_total: u64,
+}
+
+/// Receives progress updates from RemoteZip operations, see [`RemoteZipBuilder::progress`](crate::RemoteZipBuilder::progress).
+///
+/// Updates are sent from the task running the operation, so implementations should return quickly.
+/// Implemented for closures taking a `&Progress`.
+pub trait ProgressListener: Send + Sync {
+    /// Called whenever the operation made progress.
+    fn update(&self, progress: &Progress);
+}
+
+impl<F: Fn(&Progress) + Send + Sync> ProgressListener for F {
+    fn update(&self, progress: &Progress) {
+        self(progress)
+    }
+}
+
+/// Tracks the progress of a single operation and forwards it to the listener, if there is one.
+pub(crate) struct ProgressTracker {
+    listener: Option<Arc<dyn ProgressListener>>,
+    progress: Progress,
+}
+
+impl ProgressTracker {
+    pub(crate) fn directory(listener: Option<Arc<dyn ProgressListener>>, size: u64) -> Self {
+        let tracker = Self {
+            listener,
+            progress: Progress {
+                stage: ProgressStage::Directory,
+                bytes_total: size,
+                ..Default::default()
+            },
+        };
+        tracker.emit();
+        tracker
+    }
+
+    pub(crate) fn entries<'a>(
+        listener: Option<Arc<dyn ProgressListener>>,
+        records: impl IntoIterator<Item = &'a CentralDirectoryRecord>,
+    ) -> Self {
+        let mut progress = Progress {
+            stage: ProgressStage::Entries,
+            ..Default::default()
+        };
+        for record in records {
+            progress.entries_total += 1;
+            progress.bytes_total += record.compressed_size as u64;
+            progress.bytes_decompressed_total += record.uncompressed_size as u64;
+        }
+
+        let tracker = Self { listener, progress };
+        tracker.emit();
+        tracker
+    }
+
+    /// Records the parsed directory, completing the directory stage.
+    pub(crate) fn directory_parsed(&mut self, entries: usize) {
+        self.progress.bytes_downloaded = self.progress.bytes_total;
+        self.progress.entries_done = entries;
+        self.progress.entries_total = entries;
+        self.emit();
+    }
+
+    pub(crate) fn start_entry(&mut self, path: &str) {
+        self.progress.entry = Some(path.into());
+        self.emit();
+    }
+
+    pub(crate) fn advance(&mut self, downloaded: u64, decompressed: u64) {
+        self.progress.bytes_downloaded += downloaded;
+        self.progress.bytes_decompressed += decompressed;
+        self.emit();
+    }
+
+    pub(crate) fn finish_entry(&mut self) {
+        self.progress.entry = None;
+        self.progress.entries_done += 1;
+        self.emit();
+    }
+
+    fn emit(&self) {
+        if let Some(listener) = &self.listener {
+            listener.update(&self.progress);
+        }
+    }
+}
@@ -0,0 +1,124 @@
+use std::{
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use bytes::Bytes;
+use futures_util::{StreamExt, stream::BoxStream};
+
+use crate::Error;
+
+/// A token bucket limiting the rate at which range response bodies are received, shared by any number of
+/// RemoteZip instances.
+///
+/// The bucket starts out full and refills at the configured rate. Received data is taken from the bucket as it
+/// arrives, and reading pauses while the bucket is in debt, letting TCP flow control slow down the sender.
+/// Only HTTP sources are limited, local archives are read at full speed.
+///
+/// Cloning a RateLimiter is cheap and the clones share their bucket, so all instances using them are limited
+/// to the rate together.
+#[derive(Debug, Clone)]
+pub struct RateLimiter {
+    bucket: Arc<Mutex<Bucket>>,
+}
+
+#[derive(Debug)]
+struct Bucket {
+    rate: f64,
+    burst: f64,
+    tokens: f64,
+    updated: Instant,
+}
+
+impl RateLimiter {
+    /// Creates a limiter allowing bursts of up to one second worth of data.
+    ///
+    /// # Arguments
+    ///
+    /// * `bytes_per_second` - The sustained rate in bytes per second, at least 1
+    ///
+    /// # Returns
+    ///
+    /// The RateLimiter
+    pub fn new(bytes_per_second: u64) -> Self {
+        Self::with_burst(bytes_per_second, bytes_per_second)
+    }
+
+    /// Creates a limiter with a custom bucket size.
+    ///
+    /// # Arguments
+    ///
+    /// * `bytes_per_second` - The sustained rate in bytes per second, at least 1
+    /// * `burst` - The number of bytes that may be received at full speed after a pause
+    ///
+    /// # Returns
+    ///
+    /// The RateLimiter
+    pub fn with_burst(bytes_per_second: u64, burst: u64) -> Self {
+        Self {
+            bucket: Arc::new(Mutex::new(Bucket {
+                rate: bytes_per_second.max(1) as f64,
+                burst: burst as f64,
+                tokens: burst as f64,
+                updated: Instant::now(),
+            })),
+        }
+    }
+
+    /// Returns the sustained rate in bytes per second.
+    pub fn bytes_per_second(&self) -> u64 {
+        self.bucket.lock().unwrap().rate as u64
+    }
+
+    /// Takes the given number of bytes from the bucket, waiting until the debt they put it in is paid off.
+    pub(crate) async fn acquire(&self, bytes: u64) {
+        let wait = {
+            let mut bucket = self.bucket.lock().unwrap();
+            let now = Instant::now();
+            let refilled = now.duration_since(bucket.updated).as_secs_f64() * bucket.rate;
+            bucket.tokens = (bucket.tokens + refilled).min(bucket.burst) - bytes as f64;
+            bucket.updated = now;
+
+            (bucket.tokens < 0.0).then(|| Duration::from_secs_f64(-bucket.tokens / bucket.rate))
+        };
+
+        if let Some(wait) = wait {
+            tokio::time::sleep(wait).await;
+        }
+    }
+
+    /// Wraps a response body, holding back each chunk until the bucket allows it.
+    pub(crate) fn throttle(
+        &self,
+        body: BoxStream<'static, Result<Bytes, Error>>,
+    ) -> BoxStream<'static, Result<Bytes, Error>> {
+        let limiter = self.clone();
+        body.then(move |chunk| {
+            let limiter = limiter.clone();
+            async move {
+                if let Ok(chunk) = &chunk {
+                    limiter.acquire(chunk.len() as u64).await;
+                }
+                chunk
+            }
+        })
+        .boxed()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn waits_for_debt() {
+        let limiter = RateLimiter::with_burst(100_000, 10_000);
+
+        let started = Instant::now();
+        limiter.acquire(10_000).await;
+        assert!(started.elapsed() < Duration::from_millis(50));
+
+        limiter.clone().acquire(20_000).await;
+        assert!(started.elapsed() >= Duration::from_millis(190));
+    }
+}
@@ -0,0 +1,173 @@
+use std::{
+    io::SeekFrom,
+    pin::Pin,
+    task::{Context, Poll, ready},
+};
+
+use bytes::Bytes;
+use futures_util::future::BoxFuture;
+use tokio::io::{AsyncRead, AsyncSeek, ReadBuf};
+
+use crate::{Error, source::SharedSource};
+
+/// The default number of bytes fetched per range request by a [`StoredEntryReader`].
+pub const DEFAULT_READ_AHEAD: u64 = 64 * 1024;
+
+/// A seekable reader over the contents of a stored (uncompressed) entry of a remote archive.
+///
+/// Reads are served from a buffer that is refilled with a range request whenever the position leaves it,
+/// so seeking is free and only the bytes around the positions actually read are downloaded.
+/// Obtained from [`RemoteZip::open_stored`](crate::RemoteZip::open_stored).
+pub struct StoredEntryReader {
+    source: SharedSource,
+    data_offset: u64,
+    length: u64,
+    position: u64,
+    read_ahead: u64,
+    buffer: Bytes,
+    buffer_start: u64,
+    buffer_consumed: u64,
+    pending: Option<(u64, BoxFuture<'static, Result<Bytes, Error>>)>,
+}
+
+impl StoredEntryReader {
+    pub(crate) fn new(source: SharedSource, data_offset: u64, length: u64) -> Self {
+        Self {
+            source,
+            data_offset,
+            length,
+            position: 0,
+            read_ahead: DEFAULT_READ_AHEAD,
+            buffer: Bytes::new(),
+            buffer_start: 0,
+            buffer_consumed: 0,
+            pending: None,
+        }
+    }
+
+    /// Sets the minimum number of bytes fetched per range request. Larger values mean fewer requests for
+    /// sequential reads, smaller values mean less over-fetching for scattered reads.
+    pub fn with_read_ahead(mut self, read_ahead: u64) -> Self {
+        self.read_ahead = read_ahead.max(1);
+        self
+    }
+
+    /// Returns the length of the entry in bytes.
+    pub fn len(&self) -> u64 {
+        self.length
+    }
+
+    /// Returns true if the entry is empty.
+    pub fn is_empty(&self) -> bool {
+        self.length == 0
+    }
+
+    /// Returns the current read position within the entry.
+    pub fn position(&self) -> u64 {
+        self.position
+    }
+
+    /// Counts the part of the buffer that was never read as wasted, before it is replaced or dropped.
+    fn discard_buffer(&mut self) {
+        let unused = (self.buffer.len() as u64).saturating_sub(self.buffer_consumed);
+        self.source.stats().record_wasted(unused);
+        self.buffer = Bytes::new();
+        self.buffer_consumed = 0;
+    }
+
+    fn buffered(&self) -> Option<&[u8]> {
+        let buffer_end = self.buffer_start + self.buffer.len() as u64;
+        if self.position >= self.buffer_start && self.position < buffer_end {
+            Some(&self.buffer[(self.position - self.buffer_start) as usize..])
+        } else {
+            None
+        }
+    }
+}
+
+impl AsyncRead for StoredEntryReader {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<std::io::Result<()>> {
+        let this = self.get_mut();
+        if this.position >= this.length || buf.remaining() == 0 {
+            return Poll::Ready(Ok(()));
+        }
+
+        if this.buffered().is_none() {
+            if this
+                .pending
+                .as_ref()
+                .is_some_and(|(start, _)| *start != this.position)
+            {
+                this.pending = None;
+            }
+
+            if this.pending.is_none() {
+                let start = this.position;
+                let length = this
+                    .read_ahead
+                    .max(buf.remaining() as u64)
+                    .min(this.length - start);
+                let source = this.source.clone();
+                let offset = this.data_offset + start;
+
+                this.pending = Some((
+                    start,
+                    Box::pin(async move { source.fetch(offset, length).await }),
+                ));
+            }
+
+            let Some((start, fetch)) = &mut this.pending else {
+                unreachable!("A pending fetch was just created");
+            };
+            let result = ready!(fetch.as_mut().poll(cx));
+            this.buffer_start = *start;
+            this.pending = None;
+            this.discard_buffer();
+            this.buffer = result.map_err(std::io::Error::other)?;
+        }
+
+        let available = this.buffered().unwrap_or_default();
+        let count = available.len().min(buf.remaining());
+        buf.put_slice(&available[..count]);
+        this.position += count as u64;
+        this.buffer_consumed = this.buffer_consumed.max(this.position - this.buffer_start);
+
+        Poll::Ready(Ok(()))
+    }
+}
+
+impl Drop for StoredEntryReader {
+    fn drop(&mut self) {
+        self.discard_buffer();
+    }
+}
+
+impl AsyncSeek for StoredEntryReader {
+    fn start_seek(self: Pin<&mut Self>, position: SeekFrom) -> std::io::Result<()> {
+        let this = self.get_mut();
+        let new_position = match position {
+            SeekFrom::Start(x) => Some(x),
+            SeekFrom::End(x) => this.length.checked_add_signed(x),
+            SeekFrom::Current(x) => this.position.checked_add_signed(x),
+        };
+
+        match new_position {
+            Some(x) => {
+                this.position = x;
+                Ok(())
+            }
+            None => Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                "Invalid seek to a negative or overflowing position",
+            )),
+        }
+    }
+
+    fn poll_complete(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<std::io::Result<u64>> {
+        Poll::Ready(Ok(self.position))
+    }
+}
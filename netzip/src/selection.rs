@@ -0,0 +1,218 @@
+use globset::{GlobBuilder, GlobMatcher};
+use netzip_parser::CentralDirectoryRecord;
+use regex::Regex;
+
+use crate::Error;
+
+/// A single path pattern used by an [`EntrySelector`].
+#[derive(Debug, Clone)]
+pub enum Pattern {
+    /// A shell-style glob. `*` and `?` never match `/`, use `**` to cross directories.
+    Glob(GlobMatcher),
+    /// A regular expression matched against the full entry path.
+    Regex(Regex),
+}
+
+impl Pattern {
+    /// Compiles a glob pattern such as `data/2024/**/*.json`.
+    ///
+    /// # Arguments
+    ///
+    /// * `pattern` - The glob to compile
+    ///
+    /// # Returns
+    ///
+    /// A Result containing either the compiled Pattern or an Error if the glob is invalid
+    pub fn glob(pattern: &str) -> Result<Self, Error> {
+        GlobBuilder::new(pattern)
+            .literal_separator(true)
+            .build()
+            .map(|glob| Self::Glob(glob.compile_matcher()))
+            .map_err(|e| Error::InvalidPattern(pattern.into(), e.to_string()))
+    }
+
+    /// Compiles a regular expression. The expression is not anchored, use `^` and `$` to match whole paths.
+    ///
+    /// # Arguments
+    ///
+    /// * `pattern` - The regular expression to compile
+    ///
+    /// # Returns
+    ///
+    /// A Result containing either the compiled Pattern or an Error if the expression is invalid
+    pub fn regex(pattern: &str) -> Result<Self, Error> {
+        Regex::new(pattern)
+            .map(Self::Regex)
+            .map_err(|e| Error::InvalidPattern(pattern.into(), e.to_string()))
+    }
+
+    /// Compiles a glob that matches exactly the given path, escaping any glob metacharacters in it.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - The literal entry path to match
+    pub fn literal(path: &str) -> Self {
+        Self::glob(&globset::escape(path)).expect("Escaped glob must be valid")
+    }
+
+    /// Checks whether the pattern matches the given entry path.
+    pub fn is_match(&self, path: &str) -> bool {
+        match self {
+            Self::Glob(glob) => glob.is_match(path),
+            Self::Regex(regex) => regex.is_match(path),
+        }
+    }
+}
+
+/// Selects entries of an archive by path using include and exclude pattern lists.
+///
+/// An entry is selected if it matches at least one include pattern (or no include patterns were added)
+/// and matches none of the exclude patterns. Directory entries are never selected.
+#[derive(Debug, Clone, Default)]
+pub struct EntrySelector {
+    include: Vec<Pattern>,
+    exclude: Vec<Pattern>,
+}
+
+impl EntrySelector {
+    /// Creates an empty selector, which selects every file entry.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a pattern to the include list.
+    pub fn include(mut self, pattern: Pattern) -> Self {
+        self.include.push(pattern);
+        self
+    }
+
+    /// Adds a pattern to the exclude list.
+    pub fn exclude(mut self, pattern: Pattern) -> Self {
+        self.exclude.push(pattern);
+        self
+    }
+
+    /// Compiles a glob and adds it to the include list.
+    ///
+    /// # Arguments
+    ///
+    /// * `pattern` - The glob to include
+    ///
+    /// # Returns
+    ///
+    /// A Result containing either the updated EntrySelector or an Error if the glob is invalid
+    pub fn include_glob(self, pattern: &str) -> Result<Self, Error> {
+        Ok(self.include(Pattern::glob(pattern)?))
+    }
+
+    /// Compiles a glob and adds it to the exclude list.
+    ///
+    /// # Arguments
+    ///
+    /// * `pattern` - The glob to exclude
+    ///
+    /// # Returns
+    ///
+    /// A Result containing either the updated EntrySelector or an Error if the glob is invalid
+    pub fn exclude_glob(self, pattern: &str) -> Result<Self, Error> {
+        Ok(self.exclude(Pattern::glob(pattern)?))
+    }
+
+    /// Compiles a regular expression and adds it to the include list.
+    ///
+    /// # Arguments
+    ///
+    /// * `pattern` - The regular expression to include
+    ///
+    /// # Returns
+    ///
+    /// A Result containing either the updated EntrySelector or an Error if the expression is invalid
+    pub fn include_regex(self, pattern: &str) -> Result<Self, Error> {
+        Ok(self.include(Pattern::regex(pattern)?))
+    }
+
+    /// Compiles a regular expression and adds it to the exclude list.
+    ///
+    /// # Arguments
+    ///
+    /// * `pattern` - The regular expression to exclude
+    ///
+    /// # Returns
+    ///
+    /// A Result containing either the updated EntrySelector or an Error if the expression is invalid
+    pub fn exclude_regex(self, pattern: &str) -> Result<Self, Error> {
+        Ok(self.exclude(Pattern::regex(pattern)?))
+    }
+
+    /// Checks whether a path is selected, ignoring whether it names a directory.
+    pub fn matches(&self, path: &str) -> bool {
+        (self.include.is_empty() || self.include.iter().any(|x| x.is_match(path)))
+            && !self.exclude.iter().any(|x| x.is_match(path))
+    }
+
+    /// Checks whether a central directory record is selected.
+    pub fn matches_record(&self, record: &CentralDirectoryRecord) -> bool {
+        !record.is_directory() && self.matches(&record.file_name)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn glob_selection() {
+        let selector = EntrySelector::new()
+            .include_glob("data/2024/**/*.json")
+            .unwrap()
+            .exclude_glob("**/skip-*")
+            .unwrap();
+
+        assert!(selector.matches("data/2024/a.json"));
+        assert!(selector.matches("data/2024/03/b.json"));
+        assert!(!selector.matches("data/2024/03/skip-b.json"));
+        assert!(!selector.matches("data/2023/a.json"));
+        assert!(!selector.matches("data/2024/a.csv"));
+    }
+
+    #[test]
+    fn star_does_not_cross_directories() {
+        let selector = EntrySelector::new().include_glob("*.txt").unwrap();
+
+        assert!(selector.matches("readme.txt"));
+        assert!(!selector.matches("docs/readme.txt"));
+    }
+
+    #[test]
+    fn regex_and_literal_selection() {
+        let selector = EntrySelector::new()
+            .include_regex(r"^logs/\d+\.log$")
+            .unwrap()
+            .include(Pattern::literal("weird[1].txt"));
+
+        assert!(selector.matches("logs/17.log"));
+        assert!(!selector.matches("logs/a.log"));
+        assert!(selector.matches("weird[1].txt"));
+        assert!(!selector.matches("weird1.txt"));
+    }
+
+    #[test]
+    fn empty_selector_selects_everything() {
+        let selector = EntrySelector::new().exclude_glob("*.tmp").unwrap();
+
+        assert!(selector.matches("a/b/c"));
+        assert!(!selector.matches("x.tmp"));
+    }
+
+    #[test]
+    fn invalid_patterns() {
+        assert!(matches!(
+            Pattern::glob("a[").unwrap_err(),
+            Error::InvalidPattern(..)
+        ));
+        assert!(matches!(
+            Pattern::regex("(").unwrap_err(),
+            Error::InvalidPattern(..)
+        ));
+    }
+}
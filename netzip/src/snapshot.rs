@@ -0,0 +1,164 @@
+use std::{
+    io::{Read, Write},
+    path::Path,
+};
+
+use netzip_parser::CentralDirectoryRecord;
+use serde::{Deserialize, Serialize};
+
+use crate::{Error, source::ArchiveInfo};
+
+const SNAPSHOT_MAGIC: [u8; 4] = *b"NZDS";
+const SNAPSHOT_VERSION: u8 = 1;
+
+/// A parsed central directory together with the validators of the archive it was read from.
+///
+/// Restoring a RemoteZip from a snapshot needs no network access. The validators are compared against the
+/// first range response afterwards, failing with [`Error::ArchiveChanged`] if the archive was modified.
+/// Obtained from [`RemoteZip::snapshot`](crate::RemoteZip::snapshot).
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DirectorySnapshot {
+    pub(crate) url: String,
+    pub(crate) info: Option<ArchiveInfo>,
+    pub(crate) records: Vec<CentralDirectoryRecord>,
+}
+
+impl DirectorySnapshot {
+    /// Returns the URL the archive was read from.
+    pub fn url(&self) -> &str {
+        &self.url
+    }
+
+    /// Returns the central directory records in the snapshot.
+    pub fn records(&self) -> &[CentralDirectoryRecord] {
+        &self.records
+    }
+
+    /// Writes the snapshot in a compact binary format.
+    ///
+    /// # Arguments
+    ///
+    /// * `writer` - The writer to serialize the snapshot to
+    ///
+    /// # Returns
+    ///
+    /// A Result indicating whether the snapshot was written successfully
+    pub fn write_to<W: Write>(&self, mut writer: W) -> Result<(), Error> {
+        let invalid = |e: bincode::Error| Error::InvalidSnapshot(e.to_string());
+
+        writer
+            .write_all(&SNAPSHOT_MAGIC)
+            .and_then(|_| writer.write_all(&[SNAPSHOT_VERSION]))
+            .map_err(|e| Error::InvalidSnapshot(e.to_string()))?;
+        bincode::serialize_into(&mut writer, self).map_err(invalid)?;
+        writer
+            .flush()
+            .map_err(|e| Error::InvalidSnapshot(e.to_string()))
+    }
+
+    /// Reads a snapshot previously written with [`DirectorySnapshot::write_to`].
+    ///
+    /// # Arguments
+    ///
+    /// * `reader` - The reader to deserialize the snapshot from
+    ///
+    /// # Returns
+    ///
+    /// A Result containing either the DirectorySnapshot or an Error if the data is not a valid snapshot
+    pub fn read_from<R: Read>(mut reader: R) -> Result<Self, Error> {
+        let mut header = [0u8; 5];
+        reader
+            .read_exact(&mut header)
+            .map_err(|e| Error::InvalidSnapshot(e.to_string()))?;
+        if header[..4] != SNAPSHOT_MAGIC || header[4] != SNAPSHOT_VERSION {
+            return Err(Error::InvalidSnapshot("unrecognized header".into()));
+        }
+
+        bincode::deserialize_from(reader).map_err(|e| Error::InvalidSnapshot(e.to_string()))
+    }
+
+    /// Saves the snapshot to a file.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - The path of the file to create or overwrite
+    ///
+    /// # Returns
+    ///
+    /// A Result indicating whether the snapshot was saved successfully
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<(), Error> {
+        let path = path.as_ref();
+        let file = std::fs::File::create(path)
+            .map_err(|e| Error::IoError(path.display().to_string(), e))?;
+        self.write_to(std::io::BufWriter::new(file))
+    }
+
+    /// Loads a snapshot from a file.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - The path of a file written by [`DirectorySnapshot::save`]
+    ///
+    /// # Returns
+    ///
+    /// A Result containing either the DirectorySnapshot or an Error if the file could not be read or is invalid
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, Error> {
+        let path = path.as_ref();
+        let file =
+            std::fs::File::open(path).map_err(|e| Error::IoError(path.display().to_string(), e))?;
+        Self::read_from(std::io::BufReader::new(file))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use netzip_parser::CompressionMethod;
+
+    use super::*;
+
+    #[test]
+    fn snapshot_round_trip() {
+        let snapshot = DirectorySnapshot {
+            url: "http://example.com/a.zip".into(),
+            info: Some(ArchiveInfo {
+                length: 1234,
+                etag: Some("\"abc\"".into()),
+                last_modified: None,
+            }),
+            records: vec![CentralDirectoryRecord {
+                zip_version_created: 20,
+                zip_version_required: 20,
+                gp_bit_flag: 0,
+                compression_method: CompressionMethod::Unsupported(12),
+                last_modification_time: 1,
+                last_modification_date: 2,
+                crc32: 3,
+                compressed_size: 4,
+                uncompressed_size: 5,
+                file_name_length: 5,
+                extra_field_length: 2,
+                file_comment_length: 1,
+                disk_number: 0,
+                file_attributes_internal: 6,
+                file_attributes_external: 7,
+                file_header_offset: 8,
+                file_name: "a.txt".into(),
+                extra_bytes: Some(vec![1, 2]),
+                comment: Some("c".into()),
+            }],
+        };
+
+        let mut serialized = Vec::new();
+        snapshot.write_to(&mut serialized).unwrap();
+        assert_eq!(
+            DirectorySnapshot::read_from(&serialized[..]).unwrap(),
+            snapshot
+        );
+
+        serialized[4] = 0;
+        assert!(matches!(
+            DirectorySnapshot::read_from(&serialized[..]),
+            Err(Error::InvalidSnapshot(_))
+        ));
+    }
+}
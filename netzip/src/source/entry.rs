@@ -0,0 +1,117 @@
+use std::sync::Arc;
+
+use bytes::Bytes;
+use futures_util::{future::BoxFuture, stream::BoxStream};
+
+#[cfg(feature = "serde")]
+use super::ArchiveInfo;
+use super::{RangeSource, SharedSource};
+use crate::{Error, stats::StatsRecorder};
+
+/// Reads byte ranges of a stored (uncompressed) entry of another archive, such as a ZIP inside a ZIP.
+#[derive(Debug)]
+pub(crate) struct EntrySource {
+    outer: SharedSource,
+    name: String,
+    data_offset: u64,
+    length: u64,
+}
+
+impl EntrySource {
+    pub(crate) fn new(outer: SharedSource, path: &str, data_offset: u64, length: u64) -> Self {
+        Self {
+            name: format!("{}!/{}", outer.url(), path),
+            outer,
+            data_offset,
+            length,
+        }
+    }
+
+    /// Fails if a range extends past the end of the entry.
+    fn check_range(&self, offset: u64, length: u64) -> Result<(), Error> {
+        let available = self.length.saturating_sub(offset);
+        if length > available {
+            return Err(Error::IncompleteResponse(
+                self.name.clone(),
+                length,
+                available,
+            ));
+        }
+
+        Ok(())
+    }
+}
+
+impl RangeSource for EntrySource {
+    fn url(&self) -> &str {
+        &self.name
+    }
+
+    fn fetch_tail(&self, length: u64) -> BoxFuture<'_, Result<Bytes, Error>> {
+        let length = length.min(self.length);
+        self.outer
+            .fetch(self.data_offset + self.length - length, length)
+    }
+
+    fn fetch(&self, offset: u64, length: u64) -> BoxFuture<'_, Result<Bytes, Error>> {
+        Box::pin(async move {
+            self.check_range(offset, length)?;
+            self.outer.fetch(self.data_offset + offset, length).await
+        })
+    }
+
+    fn fetch_stream(
+        &self,
+        offset: u64,
+        length: u64,
+    ) -> BoxFuture<'_, Result<BoxStream<'static, Result<Bytes, Error>>, Error>> {
+        Box::pin(async move {
+            self.check_range(offset, length)?;
+            self.outer
+                .fetch_stream(self.data_offset + offset, length)
+                .await
+        })
+    }
+
+    fn length(&self) -> Option<u64> {
+        Some(self.length)
+    }
+
+    fn stats(&self) -> &Arc<StatsRecorder> {
+        self.outer.stats()
+    }
+
+    #[cfg(feature = "serde")]
+    fn info(&self) -> Option<ArchiveInfo> {
+        self.outer.info()
+    }
+
+    #[cfg(feature = "serde")]
+    fn expect_info(&self, info: Option<ArchiveInfo>) {
+        self.outer.expect_info(info);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::source::MemorySource;
+
+    #[tokio::test]
+    async fn reads_within_entry() {
+        let outer = MemorySource::new(
+            "outer.zip".into(),
+            Bytes::from_static(b"headerENTRYtrailer"),
+        );
+        let source = EntrySource::new(Arc::new(outer), "inner.zip", 6, 5);
+
+        assert_eq!(source.url(), "outer.zip!/inner.zip");
+        assert_eq!(&source.fetch(1, 3).await.unwrap()[..], b"NTR");
+        assert_eq!(&source.fetch_tail(2).await.unwrap()[..], b"RY");
+        assert_eq!(&source.fetch_tail(100).await.unwrap()[..], b"ENTRY");
+        assert!(matches!(
+            source.fetch(3, 3).await,
+            Err(Error::IncompleteResponse(_, 3, 2))
+        ));
+    }
+}
@@ -0,0 +1,241 @@
+use std::{
+    fs::File,
+    path::PathBuf,
+    sync::{Arc, Mutex},
+    time::{Instant, UNIX_EPOCH},
+};
+
+use bytes::Bytes;
+use futures_util::{
+    StreamExt,
+    future::BoxFuture,
+    stream::{self, BoxStream},
+};
+
+use super::{ArchiveInfo, RangeSource, chunked_stream};
+use crate::{Error, stats::StatsRecorder};
+
+/// The largest read made at once when streaming from a local file.
+const FILE_STREAM_CHUNK: u64 = 256 * 1024;
+
+/// Reads byte ranges of an archive on the local file system using positioned reads.
+#[derive(Debug, Clone)]
+pub(crate) struct FileSource {
+    path: Arc<PathBuf>,
+    name: String,
+    file: Arc<Mutex<Option<Arc<File>>>>,
+    info: Arc<Mutex<Option<ArchiveInfo>>>,
+    stats: Arc<StatsRecorder>,
+}
+
+impl FileSource {
+    pub(crate) fn new(path: PathBuf) -> Self {
+        Self {
+            name: path.display().to_string(),
+            path: Arc::new(path),
+            file: Arc::new(Mutex::new(None)),
+            info: Arc::new(Mutex::new(None)),
+            stats: Arc::default(),
+        }
+    }
+
+    /// Records transfers in the given counters instead of counters of its own.
+    pub(crate) fn with_stats(mut self, stats: Arc<StatsRecorder>) -> Self {
+        self.stats = stats;
+        self
+    }
+
+    /// Returns the local path a URL refers to, if it is a `file://` URL or has no scheme at all.
+    pub(crate) fn path_for(url: &str) -> Option<PathBuf> {
+        if url.starts_with("file://") {
+            return Some(
+                reqwest::Url::parse(url)
+                    .ok()
+                    .and_then(|x| x.to_file_path().ok())
+                    .unwrap_or_else(|| PathBuf::from(&url["file://".len()..])),
+            );
+        }
+
+        (!url.contains("://")).then(|| PathBuf::from(url))
+    }
+
+    /// Returns the open file, opening it on first use.
+    fn file(&self) -> Result<Arc<File>, Error> {
+        let mut file = self.file.lock().unwrap();
+        if let Some(file) = file.as_ref() {
+            return Ok(file.clone());
+        }
+
+        let opened = Arc::new(
+            File::open(self.path.as_path()).map_err(|e| Error::IoError(self.name.clone(), e))?,
+        );
+        *file = Some(opened.clone());
+        Ok(opened)
+    }
+
+    /// Reads up to `length` bytes at `offset`, or the last `length` bytes of the file if `offset` is None.
+    async fn read(&self, offset: Option<u64>, length: u64) -> Result<Bytes, Error> {
+        let file = self.file()?;
+        let name = self.name.clone();
+
+        event!(debug, path = %self.name, ?offset, length, "Reading range");
+        self.stats.record_request(length);
+        let started = Instant::now();
+        let result = tokio::task::spawn_blocking(move || {
+            let io_error = |e| Error::IoError(name.clone(), e);
+            let metadata = file.metadata().map_err(io_error)?;
+            let info = ArchiveInfo {
+                length: metadata.len(),
+                etag: None,
+                last_modified: metadata
+                    .modified()
+                    .ok()
+                    .and_then(|x| x.duration_since(UNIX_EPOCH).ok())
+                    .map(|x| format!("{}.{:09}", x.as_secs(), x.subsec_nanos())),
+            };
+
+            let offset = offset.unwrap_or(info.length.saturating_sub(length));
+            let available = info.length.saturating_sub(offset).min(length);
+            let mut data = vec![0; available as usize];
+            read_exact_at(&file, &mut data, offset).map_err(io_error)?;
+
+            Ok::<_, Error>((info, data))
+        })
+        .await
+        .map_err(|e| Error::IoError(self.name.clone(), e.into()));
+        self.stats.record_elapsed(started.elapsed());
+
+        let (info, data) = result??;
+        self.stats.record_received(data.len() as u64);
+        info.verify(&self.info, &self.name)?;
+        Ok(Bytes::from(data))
+    }
+}
+
+impl RangeSource for FileSource {
+    fn url(&self) -> &str {
+        &self.name
+    }
+
+    fn fetch_tail(&self, length: u64) -> BoxFuture<'_, Result<Bytes, Error>> {
+        Box::pin(self.read(None, length))
+    }
+
+    fn fetch(&self, offset: u64, length: u64) -> BoxFuture<'_, Result<Bytes, Error>> {
+        Box::pin(async move {
+            if length == 0 {
+                return Ok(Bytes::new());
+            }
+
+            let bytes = self.read(Some(offset), length).await?;
+            if bytes.len() as u64 != length {
+                return Err(Error::IncompleteResponse(
+                    self.name.clone(),
+                    length,
+                    bytes.len() as u64,
+                ));
+            }
+
+            Ok(bytes)
+        })
+    }
+
+    fn fetch_stream(
+        &self,
+        offset: u64,
+        length: u64,
+    ) -> BoxFuture<'_, Result<BoxStream<'static, Result<Bytes, Error>>, Error>> {
+        Box::pin(async move {
+            if length == 0 {
+                return Ok(stream::empty().boxed());
+            }
+
+            Ok(chunked_stream(
+                self.clone(),
+                offset,
+                length,
+                FILE_STREAM_CHUNK,
+            ))
+        })
+    }
+
+    fn length(&self) -> Option<u64> {
+        self.info.lock().unwrap().as_ref().map(|x| x.length)
+    }
+
+    fn stats(&self) -> &Arc<StatsRecorder> {
+        &self.stats
+    }
+
+    #[cfg(feature = "serde")]
+    fn info(&self) -> Option<ArchiveInfo> {
+        self.info.lock().unwrap().clone()
+    }
+
+    #[cfg(feature = "serde")]
+    fn expect_info(&self, info: Option<ArchiveInfo>) {
+        *self.info.lock().unwrap() = info;
+    }
+}
+
+#[cfg(unix)]
+fn read_exact_at(file: &File, buf: &mut [u8], offset: u64) -> std::io::Result<()> {
+    std::os::unix::fs::FileExt::read_exact_at(file, buf, offset)
+}
+
+#[cfg(windows)]
+fn read_exact_at(file: &File, mut buf: &mut [u8], mut offset: u64) -> std::io::Result<()> {
+    while !buf.is_empty() {
+        match std::os::windows::fs::FileExt::seek_read(file, buf, offset) {
+            Ok(0) => return Err(std::io::ErrorKind::UnexpectedEof.into()),
+            Ok(count) => {
+                buf = &mut buf[count..];
+                offset += count as u64;
+            }
+            Err(e) if e.kind() == std::io::ErrorKind::Interrupted => {}
+            Err(e) => return Err(e),
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn recognizes_local_paths() {
+        assert_eq!(
+            FileSource::path_for("file:///tmp/a%20b.zip"),
+            Some(PathBuf::from("/tmp/a b.zip"))
+        );
+        assert_eq!(
+            FileSource::path_for("archives/a.zip"),
+            Some(PathBuf::from("archives/a.zip"))
+        );
+        assert_eq!(FileSource::path_for("https://example.com/a.zip"), None);
+    }
+
+    #[tokio::test]
+    async fn reads_ranges() {
+        let path = std::env::temp_dir().join(format!("netzip-file-{}", std::process::id()));
+        std::fs::write(&path, b"0123456789").unwrap();
+        let source = FileSource::new(path.clone());
+
+        assert_eq!(&source.fetch(2, 3).await.unwrap()[..], b"234");
+        assert_eq!(&source.fetch_tail(4).await.unwrap()[..], b"6789");
+        assert_eq!(&source.fetch_tail(20).await.unwrap()[..], b"0123456789");
+        assert!(matches!(
+            source.fetch(8, 3).await,
+            Err(Error::IncompleteResponse(_, 3, 2))
+        ));
+
+        std::fs::write(&path, b"01234567890").unwrap();
+        assert!(matches!(
+            source.fetch(0, 1).await,
+            Err(Error::ArchiveChanged(_))
+        ));
+        let _ = std::fs::remove_file(&path);
+    }
+}
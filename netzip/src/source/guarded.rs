@@ -0,0 +1,106 @@
+use std::sync::Arc;
+
+use bytes::Bytes;
+use futures_util::{
+    StreamExt,
+    future::BoxFuture,
+    stream::{self, BoxStream},
+};
+
+#[cfg(feature = "serde")]
+use super::ArchiveInfo;
+use super::{RangeSource, SharedSource};
+use crate::{Error, cancel::Cancellation, stats::StatsRecorder};
+
+/// Stops fetches and response bodies of another source once its RemoteZip is cancelled or past its deadline.
+#[derive(Debug)]
+pub(crate) struct GuardedSource {
+    inner: SharedSource,
+    cancellation: Arc<Cancellation>,
+}
+
+impl GuardedSource {
+    pub(crate) fn new(inner: SharedSource, cancellation: Arc<Cancellation>) -> Self {
+        Self {
+            inner,
+            cancellation,
+        }
+    }
+}
+
+impl RangeSource for GuardedSource {
+    fn url(&self) -> &str {
+        self.inner.url()
+    }
+
+    fn fetch_tail(&self, length: u64) -> BoxFuture<'_, Result<Bytes, Error>> {
+        Box::pin(async move {
+            self.cancellation
+                .run(self.url(), self.inner.fetch_tail(length))
+                .await?
+        })
+    }
+
+    fn fetch(&self, offset: u64, length: u64) -> BoxFuture<'_, Result<Bytes, Error>> {
+        Box::pin(async move {
+            self.cancellation
+                .run(self.url(), self.inner.fetch(offset, length))
+                .await?
+        })
+    }
+
+    fn fetch_stream(
+        &self,
+        offset: u64,
+        length: u64,
+    ) -> BoxFuture<'_, Result<BoxStream<'static, Result<Bytes, Error>>, Error>> {
+        Box::pin(async move {
+            let body = self
+                .cancellation
+                .run(self.url(), self.inner.fetch_stream(offset, length))
+                .await??;
+
+            // Every chunk is awaited under the cancellation, and the body ends after the first error
+            let state = (body, self.cancellation.clone(), self.url().to_string());
+            Ok(stream::unfold(Some(state), |state| async move {
+                let (mut body, cancellation, url) = state?;
+                match cancellation.run(&url, body.next()).await {
+                    Ok(Some(chunk)) => Some((chunk, Some((body, cancellation, url)))),
+                    Ok(None) => None,
+                    Err(e) => Some((Err(e), None)),
+                }
+            })
+            .boxed())
+        })
+    }
+
+    fn disk_offset(&self, disk: u16) -> BoxFuture<'_, Result<u64, Error>> {
+        Box::pin(async move {
+            self.cancellation
+                .run(self.url(), self.inner.disk_offset(disk))
+                .await?
+        })
+    }
+
+    fn disk_count(&self) -> usize {
+        self.inner.disk_count()
+    }
+
+    fn length(&self) -> Option<u64> {
+        self.inner.length()
+    }
+
+    fn stats(&self) -> &Arc<StatsRecorder> {
+        self.inner.stats()
+    }
+
+    #[cfg(feature = "serde")]
+    fn info(&self) -> Option<ArchiveInfo> {
+        self.inner.info()
+    }
+
+    #[cfg(feature = "serde")]
+    fn expect_info(&self, info: Option<ArchiveInfo>) {
+        self.inner.expect_info(info);
+    }
+}
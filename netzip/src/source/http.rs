@@ -0,0 +1,342 @@
+use std::{
+    sync::{Arc, Mutex},
+    time::Instant,
+};
+
+use bytes::{Bytes, BytesMut};
+use futures_util::{
+    StreamExt,
+    future::BoxFuture,
+    stream::{self, BoxStream},
+};
+use reqwest::header::{CONTENT_RANGE, CONTENT_TYPE, ETAG, LAST_MODIFIED};
+
+use super::{ArchiveInfo, RangeSource, chunked_stream};
+use crate::{Error, RangeCache, RateLimiter, stats::StatsRecorder};
+
+/// The number of cache blocks fetched at once when streaming through a [`RangeCache`].
+const CACHED_STREAM_BLOCKS: u64 = 16;
+
+impl ArchiveInfo {
+    fn from_response(response: &reqwest::Response) -> Option<Self> {
+        let header = |name| {
+            response
+                .headers()
+                .get(name)
+                .and_then(|x: &reqwest::header::HeaderValue| x.to_str().ok())
+                .map(String::from)
+        };
+
+        let length = header(CONTENT_RANGE)?.rsplit('/').next()?.parse().ok()?;
+
+        Some(Self {
+            length,
+            etag: header(ETAG),
+            last_modified: header(LAST_MODIFIED),
+        })
+    }
+
+    /// Returns the strongest available validator, if the server sent any.
+    fn validator(&self) -> Option<&str> {
+        self.etag.as_deref().or(self.last_modified.as_deref())
+    }
+}
+
+/// Fetches byte ranges of a remote archive over HTTP.
+#[derive(Debug, Clone)]
+pub(crate) struct HttpSource {
+    url: String,
+    client: reqwest::Client,
+    cache: Option<RangeCache>,
+    rate_limit: Option<RateLimiter>,
+    info: Arc<Mutex<Option<ArchiveInfo>>>,
+    stats: Arc<StatsRecorder>,
+}
+
+impl HttpSource {
+    pub(crate) fn new(
+        url: &str,
+        client: reqwest::Client,
+        cache: Option<RangeCache>,
+        rate_limit: Option<RateLimiter>,
+    ) -> Self {
+        Self {
+            url: url.into(),
+            client,
+            cache,
+            rate_limit,
+            info: Arc::new(Mutex::new(None)),
+            stats: Arc::default(),
+        }
+    }
+
+    /// Records transfers in the given counters instead of counters of its own.
+    pub(crate) fn with_stats(mut self, stats: Arc<StatsRecorder>) -> Self {
+        self.stats = stats;
+        self
+    }
+
+    /// Returns the cache together with the key and length of this archive, if caching is possible.
+    fn cache_key(&self) -> Option<(RangeCache, String, u64)> {
+        let cache = self.cache.as_ref()?;
+        let info = self.info.lock().unwrap();
+        let info = info.as_ref()?;
+        let key = cache.key(&self.url, info.validator()?, info.length);
+
+        Some((cache.clone(), key, info.length))
+    }
+
+    /// Serves a range from cached blocks, fetching runs of missing blocks with one request each.
+    async fn fetch_cached(
+        &self,
+        cache: &RangeCache,
+        key: &str,
+        archive_length: u64,
+        offset: u64,
+        length: u64,
+    ) -> Result<Bytes, Error> {
+        let block_size = cache.block_size();
+        let end = (offset + length).min(archive_length);
+        if offset >= end {
+            return Ok(Bytes::new());
+        }
+
+        let first_block = offset / block_size;
+        let block_count = (end - 1) / block_size - first_block + 1;
+        let block_length = |block: u64| {
+            let start = (first_block + block) * block_size;
+            block_size.min(archive_length - start)
+        };
+
+        let mut blocks = Vec::with_capacity(block_count as usize);
+        for block in 0..block_count {
+            blocks.push(
+                cache
+                    .get(key, first_block + block, block_length(block))
+                    .await,
+            );
+        }
+
+        event!(
+            trace,
+            url = %self.url,
+            offset,
+            length,
+            blocks = block_count,
+            cached = blocks.iter().filter(|x| x.is_some()).count(),
+            "Looked up range in cache"
+        );
+
+        let mut block = 0;
+        while block < block_count {
+            if blocks[block as usize].is_some() {
+                block += 1;
+                continue;
+            }
+
+            let run_start = block;
+            while block < block_count && blocks[block as usize].is_none() {
+                block += 1;
+            }
+
+            let fetch_start = (first_block + run_start) * block_size;
+            let fetch_end = ((first_block + block) * block_size).min(archive_length);
+            let fetched = self
+                .request(
+                    &format!("bytes={}-{}", fetch_start, fetch_end - 1),
+                    fetch_end - fetch_start,
+                )
+                .await?;
+            if fetched.len() as u64 != fetch_end - fetch_start {
+                return Err(Error::IncompleteResponse(
+                    self.url.clone(),
+                    fetch_end - fetch_start,
+                    fetched.len() as u64,
+                ));
+            }
+            let needed = fetch_end.min(end) - fetch_start.max(offset);
+            self.stats.record_wasted(fetched.len() as u64 - needed);
+
+            for fetched_block in run_start..block {
+                let start = ((fetched_block - run_start) * block_size) as usize;
+                let data = fetched.slice(start..start + block_length(fetched_block) as usize);
+                cache.put(key, first_block + fetched_block, &data).await;
+                blocks[fetched_block as usize] = Some(data);
+            }
+        }
+
+        let mut out = BytesMut::with_capacity((block_count * block_size) as usize);
+        for data in blocks.into_iter().flatten() {
+            out.extend_from_slice(&data);
+        }
+
+        let skip = (offset - first_block * block_size) as usize;
+        Ok(out.freeze().slice(skip..skip + (end - offset) as usize))
+    }
+
+    async fn request(&self, range_string: &str, requested: u64) -> Result<Bytes, Error> {
+        let mut body = self.body(range_string, requested).await?;
+        let mut bytes = BytesMut::with_capacity(requested as usize);
+        while let Some(chunk) = body.next().await {
+            bytes.extend_from_slice(&chunk?);
+        }
+
+        Ok(bytes.freeze())
+    }
+
+    /// Sends a range request and returns its body, metered and held to the rate limit if there is one.
+    async fn body(
+        &self,
+        range_string: &str,
+        requested: u64,
+    ) -> Result<BoxStream<'static, Result<Bytes, Error>>, Error> {
+        let started = Instant::now();
+        let response = self.response(range_string, requested).await;
+        self.stats.record_elapsed(started.elapsed());
+
+        let url = self.url.clone();
+        let body = self.stats.meter(
+            response?
+                .bytes_stream()
+                .map(move |x| x.map_err(|e| Error::NetworkError(url.clone(), e))),
+        );
+
+        // Throttled outside the meter, so time spent waiting on the limiter is not counted as waiting for data
+        Ok(match &self.rate_limit {
+            Some(limiter) => limiter.throttle(body),
+            None => body,
+        })
+    }
+
+    /// Sends a range request, failing if the server responds with anything but the requested range or
+    /// if the archive changed since the first response.
+    async fn response(
+        &self,
+        range_string: &str,
+        requested: u64,
+    ) -> Result<reqwest::Response, Error> {
+        self.stats.record_request(requested);
+        let response = self
+            .client
+            .get(&self.url)
+            .header("Range", range_string)
+            .send()
+            .await
+            .and_then(|x| x.error_for_status())
+            .map_err(|e| {
+                event!(debug, url = %self.url, range = range_string, error = %e, "Range request failed");
+                Error::NetworkError(self.url.clone(), e)
+            })?;
+        event!(
+            debug,
+            url = %self.url,
+            range = range_string,
+            status = response.status().as_u16(),
+            bytes = response.content_length(),
+            "Received range response"
+        );
+
+        if response.status() != reqwest::StatusCode::PARTIAL_CONTENT {
+            return Err(Error::RangeUnsupported(self.url.clone()));
+        }
+
+        let content_type = response.headers().get(CONTENT_TYPE);
+        if content_type.is_some_and(|x| x.as_bytes().starts_with(b"multipart/")) {
+            return Err(Error::UnexpectedResponse(
+                self.url.clone(),
+                "multipart range responses are not supported".into(),
+            ));
+        }
+
+        if let Some(received) = ArchiveInfo::from_response(&response) {
+            received.verify(&self.info, &self.url)?;
+        }
+
+        Ok(response)
+    }
+}
+
+impl RangeSource for HttpSource {
+    fn url(&self) -> &str {
+        &self.url
+    }
+
+    fn fetch_tail(&self, length: u64) -> BoxFuture<'_, Result<Bytes, Error>> {
+        Box::pin(async move {
+            if let Some((_, _, archive_length)) = self.cache_key() {
+                let start = archive_length.saturating_sub(length);
+                return self.fetch(start, archive_length - start).await;
+            }
+
+            self.request(&format!("bytes=-{length}"), length).await
+        })
+    }
+
+    fn fetch(&self, offset: u64, length: u64) -> BoxFuture<'_, Result<Bytes, Error>> {
+        Box::pin(async move {
+            if length == 0 {
+                return Ok(Bytes::new());
+            }
+
+            let bytes = match self.cache_key() {
+                Some((cache, key, archive_length)) => {
+                    self.fetch_cached(&cache, &key, archive_length, offset, length)
+                        .await?
+                }
+                None => {
+                    self.request(&format!("bytes={}-{}", offset, offset + length - 1), length)
+                        .await?
+                }
+            };
+
+            if bytes.len() as u64 != length {
+                return Err(Error::IncompleteResponse(
+                    self.url.clone(),
+                    length,
+                    bytes.len() as u64,
+                ));
+            }
+
+            Ok(bytes)
+        })
+    }
+
+    fn fetch_stream(
+        &self,
+        offset: u64,
+        length: u64,
+    ) -> BoxFuture<'_, Result<BoxStream<'static, Result<Bytes, Error>>, Error>> {
+        Box::pin(async move {
+            if length == 0 {
+                return Ok(stream::empty().boxed());
+            }
+
+            if let Some((cache, _, _)) = self.cache_key() {
+                // Go through the cache a few blocks at a time, keeping memory use bounded
+                let step = cache.block_size() * CACHED_STREAM_BLOCKS;
+                return Ok(chunked_stream(self.clone(), offset, length, step));
+            }
+
+            self.body(&format!("bytes={}-{}", offset, offset + length - 1), length)
+                .await
+        })
+    }
+
+    fn length(&self) -> Option<u64> {
+        self.info.lock().unwrap().as_ref().map(|x| x.length)
+    }
+
+    fn stats(&self) -> &Arc<StatsRecorder> {
+        &self.stats
+    }
+
+    #[cfg(feature = "serde")]
+    fn info(&self) -> Option<ArchiveInfo> {
+        self.info.lock().unwrap().clone()
+    }
+
+    #[cfg(feature = "serde")]
+    fn expect_info(&self, info: Option<ArchiveInfo>) {
+        *self.info.lock().unwrap() = info;
+    }
+}
@@ -0,0 +1,85 @@
+use std::sync::Arc;
+
+use bytes::Bytes;
+use futures_util::{
+    StreamExt,
+    future::BoxFuture,
+    stream::{self, BoxStream},
+};
+
+#[cfg(feature = "serde")]
+use super::ArchiveInfo;
+use super::RangeSource;
+use crate::{Error, stats::StatsRecorder};
+
+/// Serves byte ranges of an archive held in memory, such as a decompressed entry of another archive.
+#[derive(Debug)]
+pub(crate) struct MemorySource {
+    name: String,
+    data: Bytes,
+    stats: Arc<StatsRecorder>,
+}
+
+impl MemorySource {
+    pub(crate) fn new(name: String, data: Bytes) -> Self {
+        Self {
+            name,
+            data,
+            stats: Arc::default(),
+        }
+    }
+
+    fn slice(&self, offset: u64, length: u64) -> Result<Bytes, Error> {
+        let available = (self.data.len() as u64).saturating_sub(offset);
+        if length > available {
+            return Err(Error::IncompleteResponse(
+                self.name.clone(),
+                length,
+                available,
+            ));
+        }
+
+        Ok(self.data.slice(offset as usize..(offset + length) as usize))
+    }
+}
+
+impl RangeSource for MemorySource {
+    fn url(&self) -> &str {
+        &self.name
+    }
+
+    fn fetch_tail(&self, length: u64) -> BoxFuture<'_, Result<Bytes, Error>> {
+        let start = self.data.len().saturating_sub(length as usize);
+        Box::pin(futures_util::future::ready(Ok(self.data.slice(start..))))
+    }
+
+    fn fetch(&self, offset: u64, length: u64) -> BoxFuture<'_, Result<Bytes, Error>> {
+        Box::pin(futures_util::future::ready(self.slice(offset, length)))
+    }
+
+    fn fetch_stream(
+        &self,
+        offset: u64,
+        length: u64,
+    ) -> BoxFuture<'_, Result<BoxStream<'static, Result<Bytes, Error>>, Error>> {
+        Box::pin(futures_util::future::ready(self.slice(offset, length).map(
+            |x| stream::once(futures_util::future::ready(Ok(x))).boxed(),
+        )))
+    }
+
+    fn length(&self) -> Option<u64> {
+        Some(self.data.len() as u64)
+    }
+
+    fn stats(&self) -> &Arc<StatsRecorder> {
+        &self.stats
+    }
+
+    #[cfg(feature = "serde")]
+    fn info(&self) -> Option<ArchiveInfo> {
+        None
+    }
+
+    #[cfg(feature = "serde")]
+    fn expect_info(&self, _info: Option<ArchiveInfo>) {}
+}
@@ -0,0 +1,356 @@
+use std::{
+    sync::{
+        Arc, Mutex,
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+    },
+    time::{Duration, Instant},
+};
+
+use bytes::Bytes;
+use futures_util::{
+    StreamExt,
+    future::BoxFuture,
+    stream::{self, BoxStream},
+};
+
+#[cfg(feature = "serde")]
+use super::ArchiveInfo;
+use super::{RangeSource, SharedSource};
+use crate::{Error, stats::StatsRecorder};
+
+/// How long a mirror is only used as a last resort after a failed request.
+const MIRROR_COOLDOWN: Duration = Duration::from_secs(30);
+
+/// The number of bytes at the end of an archive compared to tell whether mirrors serve the same archive. They
+/// contain the end of central directory record, which fixes the location and size of the directory, and the
+/// last directory entries.
+const FINGERPRINT_LENGTH: u64 = netzip_parser::EOCD_MIN_SIZE as u64 + 1024;
+
+#[derive(Debug)]
+struct Mirror {
+    source: SharedSource,
+    verified: AtomicBool,
+    mismatched: AtomicBool,
+    failed_at: Mutex<Option<Instant>>,
+}
+
+impl Mirror {
+    fn cooling_down(&self) -> bool {
+        self.failed_at
+            .lock()
+            .unwrap()
+            .is_some_and(|x| x.elapsed() < MIRROR_COOLDOWN)
+    }
+}
+
+/// Spreads range requests for one archive across several sources serving identical copies of it, failing
+/// over to the next source when one errors or is slow. Streams failing or stalling partway are resumed on the
+/// next source.
+///
+/// Every mirror is verified on first use by comparing its length and the bytes at the end of the archive with
+/// those of the first mirror that responded. Mirrors serving something else are never used again.
+#[derive(Debug)]
+pub(crate) struct MirrorSource {
+    mirrors: Arc<Mirrors>,
+    stats: Arc<StatsRecorder>,
+}
+
+/// The state of a [MirrorSource], shared with the streams it opens.
+#[derive(Debug)]
+struct Mirrors {
+    mirrors: Vec<Mirror>,
+    timeout: Option<Duration>,
+    next: AtomicUsize,
+    fingerprint: Mutex<Option<(Option<u64>, Bytes)>>,
+}
+
+impl MirrorSource {
+    /// Creates a source over the given mirrors, the first of which names the archive. Every attempt on a
+    /// mirror, and every chunk streamed from it, is abandoned after `timeout`, if given.
+    pub(crate) fn new(
+        sources: Vec<SharedSource>,
+        timeout: Option<Duration>,
+        stats: Arc<StatsRecorder>,
+    ) -> Self {
+        Self {
+            mirrors: Arc::new(Mirrors {
+                mirrors: sources
+                    .into_iter()
+                    .map(|source| Mirror {
+                        source,
+                        verified: AtomicBool::new(false),
+                        mismatched: AtomicBool::new(false),
+                        failed_at: Mutex::new(None),
+                    })
+                    .collect(),
+                timeout,
+                next: AtomicUsize::new(0),
+                fingerprint: Mutex::new(None),
+            }),
+            stats,
+        }
+    }
+}
+
+impl Mirrors {
+    /// Returns the indices of the mirrors in the order to try them: round robin among the healthy ones,
+    /// followed by those that failed recently.
+    fn order(&self) -> Vec<usize> {
+        let start = self.next.fetch_add(1, Ordering::Relaxed);
+        let count = self.mirrors.len();
+        let mut order: Vec<usize> = (0..count)
+            .map(|x| (start + x) % count)
+            .filter(|x| !self.mirrors[*x].mismatched.load(Ordering::Relaxed))
+            .collect();
+        order.sort_by_key(|x| self.mirrors[*x].cooling_down());
+
+        order
+    }
+
+    /// Compares a mirror against the first verified one, unless it has been verified already.
+    async fn verify(&self, mirror: &Mirror) -> Result<(), Error> {
+        if mirror.verified.load(Ordering::Relaxed) {
+            return Ok(());
+        }
+
+        let tail = mirror.source.fetch_tail(FINGERPRINT_LENGTH).await?;
+        let fingerprint = (mirror.source.length(), tail);
+        {
+            let mut reference = self.fingerprint.lock().unwrap();
+            match reference.as_ref() {
+                Some(reference) if *reference != fingerprint => {
+                    mirror.mismatched.store(true, Ordering::Relaxed);
+                    return Err(Error::MirrorMismatch(mirror.source.url().into()));
+                }
+                Some(_) => {}
+                None => *reference = Some(fingerprint),
+            }
+        }
+
+        mirror.verified.store(true, Ordering::Relaxed);
+        Ok(())
+    }
+
+    /// Marks a mirror as failed, so it is only tried after the healthy ones for a while.
+    fn fail(&self, index: usize) {
+        *self.mirrors[index].failed_at.lock().unwrap() = Some(Instant::now());
+    }
+
+    /// Runs the operation on one mirror after another until it succeeds, returning the last error if all fail.
+    /// On success, the index of the mirror that succeeded is returned along with the result.
+    async fn run<'a, T>(
+        &'a self,
+        operation: impl Fn(&'a SharedSource) -> BoxFuture<'a, Result<T, Error>>,
+    ) -> Result<(usize, T), Error> {
+        let mut last_error = None;
+        for index in self.order() {
+            let mirror = &self.mirrors[index];
+            let attempt = async {
+                self.verify(mirror).await?;
+                operation(&mirror.source).await
+            };
+            let result = match self.timeout {
+                Some(timeout) => tokio::time::timeout(timeout, attempt)
+                    .await
+                    .unwrap_or_else(|_| Err(Error::TimedOut(mirror.source.url().into()))),
+                None => attempt.await,
+            };
+
+            match result {
+                Ok(value) => {
+                    *mirror.failed_at.lock().unwrap() = None;
+                    return Ok((index, value));
+                }
+                Err(e) => {
+                    event!(debug, url = mirror.source.url(), error = %e, "Mirror failed");
+                    self.fail(index);
+                    last_error = Some(e);
+                }
+            }
+        }
+
+        Err(last_error
+            .unwrap_or_else(|| Error::MirrorMismatch(self.mirrors[0].source.url().into())))
+    }
+
+    /// Continues a body opened on one mirror, resuming the remainder on the next mirror if the body fails,
+    /// ends early or a chunk takes longer than the timeout. Fails once every mirror has failed without
+    /// progress.
+    fn resume(
+        self: Arc<Self>,
+        offset: u64,
+        length: u64,
+        body: (usize, BoxStream<'static, Result<Bytes, Error>>),
+    ) -> BoxStream<'static, Result<Bytes, Error>> {
+        let state = ResumedStream {
+            mirrors: self,
+            offset,
+            remaining: length,
+            body: Some(body),
+            failures: 0,
+        };
+
+        stream::try_unfold(state, |mut state| async move {
+            loop {
+                if state.remaining == 0 {
+                    return Ok(None);
+                }
+
+                let (index, body) = match state.body.as_mut() {
+                    Some((index, body)) => (*index, body),
+                    None => {
+                        let (offset, remaining) = (state.offset, state.remaining);
+                        let opened = state
+                            .mirrors
+                            .run(|source| source.fetch_stream(offset, remaining))
+                            .await?;
+                        let (index, body) = state.body.insert(opened);
+                        (*index, body)
+                    }
+                };
+
+                let url = state.mirrors.mirrors[index].source.url();
+                let next = match state.mirrors.timeout {
+                    Some(timeout) => tokio::time::timeout(timeout, body.next())
+                        .await
+                        .unwrap_or_else(|_| Some(Err(Error::TimedOut(url.into())))),
+                    None => body.next().await,
+                };
+                let error = match next {
+                    Some(Ok(chunk)) => {
+                        state.offset += chunk.len() as u64;
+                        state.remaining = state.remaining.saturating_sub(chunk.len() as u64);
+                        state.failures = 0;
+                        return Ok(Some((chunk, state)));
+                    }
+                    Some(Err(e)) => e,
+                    None => Error::IncompleteResponse(url.into(), state.remaining, 0),
+                };
+
+                event!(debug, url = url, error = %error, "Mirror failed while streaming");
+                state.mirrors.fail(index);
+                state.body = None;
+                state.failures += 1;
+                if state.failures >= state.mirrors.mirrors.len() {
+                    return Err(error);
+                }
+            }
+        })
+        .boxed()
+    }
+}
+
+/// The progress of a stream over mirrors, see [Mirrors::resume].
+struct ResumedStream {
+    mirrors: Arc<Mirrors>,
+    offset: u64,
+    remaining: u64,
+    /// The body being read and the index of the mirror serving it
+    body: Option<(usize, BoxStream<'static, Result<Bytes, Error>>)>,
+    /// Failures since the last chunk was received
+    failures: usize,
+}
+
+impl RangeSource for MirrorSource {
+    fn url(&self) -> &str {
+        self.mirrors.mirrors[0].source.url()
+    }
+
+    fn fetch_tail(&self, length: u64) -> BoxFuture<'_, Result<Bytes, Error>> {
+        Box::pin(async move {
+            if length > FINGERPRINT_LENGTH {
+                return Ok(self
+                    .mirrors
+                    .run(|source| source.fetch_tail(length))
+                    .await?
+                    .1);
+            }
+
+            // Verifying a mirror fetches the tail, which is the same for all of them
+            self.mirrors.run(|_| Box::pin(async { Ok(()) })).await?;
+            let fingerprint = self.mirrors.fingerprint.lock().unwrap();
+            let (_, tail) = fingerprint
+                .as_ref()
+                .expect("A verified mirror should have set the fingerprint");
+            Ok(tail.slice(tail.len().saturating_sub(length as usize)..))
+        })
+    }
+
+    fn fetch(&self, offset: u64, length: u64) -> BoxFuture<'_, Result<Bytes, Error>> {
+        Box::pin(async move {
+            Ok(self
+                .mirrors
+                .run(move |source| source.fetch(offset, length))
+                .await?
+                .1)
+        })
+    }
+
+    fn fetch_stream(
+        &self,
+        offset: u64,
+        length: u64,
+    ) -> BoxFuture<'_, Result<BoxStream<'static, Result<Bytes, Error>>, Error>> {
+        Box::pin(async move {
+            let body = self
+                .mirrors
+                .run(move |source| source.fetch_stream(offset, length))
+                .await?;
+            Ok(self.mirrors.clone().resume(offset, length, body))
+        })
+    }
+
+    fn length(&self) -> Option<u64> {
+        self.mirrors.fingerprint.lock().unwrap().as_ref()?.0
+    }
+
+    fn stats(&self) -> &Arc<StatsRecorder> {
+        &self.stats
+    }
+
+    #[cfg(feature = "serde")]
+    fn info(&self) -> Option<ArchiveInfo> {
+        self.mirrors.mirrors[0].source.info()
+    }
+
+    /// Only the first mirror is expected to match the snapshot, as validators differ between servers. The
+    /// others are compared against whichever mirror is verified first.
+    #[cfg(feature = "serde")]
+    fn expect_info(&self, info: Option<ArchiveInfo>) {
+        self.mirrors.mirrors[0].source.expect_info(info);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::source::MemorySource;
+
+    fn source(name: &str, data: &'static [u8]) -> SharedSource {
+        Arc::new(MemorySource::new(name.into(), Bytes::from_static(data)))
+    }
+
+    #[tokio::test]
+    async fn skips_mismatched_mirrors() {
+        let mirrors = MirrorSource::new(
+            vec![
+                source("a", b"archive"),
+                source("b", b"ARCHIVE"),
+                source("c", b"archive"),
+            ],
+            None,
+            Arc::default(),
+        );
+
+        for _ in 0..3 {
+            assert_eq!(&mirrors.fetch(1, 3).await.unwrap()[..], b"rch");
+        }
+        assert_eq!(&mirrors.fetch_tail(3).await.unwrap()[..], b"ive");
+        assert_eq!(mirrors.length(), Some(7));
+        assert!(
+            mirrors.mirrors.mirrors[1]
+                .mismatched
+                .load(Ordering::Relaxed)
+        );
+    }
+}
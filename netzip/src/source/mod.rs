@@ -0,0 +1,127 @@
+use std::sync::{Arc, Mutex};
+
+use bytes::Bytes;
+use futures_util::{
+    StreamExt,
+    future::BoxFuture,
+    stream::{self, BoxStream},
+};
+
+use crate::{Error, stats::StatsRecorder};
+
+mod entry;
+mod file;
+mod guarded;
+mod http;
+mod memory;
+mod mirror;
+mod split;
+
+pub(crate) use entry::EntrySource;
+pub(crate) use file::FileSource;
+pub(crate) use guarded::GuardedSource;
+pub(crate) use http::HttpSource;
+pub(crate) use memory::MemorySource;
+pub(crate) use mirror::MirrorSource;
+pub(crate) use split::SplitSource;
+
+/// A range source shared between a RemoteZip and the readers it hands out.
+pub(crate) type SharedSource = Arc<dyn RangeSource>;
+
+/// Fetches byte ranges of an archive, wherever it is stored.
+pub(crate) trait RangeSource: std::fmt::Debug + Send + Sync {
+    /// Returns the URL or path of the archive, used to identify it in errors.
+    fn url(&self) -> &str;
+
+    /// Fetches the last `length` bytes of the archive. Fewer bytes are returned if the archive is shorter.
+    fn fetch_tail(&self, length: u64) -> BoxFuture<'_, Result<Bytes, Error>>;
+
+    /// Fetches `length` bytes starting at `offset`, failing if fewer bytes are available.
+    fn fetch(&self, offset: u64, length: u64) -> BoxFuture<'_, Result<Bytes, Error>>;
+
+    /// Streams `length` bytes starting at `offset`. The caller is responsible for checking the total length
+    /// of the streamed body.
+    fn fetch_stream(
+        &self,
+        offset: u64,
+        length: u64,
+    ) -> BoxFuture<'_, Result<BoxStream<'static, Result<Bytes, Error>>, Error>>;
+
+    /// Returns the offset at which a disk of a split archive starts, which offsets in the directory are relative
+    /// to. Archives stored in one piece consist of disk 0 only.
+    fn disk_offset(&self, disk: u16) -> BoxFuture<'_, Result<u64, Error>> {
+        let result = match disk {
+            0 => Ok(0),
+            _ => Err(Error::MissingDisk(self.url().into(), disk)),
+        };
+        Box::pin(std::future::ready(result))
+    }
+
+    /// Returns the number of segments the archive was given as, one for archives stored in one piece.
+    fn disk_count(&self) -> usize {
+        1
+    }
+
+    /// Returns the length of the archive, if it is known yet.
+    fn length(&self) -> Option<u64>;
+
+    /// Returns the transfer counters of this source.
+    fn stats(&self) -> &Arc<StatsRecorder>;
+
+    /// Returns the archive properties seen so far, if the archive has been accessed.
+    #[cfg(feature = "serde")]
+    fn info(&self) -> Option<ArchiveInfo>;
+
+    /// Sets the archive properties that later reads are expected to match.
+    #[cfg(feature = "serde")]
+    fn expect_info(&self, info: Option<ArchiveInfo>);
+}
+
+/// Identifying properties of an archive, taken from the first access and compared against all later ones
+/// to detect the archive changing underneath us.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub(crate) struct ArchiveInfo {
+    pub(crate) length: u64,
+    pub(crate) etag: Option<String>,
+    pub(crate) last_modified: Option<String>,
+}
+
+impl ArchiveInfo {
+    /// Records these properties if none are known yet, or fails if they differ from the known ones.
+    fn verify(self, known: &Mutex<Option<ArchiveInfo>>, url: &str) -> Result<(), Error> {
+        let mut known = known.lock().unwrap();
+        match known.as_ref() {
+            Some(known) if *known != self => Err(Error::ArchiveChanged(url.into())),
+            Some(_) => Ok(()),
+            None => {
+                *known = Some(self);
+                Ok(())
+            }
+        }
+    }
+}
+
+/// Streams a range as a sequence of fetches of at most `step` bytes each, keeping memory use bounded.
+fn chunked_stream<S: RangeSource + Clone + 'static>(
+    source: S,
+    offset: u64,
+    length: u64,
+    step: u64,
+) -> BoxStream<'static, Result<Bytes, Error>> {
+    let end = offset + length;
+
+    stream::try_unfold(offset, move |position| {
+        let source = source.clone();
+        async move {
+            if position >= end {
+                return Ok(None);
+            }
+
+            let chunk_length = step.min(end - position);
+            let chunk = source.fetch(position, chunk_length).await?;
+            Ok(Some((chunk, position + chunk_length)))
+        }
+    })
+    .boxed()
+}
@@ -0,0 +1,228 @@
+use std::sync::{Arc, Mutex};
+
+use bytes::{Bytes, BytesMut};
+use futures_util::{
+    StreamExt, TryStreamExt,
+    future::{BoxFuture, try_join_all},
+    stream::{self, BoxStream},
+};
+
+#[cfg(feature = "serde")]
+use super::ArchiveInfo;
+use super::{RangeSource, SharedSource};
+use crate::{Error, stats::StatsRecorder};
+
+/// Reads byte ranges of an archive split into segments, such as `archive.z01`, `archive.z02` and
+/// `archive.zip`, as if the segments were concatenated in disk order. Ranges crossing the end of a segment are
+/// fetched from every segment they cover.
+#[derive(Debug)]
+pub(crate) struct SplitSource {
+    segments: Vec<SharedSource>,
+    starts: Mutex<Option<Arc<[u64]>>>,
+    stats: Arc<StatsRecorder>,
+}
+
+impl SplitSource {
+    /// Creates a source over the segments in disk order, the last of which holds the end of central directory
+    /// and names the archive.
+    pub(crate) fn new(segments: Vec<SharedSource>, stats: Arc<StatsRecorder>) -> Self {
+        Self {
+            segments,
+            starts: Mutex::new(None),
+            stats,
+        }
+    }
+
+    /// Returns the offset of every segment in the concatenated archive followed by the archive's length,
+    /// fetching the length of segments not accessed yet on first use.
+    async fn starts(&self) -> Result<Arc<[u64]>, Error> {
+        if let Some(starts) = self.starts.lock().unwrap().clone() {
+            return Ok(starts);
+        }
+
+        let lengths = try_join_all(self.segments.iter().map(|segment| async move {
+            if segment.length().is_none() {
+                segment.fetch_tail(1).await?;
+            }
+            segment.length().ok_or_else(|| {
+                Error::UnexpectedResponse(segment.url().into(), "Unknown segment length".into())
+            })
+        }))
+        .await?;
+
+        let starts: Arc<[u64]> = std::iter::once(0)
+            .chain(lengths.iter().scan(0, |end, length| {
+                *end += length;
+                Some(*end)
+            }))
+            .collect();
+        *self.starts.lock().unwrap() = Some(starts.clone());
+        Ok(starts)
+    }
+
+    /// Splits a range of the concatenated archive into the ranges of the segments it covers, as segment
+    /// indices, offsets and lengths. Fails if the range extends past the end of the archive.
+    async fn pieces(&self, offset: u64, length: u64) -> Result<Vec<(usize, u64, u64)>, Error> {
+        let starts = self.starts().await?;
+        let available = starts[starts.len() - 1].saturating_sub(offset);
+        if length > available {
+            return Err(Error::IncompleteResponse(
+                self.url().into(),
+                length,
+                available,
+            ));
+        }
+
+        let end = offset + length;
+        Ok(starts
+            .windows(2)
+            .enumerate()
+            .filter_map(|(index, bounds)| {
+                let (start, stop) = (bounds[0].max(offset), bounds[1].min(end));
+                (start < stop).then(|| (index, start - bounds[0], stop - start))
+            })
+            .collect())
+    }
+}
+
+impl RangeSource for SplitSource {
+    fn url(&self) -> &str {
+        self.segments[self.segments.len() - 1].url()
+    }
+
+    fn fetch_tail(&self, length: u64) -> BoxFuture<'_, Result<Bytes, Error>> {
+        Box::pin(async move {
+            let starts = self.starts().await?;
+            let end = starts[starts.len() - 1];
+            let start = end.saturating_sub(length);
+            self.fetch(start, end - start).await
+        })
+    }
+
+    fn fetch(&self, offset: u64, length: u64) -> BoxFuture<'_, Result<Bytes, Error>> {
+        Box::pin(async move {
+            let pieces = self.pieces(offset, length).await?;
+            if let [(index, offset, length)] = pieces[..] {
+                return self.segments[index].fetch(offset, length).await;
+            }
+
+            let chunks = try_join_all(
+                pieces
+                    .into_iter()
+                    .map(|(index, offset, length)| self.segments[index].fetch(offset, length)),
+            )
+            .await?;
+            let mut joined = BytesMut::with_capacity(length as usize);
+            for chunk in chunks {
+                joined.extend_from_slice(&chunk);
+            }
+            Ok(joined.freeze())
+        })
+    }
+
+    /// Streams the covered segments one after another, opening each once the previous one has been read.
+    fn fetch_stream(
+        &self,
+        offset: u64,
+        length: u64,
+    ) -> BoxFuture<'_, Result<BoxStream<'static, Result<Bytes, Error>>, Error>> {
+        Box::pin(async move {
+            let pieces: Vec<_> = self
+                .pieces(offset, length)
+                .await?
+                .into_iter()
+                .map(|(index, offset, length)| (self.segments[index].clone(), offset, length))
+                .collect();
+
+            Ok(stream::iter(pieces)
+                .then(|(segment, offset, length)| async move {
+                    segment.fetch_stream(offset, length).await
+                })
+                .try_flatten()
+                .boxed())
+        })
+    }
+
+    fn disk_offset(&self, disk: u16) -> BoxFuture<'_, Result<u64, Error>> {
+        Box::pin(async move {
+            if disk as usize >= self.segments.len() {
+                return Err(Error::MissingDisk(self.url().into(), disk));
+            }
+
+            Ok(self.starts().await?[disk as usize])
+        })
+    }
+
+    fn disk_count(&self) -> usize {
+        self.segments.len()
+    }
+
+    fn length(&self) -> Option<u64> {
+        let starts = self.starts.lock().unwrap();
+        starts.as_ref().map(|x| x[x.len() - 1])
+    }
+
+    fn stats(&self) -> &Arc<StatsRecorder> {
+        &self.stats
+    }
+
+    /// Only the last segment is compared against snapshots, as it holds the directory the snapshot was taken
+    /// from.
+    #[cfg(feature = "serde")]
+    fn info(&self) -> Option<ArchiveInfo> {
+        self.segments[self.segments.len() - 1].info()
+    }
+
+    #[cfg(feature = "serde")]
+    fn expect_info(&self, info: Option<ArchiveInfo>) {
+        self.segments[self.segments.len() - 1].expect_info(info);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::source::MemorySource;
+
+    fn segments(data: &[&'static [u8]]) -> SplitSource {
+        let segments = data
+            .iter()
+            .enumerate()
+            .map(|(index, data)| -> SharedSource {
+                Arc::new(MemorySource::new(
+                    format!("archive.z0{index}"),
+                    Bytes::from_static(data),
+                ))
+            })
+            .collect();
+        SplitSource::new(segments, Arc::default())
+    }
+
+    #[tokio::test]
+    async fn reads_across_segments() {
+        let source = segments(&[b"spli", b"", b"t arch", b"ive"]);
+
+        assert_eq!(&source.fetch(2, 3).await.unwrap()[..], b"lit");
+        assert_eq!(&source.fetch(3, 8).await.unwrap()[..], b"it archi");
+        assert_eq!(&source.fetch_tail(5).await.unwrap()[..], b"chive");
+        assert_eq!(source.length(), Some(13));
+        let streamed: Vec<Bytes> = source
+            .fetch_stream(0, 13)
+            .await
+            .unwrap()
+            .try_collect()
+            .await
+            .unwrap();
+        assert_eq!(streamed.concat(), b"split archive");
+
+        assert_eq!(source.disk_offset(2).await.unwrap(), 4);
+        assert!(matches!(
+            source.disk_offset(4).await,
+            Err(Error::MissingDisk(_, 4))
+        ));
+        assert!(matches!(
+            source.fetch(10, 5).await,
+            Err(Error::IncompleteResponse(_, 5, 3))
+        ));
+    }
+}
@@ -0,0 +1,113 @@
+use std::{
+    sync::{
+        Arc,
+        atomic::{AtomicU64, Ordering},
+    },
+    task::Poll,
+    time::{Duration, Instant},
+};
+
+use bytes::Bytes;
+use futures_util::{
+    Stream, StreamExt,
+    stream::{self, BoxStream},
+};
+
+use crate::Error;
+
+/// Counters of the data transferred for a RemoteZip, obtained from [`RemoteZip::stats`](crate::RemoteZip::stats).
+///
+/// Archives opened with [`RemoteZip::open_nested`](crate::RemoteZip::open_nested) read through the outer
+/// archive and count towards its statistics as well.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct TransferStats {
+    /// The number of range requests sent, or positioned reads made for local archives.
+    pub requests: u64,
+    /// The total length of all requested ranges in bytes.
+    pub bytes_requested: u64,
+    /// The number of bytes received in response bodies.
+    pub bytes_received: u64,
+    /// The number of received bytes fetched around the data that was needed and never used, such as
+    /// padding to cache blocks, unread read-ahead and oversized header fetches.
+    pub bytes_wasted: u64,
+    /// The total time spent waiting for responses.
+    pub elapsed: Duration,
+}
+
+/// Shared atomic counters behind [`TransferStats`], updated by range sources and readers.
+#[derive(Debug, Default)]
+pub(crate) struct StatsRecorder {
+    requests: AtomicU64,
+    bytes_requested: AtomicU64,
+    bytes_received: AtomicU64,
+    bytes_wasted: AtomicU64,
+    elapsed_nanos: AtomicU64,
+}
+
+impl StatsRecorder {
+    pub(crate) fn record_request(&self, bytes_requested: u64) {
+        self.requests.fetch_add(1, Ordering::Relaxed);
+        self.bytes_requested
+            .fetch_add(bytes_requested, Ordering::Relaxed);
+    }
+
+    pub(crate) fn record_received(&self, bytes: u64) {
+        self.bytes_received.fetch_add(bytes, Ordering::Relaxed);
+    }
+
+    pub(crate) fn record_wasted(&self, bytes: u64) {
+        self.bytes_wasted.fetch_add(bytes, Ordering::Relaxed);
+    }
+
+    pub(crate) fn record_elapsed(&self, elapsed: Duration) {
+        self.elapsed_nanos
+            .fetch_add(elapsed.as_nanos() as u64, Ordering::Relaxed);
+    }
+
+    /// Wraps a response body, counting the bytes received and the time spent waiting for them.
+    pub(crate) fn meter(
+        self: &Arc<Self>,
+        body: impl Stream<Item = Result<Bytes, Error>> + Send + 'static,
+    ) -> BoxStream<'static, Result<Bytes, Error>> {
+        let stats = self.clone();
+        let mut body = body.boxed();
+        let mut waiting_since = None;
+
+        stream::poll_fn(move |cx| {
+            let started = *waiting_since.get_or_insert_with(Instant::now);
+            let Poll::Ready(item) = body.poll_next_unpin(cx) else {
+                return Poll::Pending;
+            };
+
+            waiting_since = None;
+            stats.record_elapsed(started.elapsed());
+            if let Some(Ok(chunk)) = &item {
+                stats.record_received(chunk.len() as u64);
+            }
+            Poll::Ready(item)
+        })
+        .boxed()
+    }
+
+    pub(crate) fn snapshot(&self) -> TransferStats {
+        TransferStats {
+            requests: self.requests.load(Ordering::Relaxed),
+            bytes_requested: self.bytes_requested.load(Ordering::Relaxed),
+            bytes_received: self.bytes_received.load(Ordering::Relaxed),
+            bytes_wasted: self.bytes_wasted.load(Ordering::Relaxed),
+            elapsed: Duration::from_nanos(self.elapsed_nanos.load(Ordering::Relaxed)),
+        }
+    }
+
+    pub(crate) fn reset(&self) {
+        for counter in [
+            &self.requests,
+            &self.bytes_requested,
+            &self.bytes_received,
+            &self.bytes_wasted,
+            &self.elapsed_nanos,
+        ] {
+            counter.store(0, Ordering::Relaxed);
+        }
+    }
+}
@@ -0,0 +1,162 @@
+use std::{io::Write, path::Path, pin::pin};
+
+use futures_util::{StreamExt, stream};
+use netzip_parser::{CentralDirectoryRecord, EntryOptions, ZipWriter};
+use tokio::io::{AsyncWrite, AsyncWriteExt};
+
+use crate::{Error, RemoteZip, progress::ProgressTracker, temp_path_for};
+
+impl RemoteZip {
+    /// Builds a new archive containing only the given entries and writes it to an asynchronous writer. The
+    /// entries' compressed data is copied as is, without decompressing and recompressing it, so each entry
+    /// costs one range request for its local header and data. Offsets and the central directory are
+    /// rewritten for the new archive.
+    ///
+    /// # Arguments
+    ///
+    /// * `paths` - The file paths/names of the entries to copy, in the order they are written
+    /// * `writer` - The writer to write the new archive to
+    ///
+    /// # Returns
+    ///
+    /// A Result containing either the central directory records of the new archive or an Error if an entry
+    /// could not be found, downloaded or written. The writer may have received a partial archive on error.
+    pub async fn subset_to_writer<W: AsyncWrite + Unpin>(
+        &self,
+        paths: Vec<String>,
+        writer: &mut W,
+    ) -> Result<Vec<CentralDirectoryRecord>, Error> {
+        let records = paths
+            .iter()
+            .map(|x| self.find_record(x))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        // The archive is written to a buffer which is handed to the writer after every chunk
+        let mut zip = ZipWriter::new(Vec::new());
+        let mut progress = self.track_entries(records.iter().copied());
+        for cd_record in records {
+            progress.start_entry(&cd_record.file_name);
+            let result = self
+                .copy_record(cd_record, &mut zip, writer, &mut progress)
+                .await;
+            progress.finish_entry();
+            result?;
+        }
+
+        let subset_records = zip.records().to_vec();
+        let write_error = |e| Error::IoError("central directory".into(), e);
+        let directory = zip.finish().map_err(write_error)?;
+        writer.write_all(&directory).await.map_err(write_error)?;
+        writer.flush().await.map_err(write_error)?;
+
+        Ok(subset_records)
+    }
+
+    /// Builds a new archive containing only the given entries and writes it to a path on disk, see
+    /// [`RemoteZip::subset_to_writer`]. Like [`RemoteZip::download_to_path`], the archive is written to a
+    /// temporary file first so no partial archive is left behind on failure or cancellation.
+    ///
+    /// # Arguments
+    ///
+    /// * `paths` - The file paths/names of the entries to copy, in the order they are written
+    /// * `destination` - The path on disk to write the new archive to
+    ///
+    /// # Returns
+    ///
+    /// A Result containing either the central directory records of the new archive or an Error if an entry
+    /// could not be found, downloaded or written
+    pub async fn subset_to_path(
+        &self,
+        paths: Vec<String>,
+        destination: impl AsRef<Path>,
+    ) -> Result<Vec<CentralDirectoryRecord>, Error> {
+        let destination = destination.as_ref();
+        let temp_path = temp_path_for(destination);
+        let io_error = |e| Error::IoError(destination.display().to_string(), e);
+
+        let result = async {
+            let mut file = tokio::io::BufWriter::new(
+                tokio::fs::File::create(&temp_path)
+                    .await
+                    .map_err(io_error)?,
+            );
+            let records = self.subset_to_writer(paths, &mut file).await?;
+            file.get_ref().sync_all().await.map_err(io_error)?;
+            tokio::fs::rename(&temp_path, destination)
+                .await
+                .map_err(io_error)?;
+            Ok(records)
+        }
+        .await;
+
+        if result.is_err() {
+            let _ = tokio::fs::remove_file(&temp_path).await;
+        }
+
+        result
+    }
+
+    /// Copies the compressed data of an entry into the archive being written, passing the archive's bytes on
+    /// to the writer as they are produced.
+    async fn copy_record<W: AsyncWrite + Unpin>(
+        &self,
+        cd_record: &CentralDirectoryRecord,
+        zip: &mut ZipWriter<Vec<u8>>,
+        writer: &mut W,
+        progress: &mut ProgressTracker,
+    ) -> Result<(), Error> {
+        let write_error = |e| Error::IoError(cd_record.file_name.clone(), e);
+        let extent = self.entry_extent(cd_record)?;
+        let length = extent.compressed_size;
+
+        // Entries without data, like directories, need nothing from the archive but their central record
+        let body = if length == 0 {
+            stream::empty().boxed()
+        } else {
+            self.open_body(cd_record).await?.1
+        };
+
+        zip.start_raw_entry(
+            &cd_record.file_name,
+            EntryOptions::from_record(cd_record),
+            cd_record.crc32,
+            extent.compressed_size,
+            extent.uncompressed_size,
+        )
+        .map_err(write_error)?;
+
+        let mut received = 0;
+        let mut body = pin!(body);
+        while let Some(chunk) = body.next().await {
+            let chunk = chunk?;
+            received += chunk.len() as u64;
+            if received > length {
+                return Err(Error::IncompleteResponse(
+                    self.source.url().into(),
+                    length,
+                    received,
+                ));
+            }
+
+            zip.write_all(&chunk).map_err(write_error)?;
+            let output = std::mem::take(zip.get_mut());
+            writer.write_all(&output).await.map_err(write_error)?;
+            progress.advance(chunk.len() as u64, 0);
+        }
+
+        if received != length {
+            return Err(Error::IncompleteResponse(
+                self.source.url().into(),
+                length,
+                received,
+            ));
+        }
+
+        zip.finish_entry().map_err(write_error)?;
+        let output = std::mem::take(zip.get_mut());
+        writer.write_all(&output).await.map_err(write_error)?;
+        progress.advance(0, extent.uncompressed_size);
+
+        Ok(())
+    }
+}
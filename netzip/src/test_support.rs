@@ -0,0 +1,287 @@
+//! An in-process HTTP server for testing code built on netzip without network access.
+//!
+//! [`MockServer`] serves a single archive from memory and can be told to misbehave in the ways real servers
+//! do, see [`MockBehavior`]. Requires the `test-support` feature.
+
+use std::{
+    net::SocketAddr,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use bytes::Bytes;
+use tokio::{
+    io::{AsyncBufReadExt, AsyncWriteExt, BufReader},
+    net::{TcpListener, TcpStream},
+    task::JoinHandle,
+};
+
+/// How a [`MockServer`] responds to requests. The default is a well behaved server with range support.
+#[derive(Debug, Clone)]
+pub struct MockBehavior {
+    ranges: bool,
+    multipart: bool,
+    delay: Option<Duration>,
+    disconnect_after: Option<usize>,
+    pause_after: Option<(usize, Duration)>,
+}
+
+impl Default for MockBehavior {
+    fn default() -> Self {
+        Self {
+            ranges: true,
+            multipart: false,
+            delay: None,
+            disconnect_after: None,
+            pause_after: None,
+        }
+    }
+}
+
+impl MockBehavior {
+    /// Creates the default behavior.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets whether range requests are honored. Without range support, the whole archive is sent with
+    /// status 200 for every request.
+    pub fn ranges(mut self, ranges: bool) -> Self {
+        self.ranges = ranges;
+        self
+    }
+
+    /// Sets whether ranges are sent as a single part `multipart/byteranges` body.
+    pub fn multipart(mut self, multipart: bool) -> Self {
+        self.multipart = multipart;
+        self
+    }
+
+    /// Delays every response by the given duration before anything is sent.
+    pub fn delay(mut self, delay: Duration) -> Self {
+        self.delay = Some(delay);
+        self
+    }
+
+    /// Closes the connection after sending the given number of body bytes, while announcing the full length.
+    pub fn disconnect_after(mut self, bytes: usize) -> Self {
+        self.disconnect_after = Some(bytes);
+        self
+    }
+
+    /// Stops sending for the given duration after the given number of body bytes, then sends the rest.
+    pub fn pause_after(mut self, bytes: usize, pause: Duration) -> Self {
+        self.pause_after = Some((bytes, pause));
+        self
+    }
+}
+
+#[derive(Debug)]
+struct ServerState {
+    content: Bytes,
+    version: u64,
+    behavior: MockBehavior,
+    requests: Vec<Option<String>>,
+}
+
+/// An HTTP server on a local port serving an archive from memory, stopped when dropped.
+///
+/// Responses carry an ETag that changes whenever the content is replaced. Unsatisfiable ranges are
+/// answered with status 416, as real servers do.
+pub struct MockServer {
+    address: SocketAddr,
+    state: Arc<Mutex<ServerState>>,
+    task: JoinHandle<()>,
+}
+
+impl MockServer {
+    /// Starts a server on a free local port. Must be called from within a tokio runtime.
+    ///
+    /// # Arguments
+    ///
+    /// * `content` - The archive to serve
+    ///
+    /// # Returns
+    ///
+    /// The running MockServer
+    pub async fn start(content: impl Into<Bytes>) -> Self {
+        let listener = TcpListener::bind("127.0.0.1:0")
+            .await
+            .expect("Binding to a local port should succeed");
+        let address = listener
+            .local_addr()
+            .expect("A bound listener should have an address");
+        let state = Arc::new(Mutex::new(ServerState {
+            content: content.into(),
+            version: 1,
+            behavior: MockBehavior::default(),
+            requests: Vec::new(),
+        }));
+
+        let task_state = state.clone();
+        let task = tokio::spawn(async move {
+            while let Ok((stream, _)) = listener.accept().await {
+                tokio::spawn(handle_connection(stream, task_state.clone()));
+            }
+        });
+
+        Self {
+            address,
+            state,
+            task,
+        }
+    }
+
+    /// Returns the URL the archive is served at.
+    pub fn url(&self) -> String {
+        format!("http://{}/archive.zip", self.address)
+    }
+
+    /// Changes how later requests are responded to.
+    pub fn set_behavior(&self, behavior: MockBehavior) {
+        self.state.lock().unwrap().behavior = behavior;
+    }
+
+    /// Replaces the served archive, as if it was modified on the server. The ETag changes accordingly.
+    pub fn set_content(&self, content: impl Into<Bytes>) {
+        let mut state = self.state.lock().unwrap();
+        state.content = content.into();
+        state.version += 1;
+    }
+
+    /// Returns the Range header of every request received so far, or None for requests without one.
+    pub fn requests(&self) -> Vec<Option<String>> {
+        self.state.lock().unwrap().requests.clone()
+    }
+
+    /// Forgets the requests received so far.
+    pub fn clear_requests(&self) {
+        self.state.lock().unwrap().requests.clear();
+    }
+}
+
+impl Drop for MockServer {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
+
+/// A byte range requested with a Range header, resolved against the content length.
+enum RequestedRange {
+    Satisfiable(usize, usize),
+    Unsatisfiable,
+}
+
+fn parse_range(header: &str, length: usize) -> Option<RequestedRange> {
+    let (start, end) = header.trim().strip_prefix("bytes=")?.split_once('-')?;
+    let (start, end) = match (start.trim(), end.trim()) {
+        ("", suffix) => {
+            let suffix: usize = suffix.parse().ok()?;
+            (length.saturating_sub(suffix), length)
+        }
+        (start, "") => (start.parse().ok()?, length),
+        (start, end) => (
+            start.parse().ok()?,
+            end.parse::<usize>().ok()?.saturating_add(1).min(length),
+        ),
+    };
+
+    if start >= length || start >= end {
+        return Some(RequestedRange::Unsatisfiable);
+    }
+    Some(RequestedRange::Satisfiable(start, end))
+}
+
+async fn handle_connection(stream: TcpStream, state: Arc<Mutex<ServerState>>) {
+    let mut stream = BufReader::new(stream);
+
+    let mut range = None;
+    let mut line = String::new();
+    loop {
+        line.clear();
+        match stream.read_line(&mut line).await {
+            Ok(0) | Err(_) => return,
+            Ok(_) if line.trim_end().is_empty() => break,
+            Ok(_) => {}
+        }
+
+        if let Some((name, value)) = line.split_once(':')
+            && name.trim().eq_ignore_ascii_case("range")
+        {
+            range = Some(value.trim().to_string());
+        }
+    }
+
+    let (content, etag, behavior) = {
+        let mut state = state.lock().unwrap();
+        state.requests.push(range.clone());
+        (
+            state.content.clone(),
+            format!("\"v{}\"", state.version),
+            state.behavior.clone(),
+        )
+    };
+
+    if let Some(delay) = behavior.delay {
+        tokio::time::sleep(delay).await;
+    }
+
+    let length = content.len();
+    let requested = range
+        .filter(|_| behavior.ranges)
+        .and_then(|x| parse_range(&x, length));
+    let (status, headers, body) = match requested {
+        None => ("200 OK", String::new(), content),
+        Some(RequestedRange::Unsatisfiable) => (
+            "416 Range Not Satisfiable",
+            format!("Content-Range: bytes */{length}\r\n"),
+            Bytes::new(),
+        ),
+        Some(RequestedRange::Satisfiable(start, end)) if behavior.multipart => {
+            let mut body = format!(
+                "--netzip-boundary\r\nContent-Type: application/zip\r\nContent-Range: bytes {}-{}/{length}\r\n\r\n",
+                start,
+                end - 1
+            )
+            .into_bytes();
+            body.extend_from_slice(&content[start..end]);
+            body.extend_from_slice(b"\r\n--netzip-boundary--\r\n");
+
+            (
+                "206 Partial Content",
+                "Content-Type: multipart/byteranges; boundary=netzip-boundary\r\n".into(),
+                Bytes::from(body),
+            )
+        }
+        Some(RequestedRange::Satisfiable(start, end)) => (
+            "206 Partial Content",
+            format!("Content-Range: bytes {}-{}/{length}\r\n", start, end - 1),
+            content.slice(start..end),
+        ),
+    };
+
+    let head = format!(
+        "HTTP/1.1 {status}\r\nContent-Length: {}\r\nETag: {etag}\r\nAccept-Ranges: bytes\r\nConnection: close\r\n{headers}\r\n",
+        body.len()
+    );
+    let sent = behavior
+        .disconnect_after
+        .unwrap_or(body.len())
+        .min(body.len());
+
+    let (paused, pause) = behavior.pause_after.unwrap_or((sent, Duration::ZERO));
+    let paused = paused.min(sent);
+
+    let stream = stream.get_mut();
+    if stream.write_all(head.as_bytes()).await.is_err()
+        || stream.write_all(&body[..paused]).await.is_err()
+    {
+        return;
+    }
+    if !pause.is_zero() {
+        tokio::time::sleep(pause).await;
+    }
+    if stream.write_all(&body[paused..sent]).await.is_ok() {
+        let _ = stream.shutdown().await;
+    }
+}
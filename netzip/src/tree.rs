@@ -0,0 +1,259 @@
+use std::collections::BTreeMap;
+
+use netzip_parser::CentralDirectoryRecord;
+
+/// A directory in the hierarchical view of an archive's central directory.
+///
+/// Directories that have no explicit entry in the archive are synthesized from the paths of their
+/// descendants. Sizes are aggregated over all files below the directory.
+#[derive(Debug)]
+pub struct DirectoryNode<'a> {
+    name: String,
+    path: String,
+    record: Option<&'a CentralDirectoryRecord>,
+    directories: BTreeMap<String, DirectoryNode<'a>>,
+    files: BTreeMap<String, &'a CentralDirectoryRecord>,
+    compressed_size: u64,
+    uncompressed_size: u64,
+    file_count: usize,
+}
+
+/// A child of a [`DirectoryNode`].
+#[derive(Debug, Clone, Copy)]
+pub enum TreeEntry<'a, 'b> {
+    Directory(&'b DirectoryNode<'a>),
+    File(&'a CentralDirectoryRecord),
+}
+
+impl<'a> DirectoryNode<'a> {
+    /// Builds a tree from a set of central directory records, returning its root directory.
+    ///
+    /// # Arguments
+    ///
+    /// * `records` - The records to arrange, typically `RemoteZip::records()`
+    ///
+    /// # Returns
+    ///
+    /// The root DirectoryNode, which has an empty name and path
+    pub fn from_records(records: impl IntoIterator<Item = &'a CentralDirectoryRecord>) -> Self {
+        let mut root = Self::new(String::new(), String::new());
+        for record in records {
+            root.insert(record);
+        }
+
+        root
+    }
+
+    fn new(name: String, path: String) -> Self {
+        Self {
+            name,
+            path,
+            record: None,
+            directories: BTreeMap::new(),
+            files: BTreeMap::new(),
+            compressed_size: 0,
+            uncompressed_size: 0,
+            file_count: 0,
+        }
+    }
+
+    fn insert(&mut self, record: &'a CentralDirectoryRecord) {
+        let mut components: Vec<&str> = record
+            .file_name
+            .split('/')
+            .filter(|x| !x.is_empty())
+            .collect();
+        let Some(last) = components.pop() else {
+            return;
+        };
+
+        let mut node = self;
+        for component in components {
+            node.add_file_sizes(record);
+            node = node.child_directory(component);
+        }
+
+        if record.is_directory() {
+            node.child_directory(last).record = Some(record);
+        } else {
+            node.add_file_sizes(record);
+            node.files.insert(last.into(), record);
+        }
+    }
+
+    fn child_directory(&mut self, name: &str) -> &mut Self {
+        let path = format!("{}{name}/", self.path);
+        self.directories
+            .entry(name.into())
+            .or_insert_with(|| Self::new(name.into(), path))
+    }
+
+    fn add_file_sizes(&mut self, record: &CentralDirectoryRecord) {
+        self.compressed_size += record.compressed_size as u64;
+        self.uncompressed_size += record.uncompressed_size as u64;
+        self.file_count += 1;
+    }
+
+    /// Returns the name of this directory, without any slashes. Empty for the root.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Returns the full path of this directory inside the archive, ending with a slash. Empty for the root.
+    pub fn path(&self) -> &str {
+        &self.path
+    }
+
+    /// Returns the explicit directory entry from the archive, or None if this directory was synthesized.
+    pub fn record(&self) -> Option<&'a CentralDirectoryRecord> {
+        self.record
+    }
+
+    /// Returns the total compressed size of all files below this directory.
+    pub fn compressed_size(&self) -> u64 {
+        self.compressed_size
+    }
+
+    /// Returns the total uncompressed size of all files below this directory.
+    pub fn uncompressed_size(&self) -> u64 {
+        self.uncompressed_size
+    }
+
+    /// Returns the number of files below this directory, including those in subdirectories.
+    pub fn file_count(&self) -> usize {
+        self.file_count
+    }
+
+    /// Iterates over the immediate subdirectories, sorted by name.
+    pub fn directories(&self) -> impl Iterator<Item = &DirectoryNode<'a>> {
+        self.directories.values()
+    }
+
+    /// Iterates over the files directly inside this directory, sorted by name.
+    pub fn files(&self) -> impl Iterator<Item = &'a CentralDirectoryRecord> + '_ {
+        self.files.values().copied()
+    }
+
+    /// Iterates over all immediate children, directories first, each group sorted by name.
+    pub fn children(&self) -> impl Iterator<Item = TreeEntry<'a, '_>> {
+        self.directories()
+            .map(TreeEntry::Directory)
+            .chain(self.files().map(TreeEntry::File))
+    }
+
+    /// Looks up a file or directory by its path relative to this directory.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - A slash-separated path; a trailing slash is ignored
+    ///
+    /// # Returns
+    ///
+    /// The matching TreeEntry, or None if nothing exists at the path
+    pub fn get(&self, path: &str) -> Option<TreeEntry<'a, '_>> {
+        let mut components: Vec<&str> = path.split('/').filter(|x| !x.is_empty()).collect();
+        let Some(last) = components.pop() else {
+            return Some(TreeEntry::Directory(self));
+        };
+
+        let mut node = self;
+        for component in components {
+            node = node.directories.get(component)?;
+        }
+
+        if let Some(directory) = node.directories.get(last) {
+            Some(TreeEntry::Directory(directory))
+        } else {
+            node.files.get(last).map(|x| TreeEntry::File(x))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use netzip_parser::CompressionMethod;
+
+    use super::*;
+
+    fn record(
+        file_name: &str,
+        compressed_size: u32,
+        uncompressed_size: u32,
+    ) -> CentralDirectoryRecord {
+        CentralDirectoryRecord {
+            zip_version_created: 20,
+            zip_version_required: 20,
+            gp_bit_flag: 0,
+            compression_method: CompressionMethod::Deflate,
+            last_modification_time: 0,
+            last_modification_date: 0,
+            crc32: 0,
+            compressed_size,
+            uncompressed_size,
+            file_name_length: file_name.len() as u16,
+            extra_field_length: 0,
+            file_comment_length: 0,
+            disk_number: 0,
+            file_attributes_internal: 0,
+            file_attributes_external: 0,
+            file_header_offset: 0,
+            file_name: file_name.into(),
+            extra_bytes: None,
+            comment: None,
+        }
+    }
+
+    #[test]
+    fn synthesizes_missing_directories() {
+        let records = vec![
+            record("a/", 0, 0),
+            record("a/b/c.txt", 10, 20),
+            record("a/d.txt", 1, 2),
+            record("e.txt", 100, 200),
+        ];
+        let root = DirectoryNode::from_records(&records);
+
+        assert_eq!(root.file_count(), 3);
+        assert_eq!(root.compressed_size(), 111);
+        assert_eq!(root.uncompressed_size(), 222);
+
+        let Some(TreeEntry::Directory(a)) = root.get("a") else {
+            panic!("a/ missing");
+        };
+        assert!(a.record().is_some());
+        assert_eq!(a.path(), "a/");
+        assert_eq!(a.file_count(), 2);
+        assert_eq!(a.compressed_size(), 11);
+
+        let Some(TreeEntry::Directory(b)) = root.get("a/b/") else {
+            panic!("a/b/ missing");
+        };
+        assert!(b.record().is_none());
+        assert_eq!(b.path(), "a/b/");
+        assert_eq!(b.uncompressed_size(), 20);
+
+        assert!(
+            matches!(root.get("a/b/c.txt"), Some(TreeEntry::File(x)) if x.file_name == "a/b/c.txt")
+        );
+        assert!(root.get("a/x").is_none());
+    }
+
+    #[test]
+    fn children_are_ordered() {
+        let records = vec![
+            record("z.txt", 0, 0),
+            record("y/x.txt", 0, 0),
+            record("a.txt", 0, 0),
+        ];
+        let root = DirectoryNode::from_records(&records);
+
+        let names: Vec<&str> = root
+            .children()
+            .map(|x| match x {
+                TreeEntry::Directory(d) => d.name(),
+                TreeEntry::File(f) => &f.file_name,
+            })
+            .collect();
+        assert_eq!(names, vec!["y", "a.txt", "z.txt"]);
+    }
+}
@@ -0,0 +1,71 @@
+#!/usr/bin/env python3
+"""Regenerates the fixture archives used by the integration tests."""
+
+import io
+import os
+import random
+import subprocess
+import tempfile
+import zipfile
+from pathlib import Path
+
+HERE = Path(__file__).parent
+DATE = (2024, 1, 1, 0, 0, 0)
+
+
+def add(archive, name, data, compression=zipfile.ZIP_DEFLATED):
+    info = zipfile.ZipInfo(name, DATE)
+    info.compress_type = compression
+    info.external_attr = 0o644 << 16
+    archive.writestr(info, data)
+
+
+def basic():
+    rng = random.Random(7)
+    out = io.BytesIO()
+    with zipfile.ZipFile(out, "w") as archive:
+        add(archive, "hello.txt", b"Hello, netzip!\n" * 20)
+        add(archive, "stored.bin", rng.randbytes(100_000), zipfile.ZIP_STORED)
+        add(archive, "docs/", b"", zipfile.ZIP_STORED)
+        add(archive, "docs/guide.md", b"# Guide\n\nRead the docs.\n")
+        add(archive, "docs/empty.txt", b"")
+        words = [b"alpha", b"beta", b"gamma", b"delta", b"epsilon", b"zeta"]
+        text = b" ".join(rng.choice(words) for _ in range(60_000))
+        add(archive, "large.txt", text)
+        add(archive, "legacy.bz2.txt", b"compressed with bzip2\n", zipfile.ZIP_BZIP2)
+    return out.getvalue()
+
+
+def nested(inner):
+    out = io.BytesIO()
+    with zipfile.ZipFile(out, "w") as archive:
+        add(archive, "inner.zip", inner, zipfile.ZIP_STORED)
+        add(archive, "inner.jar", inner)
+        add(archive, "notes.txt", b"Two copies of the same archive.\n")
+    return out.getvalue()
+
+
+def split(inner):
+    """Writes the entries of `inner` as a split archive of 64 KiB segments. Python's zipfile cannot write
+    split archives, so this needs Info-ZIP's zip."""
+    with tempfile.TemporaryDirectory() as work:
+        work = Path(work)
+        names = zipfile.ZipFile(io.BytesIO(inner)).namelist()
+        zipfile.ZipFile(io.BytesIO(inner)).extractall(work / "src")
+        for name in names:
+            os.utime(work / "src" / name, (1704067200, 1704067200))
+        subprocess.run(
+            ["zip", "-q", "-X", "-s", "64k", work / "split.zip", *names],
+            cwd=work / "src",
+            env={**os.environ, "TZ": "UTC"},
+            check=True,
+        )
+        for segment in ["split.z01", "split.z02", "split.zip"]:
+            (HERE / segment).write_bytes((work / segment).read_bytes())
+
+
+if __name__ == "__main__":
+    basic_zip = basic()
+    (HERE / "basic.zip").write_bytes(basic_zip)
+    (HERE / "nested.zip").write_bytes(nested(basic_zip))
+    split(basic_zip)
@@ -0,0 +1,935 @@
+use std::{
+    io::SeekFrom,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use futures_util::TryStreamExt;
+use netzip::{
+    DEFAULT_READ_AHEAD, EntryResult, Error, Progress, ProgressStage, RangeCache, RateLimiter,
+    RemoteZip, TreeEntry,
+    test_support::{MockBehavior, MockServer},
+};
+use netzip_parser::{
+    CentralDirectoryEnd, CentralDirectoryRecord, CompressionMethod, EntryOptions,
+    ZIP64_PLACEHOLDER_U32, Zip64ExtraField, ZipWriter,
+};
+use tokio::io::{AsyncReadExt, AsyncSeekExt};
+
+const BASIC: &[u8] = include_bytes!("fixtures/basic.zip");
+const NESTED: &[u8] = include_bytes!("fixtures/nested.zip");
+const SPLIT: [&[u8]; 3] = [
+    include_bytes!("fixtures/split.z01"),
+    include_bytes!("fixtures/split.z02"),
+    include_bytes!("fixtures/split.zip"),
+];
+
+fn fixture_path(name: &str) -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("tests/fixtures")
+        .join(name)
+}
+
+fn temp_dir(name: &str) -> PathBuf {
+    let path = std::env::temp_dir().join(format!("netzip-test-{name}-{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&path);
+    std::fs::create_dir_all(&path).unwrap();
+    path
+}
+
+async fn download(zip: &RemoteZip, path: &str) -> Vec<u8> {
+    let mut files = zip.download_files(vec![path.into()]).await.unwrap();
+    files.remove(0).1
+}
+
+#[tokio::test]
+async fn lists_entries() {
+    let server = MockServer::start(BASIC).await;
+    let zip = RemoteZip::get(&server.url()).await.unwrap();
+
+    let names: Vec<&str> = zip.records().iter().map(|x| x.file_name.as_str()).collect();
+    assert_eq!(
+        names,
+        [
+            "hello.txt",
+            "stored.bin",
+            "docs/",
+            "docs/guide.md",
+            "docs/empty.txt",
+            "large.txt",
+            "legacy.bz2.txt"
+        ]
+    );
+
+    let tree = zip.tree();
+    let Some(TreeEntry::Directory(docs)) = tree.get("docs") else {
+        panic!("docs should be a directory");
+    };
+    assert_eq!(docs.file_count(), 2);
+}
+
+#[tokio::test]
+async fn streams_directory_records() {
+    let server = MockServer::start(BASIC).await;
+    let zip = RemoteZip::get(&server.url()).await.unwrap();
+
+    let records: Vec<_> = RemoteZip::builder(&server.url())
+        .stream_records()
+        .await
+        .unwrap()
+        .try_collect()
+        .await
+        .unwrap();
+    assert_eq!(&records, zip.records());
+}
+
+#[tokio::test]
+async fn fetches_only_needed_ranges() {
+    let server = MockServer::start(BASIC).await;
+    let zip = RemoteZip::get(&server.url()).await.unwrap();
+    server.clear_requests();
+
+    assert_eq!(
+        download(&zip, "hello.txt").await,
+        b"Hello, netzip!\n".repeat(20)
+    );
+    let requests = server.requests();
+    assert_eq!(requests.len(), 2);
+    assert!(requests.iter().all(|x| x.is_some()));
+}
+
+#[tokio::test]
+async fn counts_transferred_bytes() {
+    let server = MockServer::start(BASIC).await;
+    let zip = RemoteZip::get(&server.url()).await.unwrap();
+    zip.reset_stats();
+
+    download(&zip, "hello.txt").await;
+    let stats = zip.stats();
+    assert_eq!(stats.requests, 2);
+    assert_eq!(stats.bytes_received, stats.bytes_requested);
+    assert_eq!(stats.bytes_wasted, 0);
+
+    zip.reset_stats();
+    let mut reader = zip.open_stored("stored.bin").await.unwrap();
+    let mut buffer = vec![0; 1000];
+    reader.read_exact(&mut buffer).await.unwrap();
+    drop(reader);
+    let stats = zip.stats();
+    assert_eq!(stats.requests, 2);
+    assert_eq!(stats.bytes_wasted, DEFAULT_READ_AHEAD - 1000);
+}
+
+#[tokio::test]
+async fn reports_progress() {
+    let server = MockServer::start(BASIC).await;
+    let updates = Arc::new(Mutex::new(Vec::new()));
+    let listener_updates = updates.clone();
+    let zip = RemoteZip::builder(&server.url())
+        .progress(move |x: &Progress| listener_updates.lock().unwrap().push(x.clone()))
+        .build()
+        .await
+        .unwrap();
+
+    let directory = updates.lock().unwrap().pop().unwrap();
+    assert_eq!(directory.stage, ProgressStage::Directory);
+    assert_eq!(directory.entries_total, 7);
+    assert_eq!(directory.bytes_downloaded, directory.bytes_total);
+
+    // A directory larger than a single read of a local file arrives in several parts
+    let mut writer = ZipWriter::new(Vec::new());
+    let options = EntryOptions {
+        compression_method: CompressionMethod::Stored,
+        ..Default::default()
+    };
+    for index in 0..10_000 {
+        writer
+            .start_entry(&format!("entries/{index:05}.txt"), options.clone())
+            .unwrap();
+    }
+    let directory = temp_dir("progress");
+    let path = directory.join("many.zip");
+    std::fs::write(&path, writer.finish().unwrap()).unwrap();
+    let directory_updates = Arc::new(Mutex::new(Vec::new()));
+    let listener_updates = directory_updates.clone();
+    RemoteZip::builder(path.to_str().unwrap())
+        .progress(move |x: &Progress| listener_updates.lock().unwrap().push(x.clone()))
+        .build()
+        .await
+        .unwrap();
+    assert!(directory_updates.lock().unwrap().iter().any(|x| {
+        x.stage == ProgressStage::Directory
+            && x.bytes_downloaded > 0
+            && x.bytes_downloaded < x.bytes_total
+    }));
+    let _ = std::fs::remove_dir_all(&directory);
+
+    updates.lock().unwrap().clear();
+    zip.download_files(vec!["hello.txt".into(), "large.txt".into()])
+        .await
+        .unwrap();
+    let updates = updates.lock().unwrap();
+    assert!(
+        updates
+            .iter()
+            .any(|x| x.entry.as_deref() == Some("large.txt"))
+    );
+
+    let done = updates.last().unwrap();
+    assert_eq!(done.stage, ProgressStage::Entries);
+    assert_eq!((done.entries_done, done.entries_total), (2, 2));
+    assert_eq!(done.bytes_downloaded, done.bytes_total);
+    assert_eq!(done.bytes_decompressed, 300 + 360_316);
+    assert_eq!(done.bytes_decompressed, done.bytes_decompressed_total);
+}
+
+#[tokio::test]
+async fn reports_each_entry_separately() {
+    let server = MockServer::start(BASIC).await;
+    let zip = RemoteZip::get(&server.url()).await.unwrap();
+
+    let results = zip
+        .download_entries(vec![
+            "docs/guide.md".into(),
+            "docs/empty.txt".into(),
+            "missing.txt".into(),
+            "legacy.bz2.txt".into(),
+        ])
+        .await;
+
+    assert!(
+        matches!(&results[0].1, EntryResult::Downloaded(_, data) if data == b"# Guide\n\nRead the docs.\n")
+    );
+    assert!(matches!(&results[1].1, EntryResult::Downloaded(_, data) if data.is_empty()));
+    assert!(matches!(results[2].1, EntryResult::Missing));
+    assert!(matches!(results[3].1, EntryResult::Unsupported(12)));
+
+    // An entry that cannot be inflated fails without affecting the entries after it
+    let mut writer = ZipWriter::new(Vec::new());
+    writer
+        .start_raw_entry("corrupt.txt", EntryOptions::default(), 0, 4, 4)
+        .unwrap();
+    std::io::Write::write_all(&mut writer, &[0xFF; 4]).unwrap();
+    writer
+        .start_entry("intact.txt", EntryOptions::default())
+        .unwrap();
+    std::io::Write::write_all(&mut writer, b"intact").unwrap();
+    let server = MockServer::start(writer.finish().unwrap()).await;
+    let zip = RemoteZip::get(&server.url()).await.unwrap();
+
+    let results = zip
+        .download_entries(vec!["corrupt.txt".into(), "intact.txt".into()])
+        .await;
+    assert_eq!(results[0].0, "corrupt.txt");
+    assert!(matches!(
+        results[0].1,
+        EntryResult::Failed(Error::DecompressionError(..))
+    ));
+    assert!(matches!(&results[1].1, EntryResult::Downloaded(lfh, data)
+        if lfh.file_name == "intact.txt" && data == b"intact"));
+}
+
+#[tokio::test]
+async fn downloads_to_path() {
+    let server = MockServer::start(BASIC).await;
+    let zip = RemoteZip::get(&server.url()).await.unwrap();
+    let directory = temp_dir("path");
+
+    let destination = directory.join("large.txt");
+    zip.download_to_path("large.txt", &destination)
+        .await
+        .unwrap();
+    assert_eq!(
+        std::fs::read(&destination).unwrap(),
+        download(&zip, "large.txt").await
+    );
+
+    assert!(matches!(
+        zip.download_to_path("missing.txt", directory.join("missing.txt"))
+            .await,
+        Err(Error::EntryNotFound(_))
+    ));
+    assert_eq!(std::fs::read_dir(&directory).unwrap().count(), 1);
+    let _ = std::fs::remove_dir_all(&directory);
+}
+
+#[tokio::test]
+async fn reads_split_archives() {
+    let server = MockServer::start(BASIC).await;
+    let basic = RemoteZip::get(&server.url()).await.unwrap();
+    let mut segments = Vec::new();
+    for data in SPLIT {
+        segments.push(MockServer::start(data).await);
+    }
+    let urls: Vec<String> = segments.iter().map(|x| x.url()).collect();
+    let paths: Vec<String> = ["split.z01", "split.z02", "split.zip"]
+        .into_iter()
+        .map(|x| fixture_path(x).display().to_string())
+        .collect();
+
+    for urls in [urls, paths] {
+        let (last, earlier) = urls.split_last().unwrap();
+        let zip = RemoteZip::builder(last)
+            .segments(earlier)
+            .build()
+            .await
+            .unwrap();
+        let disks: Vec<u16> = zip.records().iter().map(|x| x.disk_number).collect();
+        assert_eq!(disks, [0, 0, 1, 1, 1, 1, 2]);
+
+        // stored.bin starts in the first segment and ends in the second
+        for name in ["hello.txt", "stored.bin", "docs/guide.md", "large.txt"] {
+            assert_eq!(download(&zip, name).await, download(&basic, name).await);
+        }
+
+        let streamed: Vec<_> = RemoteZip::builder(last)
+            .segments(earlier)
+            .stream_records()
+            .await
+            .unwrap()
+            .try_collect()
+            .await
+            .unwrap();
+        assert_eq!(&streamed, zip.records());
+    }
+
+    assert!(matches!(
+        RemoteZip::get(&segments[2].url()).await,
+        Err(Error::SegmentCountMismatch(_, 3, 1))
+    ));
+    let urls: Vec<String> = segments.iter().map(|x| x.url()).collect();
+    for (earlier, given) in [
+        (&urls[1..2], 2),
+        (&[&urls[..2], &urls[..2]].concat()[..], 5),
+    ] {
+        assert!(matches!(
+            RemoteZip::builder(&urls[2]).segments(earlier).build().await,
+            Err(Error::SegmentCountMismatch(_, 3, x)) if x == given
+        ));
+        assert!(matches!(
+            RemoteZip::builder(&urls[2]).segments(earlier).stream_records().await,
+            Err(Error::SegmentCountMismatch(_, 3, x)) if x == given
+        ));
+    }
+}
+
+#[tokio::test]
+async fn builds_subset_archives() {
+    let server = MockServer::start(BASIC).await;
+    let zip = RemoteZip::get(&server.url()).await.unwrap();
+    let directory = temp_dir("subset");
+    let destination = directory.join("subset.zip");
+    let paths = ["large.txt", "docs/", "docs/empty.txt", "legacy.bz2.txt"];
+
+    let records = zip
+        .subset_to_path(paths.iter().map(|x| x.to_string()).collect(), &destination)
+        .await
+        .unwrap();
+    let subset = RemoteZip::get(destination.to_str().unwrap()).await.unwrap();
+    assert_eq!(subset.records(), &records);
+    for (record, path) in subset.records().iter().zip(paths) {
+        let original = zip.records().iter().find(|x| x.file_name == path).unwrap();
+        assert_eq!(record.file_name, path);
+        assert_eq!(record.compression_method, original.compression_method);
+        assert_eq!(record.compressed_size, original.compressed_size);
+        assert_eq!(record.crc32, original.crc32);
+    }
+    for path in ["large.txt", "docs/empty.txt"] {
+        assert_eq!(download(&subset, path).await, download(&zip, path).await);
+    }
+
+    assert!(matches!(
+        zip.subset_to_path(vec!["missing.txt".into()], directory.join("missing.zip"))
+            .await,
+        Err(Error::EntryNotFound(_))
+    ));
+    assert_eq!(std::fs::read_dir(&directory).unwrap().count(), 1);
+    let _ = std::fs::remove_dir_all(&directory);
+}
+
+#[tokio::test]
+async fn resolves_zip64_extra_fields() {
+    let mut writer = ZipWriter::new(Vec::new());
+    for name in ["first.txt", "second.txt"] {
+        writer.start_entry(name, EntryOptions::default()).unwrap();
+        std::io::Write::write_all(&mut writer, name.repeat(100).as_bytes()).unwrap();
+    }
+    let mut archive = writer.finish().unwrap();
+
+    // Move the sizes and offsets of every record into ZIP64 extra fields, as writers of large archives do
+    let end = CentralDirectoryEnd::find_and_parse(&archive).unwrap();
+    let start = end.central_directory_offset as usize;
+    let directory = &archive[start..start + end.directory_size as usize];
+    let records = CentralDirectoryRecord::parse_directory(directory, &end).unwrap();
+    archive.truncate(start);
+    for mut record in records {
+        let extra_bytes = Zip64ExtraField {
+            uncompressed_size: Some(record.uncompressed_size as u64),
+            compressed_size: Some(record.compressed_size as u64),
+            file_header_offset: Some(record.file_header_offset as u64),
+            disk_number: None,
+        }
+        .to_bytes();
+        record.uncompressed_size = ZIP64_PLACEHOLDER_U32;
+        record.compressed_size = ZIP64_PLACEHOLDER_U32;
+        record.file_header_offset = ZIP64_PLACEHOLDER_U32;
+        record.extra_field_length = extra_bytes.len() as u16;
+        record.extra_bytes = Some(extra_bytes);
+        archive.extend(record.to_bytes().unwrap());
+    }
+    let end = CentralDirectoryEnd {
+        directory_size: (archive.len() - start) as u32,
+        ..end
+    };
+    archive.extend(end.to_bytes().unwrap());
+
+    let server = MockServer::start(archive).await;
+    let zip = RemoteZip::get(&server.url()).await.unwrap();
+    let contents = "second.txt".repeat(100);
+    assert_eq!(download(&zip, "second.txt").await, contents.as_bytes());
+
+    let mut archive = Vec::new();
+    let records = zip
+        .subset_to_writer(vec!["second.txt".into()], &mut archive)
+        .await
+        .unwrap();
+    assert_eq!(records[0].uncompressed_size as usize, contents.len());
+    assert_eq!(records[0].extra_bytes, None);
+    let subset_server = MockServer::start(archive).await;
+    let subset = RemoteZip::get(&subset_server.url()).await.unwrap();
+    assert_eq!(download(&subset, "second.txt").await, contents.as_bytes());
+}
+
+#[tokio::test]
+async fn reads_stored_entries_at_offsets() {
+    let server = MockServer::start(BASIC).await;
+    let zip = RemoteZip::get(&server.url()).await.unwrap();
+    let expected = download(&zip, "stored.bin").await;
+
+    let mut reader = zip
+        .open_stored("stored.bin")
+        .await
+        .unwrap()
+        .with_read_ahead(1024);
+    assert_eq!(reader.len(), 100_000);
+    reader.seek(SeekFrom::Start(50_000)).await.unwrap();
+    let mut buffer = vec![0; 4096];
+    reader.read_exact(&mut buffer).await.unwrap();
+    assert_eq!(buffer, expected[50_000..54_096]);
+
+    assert!(matches!(
+        zip.open_stored("large.txt").await,
+        Err(Error::NotSeekable(_))
+    ));
+}
+
+#[tokio::test]
+async fn reads_deflate_entries_through_index() {
+    let server = MockServer::start(BASIC).await;
+    let zip = RemoteZip::get(&server.url()).await.unwrap();
+    let expected = download(&zip, "large.txt").await;
+
+    let index = zip
+        .build_deflate_index("large.txt", 16 * 1024)
+        .await
+        .unwrap();
+    assert!(index.points().len() > 1);
+
+    let mut reader = zip.open_indexed("large.txt", index).await.unwrap();
+    reader.seek(SeekFrom::Start(200_000)).await.unwrap();
+    let mut buffer = vec![0; 10_000];
+    reader.read_exact(&mut buffer).await.unwrap();
+    assert_eq!(buffer, expected[200_000..210_000]);
+}
+
+#[tokio::test]
+async fn local_files_match_http() {
+    let server = MockServer::start(BASIC).await;
+    let remote = RemoteZip::get(&server.url()).await.unwrap();
+    let path = fixture_path("basic.zip");
+    let url = format!("file://{}", path.display());
+
+    for local in [
+        RemoteZip::get(path.to_str().unwrap()).await.unwrap(),
+        RemoteZip::get(&url).await.unwrap(),
+    ] {
+        assert_eq!(local.records(), remote.records());
+        for name in ["hello.txt", "stored.bin", "large.txt"] {
+            assert_eq!(download(&local, name).await, download(&remote, name).await);
+        }
+    }
+}
+
+#[tokio::test]
+async fn opens_nested_archives() {
+    let server = MockServer::start(NESTED).await;
+    let outer = RemoteZip::get(&server.url()).await.unwrap();
+    let basic = RemoteZip::get(fixture_path("basic.zip").to_str().unwrap())
+        .await
+        .unwrap();
+
+    for name in ["inner.zip", "inner.jar"] {
+        let inner = outer.open_nested(name).await.unwrap();
+        assert_eq!(inner.records(), basic.records());
+        assert_eq!(
+            download(&inner, "large.txt").await,
+            download(&basic, "large.txt").await
+        );
+    }
+
+    assert!(matches!(
+        outer.open_nested_with_limit("inner.jar", 1024).await,
+        Err(Error::EntryTooLarge(_, 1024))
+    ));
+}
+
+#[tokio::test]
+async fn limits_nested_archives_with_understated_sizes() {
+    // An inner archive declaring 100 bytes that inflates to 10 MiB
+    let mut encoder = flate2::write::DeflateEncoder::new(Vec::new(), flate2::Compression::best());
+    std::io::Write::write_all(&mut encoder, &vec![0; 10 * 1024 * 1024]).unwrap();
+    let compressed = encoder.finish().unwrap();
+    let mut writer = ZipWriter::new(Vec::new());
+    writer
+        .start_raw_entry(
+            "bomb.jar",
+            EntryOptions::default(),
+            0,
+            compressed.len() as u64,
+            100,
+        )
+        .unwrap();
+    std::io::Write::write_all(&mut writer, &compressed).unwrap();
+
+    let server = MockServer::start(writer.finish().unwrap()).await;
+    let outer = RemoteZip::get(&server.url()).await.unwrap();
+    assert!(matches!(
+        outer.open_nested_with_limit("bomb.jar", 1024 * 1024).await,
+        Err(Error::EntryTooLarge(_, 1048576))
+    ));
+}
+
+#[tokio::test]
+async fn caches_ranges_across_instances() {
+    let server = MockServer::start(BASIC).await;
+    let directory = temp_dir("cache");
+    let cache = RangeCache::open_with_block_size(&directory, 1 << 20, 4096).unwrap();
+
+    let zip = RemoteZip::builder(&server.url())
+        .cache(cache.clone())
+        .build()
+        .await
+        .unwrap();
+    let expected = download(&zip, "stored.bin").await;
+    server.clear_requests();
+
+    let zip = RemoteZip::builder(&server.url())
+        .cache(cache)
+        .build()
+        .await
+        .unwrap();
+    assert_eq!(download(&zip, "stored.bin").await, expected);
+    // Only the tail is requested again, to learn the archive's validators
+    assert_eq!(server.requests(), [Some("bytes=-22".to_string())]);
+    let _ = std::fs::remove_dir_all(&directory);
+}
+
+#[tokio::test]
+async fn limits_rate_across_instances() {
+    let server = MockServer::start(BASIC).await;
+    let limiter = RateLimiter::with_burst(1_000_000, 50_000);
+    let open = || {
+        RemoteZip::builder(&server.url())
+            .rate_limit(limiter.clone())
+            .build()
+    };
+    let (first, second) = (open().await.unwrap(), open().await.unwrap());
+
+    let started = Instant::now();
+    let (a, b) = tokio::join!(
+        download(&first, "stored.bin"),
+        download(&second, "stored.bin")
+    );
+    assert_eq!(a, b);
+    // 200 kB beyond the initial burst, at 1 MB/s
+    assert!(started.elapsed() >= Duration::from_millis(140));
+}
+
+#[tokio::test]
+async fn spreads_requests_across_mirrors() {
+    let (first, second) = (
+        MockServer::start(BASIC).await,
+        MockServer::start(BASIC).await,
+    );
+    let zip = RemoteZip::builder(&first.url())
+        .mirrors([second.url()])
+        .build()
+        .await
+        .unwrap();
+    let basic = RemoteZip::get(fixture_path("basic.zip").to_str().unwrap())
+        .await
+        .unwrap();
+
+    assert_eq!(zip.records(), basic.records());
+    for name in ["hello.txt", "stored.bin", "large.txt"] {
+        assert_eq!(download(&zip, name).await, download(&basic, name).await);
+    }
+    assert!(!first.requests().is_empty());
+    assert!(!second.requests().is_empty());
+}
+
+#[tokio::test]
+async fn fails_over_between_mirrors() {
+    let broken = MockServer::start(BASIC).await;
+    broken.set_behavior(MockBehavior::new().ranges(false));
+    let different = MockServer::start(NESTED).await;
+    let slow = MockServer::start(BASIC).await;
+    slow.set_behavior(MockBehavior::new().delay(Duration::from_secs(5)));
+    let working = MockServer::start(BASIC).await;
+
+    // The archive's own URL answers first and serves as the reference for the mirrors
+    let zip = RemoteZip::builder(&working.url())
+        .mirrors([broken.url(), different.url(), slow.url()])
+        .mirror_timeout(Duration::from_millis(200))
+        .build()
+        .await
+        .unwrap();
+    assert_eq!(zip.records().len(), 7);
+    for _ in 0..4 {
+        assert_eq!(
+            download(&zip, "hello.txt").await,
+            b"Hello, netzip!\n".repeat(20)
+        );
+    }
+
+    working.set_behavior(MockBehavior::new().ranges(false));
+    assert!(matches!(
+        zip.download_files(vec!["hello.txt".into()]).await,
+        Err(Error::RangeUnsupported(_) | Error::TimedOut(_))
+    ));
+}
+
+#[tokio::test]
+async fn resumes_streams_on_other_mirrors() {
+    let working = MockServer::start(BASIC).await;
+    let flaky = MockServer::start(BASIC).await;
+    // Whether the flaky mirror was asked for a range longer than it sends in one go
+    let streamed_from_flaky = || {
+        flaky.requests().iter().flatten().any(|range| {
+            let (start, end) = range["bytes=".len()..].split_once('-').unwrap();
+            end.parse::<u64>().unwrap() - start.parse::<u64>().unwrap() >= 1000
+        })
+    };
+
+    for behavior in [
+        MockBehavior::new().disconnect_after(1000),
+        MockBehavior::new().pause_after(1000, Duration::from_secs(5)),
+    ] {
+        // The mirror only misbehaves once it has been verified
+        flaky.set_behavior(MockBehavior::new());
+        let zip = RemoteZip::builder(&working.url())
+            .mirrors([flaky.url()])
+            .mirror_timeout(Duration::from_millis(500))
+            .build()
+            .await
+            .unwrap();
+        let expected = download(&zip, "stored.bin").await;
+
+        flaky.set_behavior(behavior);
+        flaky.clear_requests();
+        let start = Instant::now();
+        for _ in 0..4 {
+            assert_eq!(download(&zip, "stored.bin").await, expected);
+        }
+        assert!(streamed_from_flaky());
+        assert!(start.elapsed() < Duration::from_secs(4));
+    }
+}
+
+#[tokio::test]
+async fn rejects_servers_without_range_support() {
+    let server = MockServer::start(BASIC).await;
+    server.set_behavior(MockBehavior::new().ranges(false));
+
+    assert!(matches!(
+        RemoteZip::get(&server.url()).await,
+        Err(Error::RangeUnsupported(_))
+    ));
+}
+
+#[tokio::test]
+async fn rejects_multipart_responses() {
+    let server = MockServer::start(BASIC).await;
+    server.set_behavior(MockBehavior::new().multipart(true));
+
+    assert!(matches!(
+        RemoteZip::get(&server.url()).await,
+        Err(Error::UnexpectedResponse(..))
+    ));
+}
+
+#[tokio::test]
+async fn reports_unsatisfiable_ranges() {
+    let server = MockServer::start(Vec::new()).await;
+
+    let Err(Error::NetworkError(_, error)) = RemoteZip::get(&server.url()).await else {
+        panic!("An empty archive should not be readable");
+    };
+    assert_eq!(
+        error.status(),
+        Some(reqwest::StatusCode::RANGE_NOT_SATISFIABLE)
+    );
+}
+
+#[tokio::test]
+async fn times_out_slow_servers() {
+    let server = MockServer::start(BASIC).await;
+    server.set_behavior(MockBehavior::new().delay(Duration::from_secs(5)));
+    let client = reqwest::Client::builder()
+        .timeout(Duration::from_millis(100))
+        .build()
+        .unwrap();
+
+    let Err(Error::NetworkError(_, error)) = RemoteZip::get_using(&server.url(), client).await
+    else {
+        panic!("A slow server should time out");
+    };
+    assert!(error.is_timeout());
+}
+
+#[tokio::test]
+async fn cancels_running_downloads() {
+    let server = MockServer::start(BASIC).await;
+    let zip = RemoteZip::get(&server.url()).await.unwrap();
+    let directory = temp_dir("cancel");
+    server.set_behavior(MockBehavior::new().delay(Duration::from_secs(5)));
+
+    let token = zip.cancellation_token().clone();
+    tokio::spawn(async move {
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        token.cancel();
+    });
+    assert!(matches!(
+        zip.download_to_path("large.txt", directory.join("large.txt"))
+            .await,
+        Err(Error::Cancelled(_))
+    ));
+    assert_eq!(std::fs::read_dir(&directory).unwrap().count(), 0);
+
+    server.clear_requests();
+    let results = zip
+        .download_entries(vec!["hello.txt".into(), "missing.txt".into()])
+        .await;
+    assert!(matches!(
+        results[0].1,
+        EntryResult::Failed(Error::Cancelled(_))
+    ));
+    assert!(matches!(results[1].1, EntryResult::Missing));
+    assert!(server.requests().is_empty());
+    let _ = std::fs::remove_dir_all(&directory);
+}
+
+#[tokio::test]
+async fn enforces_deadlines() {
+    let server = MockServer::start(BASIC).await;
+    let zip = RemoteZip::get(&server.url()).await.unwrap();
+    server.set_behavior(MockBehavior::new().delay(Duration::from_secs(5)));
+
+    zip.set_deadline(Some(Instant::now() + Duration::from_millis(100)));
+    assert!(matches!(
+        zip.download_files(vec!["hello.txt".into()]).await,
+        Err(Error::DeadlineExceeded(_))
+    ));
+
+    server.set_behavior(MockBehavior::new());
+    zip.set_deadline(None);
+    assert_eq!(
+        download(&zip, "hello.txt").await,
+        b"Hello, netzip!\n".repeat(20)
+    );
+
+    let past = Instant::now();
+    assert!(matches!(
+        RemoteZip::builder(&server.url())
+            .deadline(past)
+            .build()
+            .await,
+        Err(Error::DeadlineExceeded(_))
+    ));
+}
+
+#[tokio::test]
+async fn detects_truncated_bodies() {
+    let server = MockServer::start(BASIC).await;
+    let zip = RemoteZip::get(&server.url()).await.unwrap();
+    server.set_behavior(MockBehavior::new().disconnect_after(1000));
+
+    assert!(matches!(
+        zip.download_files(vec!["stored.bin".into()]).await,
+        Err(Error::NetworkError(..) | Error::IncompleteResponse(..))
+    ));
+}
+
+#[tokio::test]
+async fn rejects_miscounted_directories() {
+    // Declare one record more than the directory holds in the end of central directory record
+    let mut archive = BASIC.to_vec();
+    let count = archive.len() - netzip_parser::EOCD_MIN_SIZE + 10;
+    archive[count] += 1;
+    let server = MockServer::start(archive).await;
+
+    assert!(matches!(
+        RemoteZip::get(&server.url()).await,
+        Err(Error::ParserError(
+            _,
+            netzip_parser::ZipError::RecordCountMismatch(..)
+        ))
+    ));
+}
+
+#[cfg(feature = "serde")]
+#[tokio::test]
+async fn restores_snapshots_without_requests() {
+    let server = MockServer::start(BASIC).await;
+    let zip = RemoteZip::get(&server.url()).await.unwrap();
+    let mut saved = Vec::new();
+    zip.snapshot().write_to(&mut saved).unwrap();
+    let snapshot = netzip::DirectorySnapshot::read_from(&saved[..]).unwrap();
+    server.clear_requests();
+
+    let restored = RemoteZip::builder(&server.url()).build_from_snapshot(snapshot.clone());
+    let changed = RemoteZip::builder(&server.url()).build_from_snapshot(snapshot);
+    assert!(server.requests().is_empty());
+    assert_eq!(restored.records(), zip.records());
+    assert_eq!(
+        download(&restored, "hello.txt").await,
+        b"Hello, netzip!\n".repeat(20)
+    );
+
+    // The archive is only revalidated once data is requested
+    server.set_content(NESTED);
+    assert!(matches!(
+        changed.download_files(vec!["hello.txt".into()]).await,
+        Err(Error::ArchiveChanged(_))
+    ));
+}
+
+#[tokio::test]
+async fn detects_changed_archives() {
+    let server = MockServer::start(BASIC).await;
+    let zip = RemoteZip::get(&server.url()).await.unwrap();
+    server.set_content(NESTED);
+
+    assert!(matches!(
+        zip.download_files(vec!["hello.txt".into()]).await,
+        Err(Error::ArchiveChanged(_))
+    ));
+}
+
+/// Serves an archive from a runtime of its own, so the blocking API can be used from the test thread.
+#[cfg(feature = "blocking")]
+fn start_blocking_server(content: &'static [u8]) -> (tokio::runtime::Runtime, MockServer) {
+    let runtime = tokio::runtime::Builder::new_multi_thread()
+        .worker_threads(1)
+        .enable_all()
+        .build()
+        .unwrap();
+    let server = runtime.block_on(MockServer::start(content));
+    (runtime, server)
+}
+
+#[cfg(feature = "blocking")]
+#[test]
+fn reads_entries_blocking() {
+    use std::io::Read;
+
+    let (_runtime, server) = start_blocking_server(BASIC);
+    let zip = netzip::blocking::RemoteZip::get(&server.url()).unwrap();
+    let basic =
+        netzip::blocking::RemoteZip::get(fixture_path("basic.zip").to_str().unwrap()).unwrap();
+    assert_eq!(zip.records(), basic.records());
+
+    for path in ["hello.txt", "large.txt"] {
+        let mut reader = zip.open(path).unwrap();
+        assert_eq!(reader.local_file().file_name, path);
+        let mut data = Vec::new();
+        reader.read_to_end(&mut data).unwrap();
+        assert_eq!(
+            data,
+            zip.download_files(vec![path.into()]).unwrap().remove(0).1
+        );
+    }
+    assert!(matches!(
+        zip.open("missing.txt"),
+        Err(Error::EntryNotFound(_))
+    ));
+}
+
+#[cfg(feature = "blocking")]
+#[test]
+fn writes_to_paths_blocking() {
+    let (_runtime, server) = start_blocking_server(BASIC);
+    let zip = netzip::blocking::RemoteZip::get(&server.url()).unwrap();
+    let directory = temp_dir("blocking");
+    let large = zip
+        .download_files(vec!["large.txt".into()])
+        .unwrap()
+        .remove(0)
+        .1;
+
+    let destination = directory.join("large.txt");
+    zip.download_to_path("large.txt", &destination).unwrap();
+    assert_eq!(std::fs::read(&destination).unwrap(), large);
+
+    let destination = directory.join("subset.zip");
+    let records = zip
+        .subset_to_path(
+            vec!["large.txt".into(), "docs/empty.txt".into()],
+            &destination,
+        )
+        .unwrap();
+    let subset = netzip::blocking::RemoteZip::get(destination.to_str().unwrap()).unwrap();
+    assert_eq!(subset.records(), &records);
+    assert_eq!(
+        subset
+            .download_files(vec!["large.txt".into()])
+            .unwrap()
+            .remove(0)
+            .1,
+        large
+    );
+
+    assert!(matches!(
+        zip.download_to_path("missing.txt", directory.join("missing.txt")),
+        Err(Error::EntryNotFound(_))
+    ));
+    assert_eq!(std::fs::read_dir(&directory).unwrap().count(), 2);
+    let _ = std::fs::remove_dir_all(&directory);
+}
+
+#[cfg(feature = "blocking")]
+#[test]
+fn opens_nested_archives_blocking() {
+    let (_runtime, server) = start_blocking_server(NESTED);
+    let outer = netzip::blocking::RemoteZip::get(&server.url()).unwrap();
+    let basic =
+        netzip::blocking::RemoteZip::get(fixture_path("basic.zip").to_str().unwrap()).unwrap();
+
+    for name in ["inner.zip", "inner.jar"] {
+        let inner = outer.open_nested(name).unwrap();
+        assert_eq!(inner.records(), basic.records());
+        assert_eq!(
+            inner
+                .download_files(vec!["large.txt".into()])
+                .unwrap()
+                .remove(0)
+                .1,
+            basic
+                .download_files(vec!["large.txt".into()])
+                .unwrap()
+                .remove(0)
+                .1
+        );
+    }
+}
@@ -1,112 +1,543 @@
-use std::time::Duration;
+use std::{
+    collections::HashSet,
+    path::PathBuf,
+    sync::{
+        Arc,
+        atomic::{AtomicU64, Ordering},
+    },
+    time::Duration,
+};
 
 use bittenhumans::{ByteSizeFormatter, consts::System};
 use clap::{Parser, Subcommand};
 use comfy_table::{Cell, ContentArrangement, Table, presets::ASCII_FULL_CONDENSED};
-use indicatif::ProgressBar;
+use futures_util::StreamExt;
+use indicatif::{ProgressBar, ProgressStyle};
 use netzip::*;
+use tracing_subscriber::{
+    filter::{LevelFilter, Targets},
+    layer::SubscriberExt,
+    util::SubscriberInitExt,
+};
 
 #[derive(Parser)]
 #[command(version, about, propagate_version = true)]
 struct Args {
     #[command(subcommand)]
     command: Commands,
+    #[command(flatten)]
+    access: AccessOptions,
+    /// Print request and transfer statistics when done
+    #[arg(long, global = true)]
+    stats: bool,
+    /// Log range requests, directory parsing and decoding to stderr; repeat for more detail
+    #[arg(short, long, global = true, action = clap::ArgAction::Count)]
+    verbose: u8,
+}
+
+/// Options controlling how the archive is accessed, shared by all commands.
+#[derive(clap::Args)]
+struct AccessOptions {
+    /// Cache fetched byte ranges in this directory and reuse them across runs
+    #[arg(long, global = true, value_name = "DIR")]
+    cache_dir: Option<PathBuf>,
+    /// Maximum size of the cache, e.g. 500M or 2G
+    #[arg(long, global = true, value_name = "SIZE", default_value = "1G", value_parser = parse_size)]
+    cache_size: u64,
+    /// An earlier segment of a split archive, e.g. archive.z01, given in disk order before the .zip (repeatable)
+    #[arg(long = "segment", global = true, value_name = "URL")]
+    segments: Vec<String>,
+    /// Another URL or path serving the same archive, used to spread and retry requests (repeatable)
+    #[arg(long = "mirror", global = true, value_name = "URL")]
+    mirrors: Vec<String>,
+    /// Limit the download rate in bytes per second, e.g. 500K or 2M
+    #[arg(long, global = true, value_name = "RATE", value_parser = parse_size)]
+    limit_rate: Option<u64>,
 }
 
 #[derive(Subcommand)]
 enum Commands {
-    /// List the files inside a ZIP archive accessible via a URL
+    /// List the files inside a ZIP archive accessible via a URL or local path
     #[command(alias = "l")]
     List {
-        /// URL of the ZIP archive to list
+        /// URL or local path of the ZIP archive to list
         url: String,
+        /// Show entries as a directory tree with aggregate directory sizes
+        #[arg(short, long)]
+        tree: bool,
+        /// Print entries as tab separated lines while the directory is downloaded, in archive order
+        #[arg(long, conflicts_with = "tree")]
+        stream: bool,
     },
-    /// Extract specific files from a ZIP archive accessible via a URL
+    /// Extract specific files from a ZIP archive accessible via a URL or local path
     #[command(alias = "x")]
     Extract {
-        /// URL of the ZIP archive to extract from
+        /// URL or local path of the ZIP archive to extract from
         url: String,
         /// Files to extract from the archive
+        #[arg(required_unless_present = "include")]
+        files: Vec<String>,
+        /// Also extract files matching this pattern (repeatable)
+        #[arg(short, long = "include", value_name = "PATTERN")]
+        include: Vec<String>,
+        /// Skip files matching this pattern (repeatable)
+        #[arg(short, long = "exclude", value_name = "PATTERN")]
+        exclude: Vec<String>,
+        /// Interpret include and exclude patterns as regular expressions instead of globs
+        #[arg(long)]
+        regex: bool,
+    },
+    /// Copy specific files from a ZIP archive into a new local ZIP archive without recompressing them
+    Subset {
+        /// URL or local path of the ZIP archive to copy from
+        url: String,
+        /// Path of the new archive to write
+        #[arg(short, long, value_name = "PATH")]
+        output: PathBuf,
+        /// Files to copy into the new archive
+        #[arg(required_unless_present = "include")]
         files: Vec<String>,
+        /// Also copy files matching this pattern (repeatable)
+        #[arg(short, long = "include", value_name = "PATTERN")]
+        include: Vec<String>,
+        /// Skip files matching this pattern (repeatable)
+        #[arg(short, long = "exclude", value_name = "PATTERN")]
+        exclude: Vec<String>,
+        /// Interpret include and exclude patterns as regular expressions instead of globs
+        #[arg(long)]
+        regex: bool,
     },
 }
 
 #[tokio::main]
 async fn main() {
     let args = Args::parse();
-    let pb = ProgressBar::new_spinner().with_message("Requesting data...");
+    init_logging(args.verbose);
+    // Log lines would be garbled by the progress bar redrawing itself
+    let pb = if args.verbose > 0 {
+        ProgressBar::hidden()
+    } else {
+        ProgressBar::new_spinner().with_message("Requesting data...")
+    };
     pb.enable_steady_tick(Duration::from_millis(100));
+    // Compressed bytes of the entries already extracted, as progress is reported per download
+    let extracted_bytes = Arc::new(AtomicU64::new(0));
+    let listener = progress_listener(pb.clone(), extracted_bytes.clone());
+    let cancellation = CancellationToken::new();
+    tokio::spawn(cancel_on_interrupt(cancellation.clone()));
 
     match args.command {
-        Commands::Extract { url, files } => match RemoteZip::get(&url).await {
+        Commands::Extract {
+            url,
+            files,
+            include,
+            exclude,
+            regex,
+        } => match build_selector(&files, include, exclude, regex) {
             Err(e) => {
                 pb.finish();
                 eprintln!("{e}");
             }
-            Ok(zip) => match zip.download_files(files).await {
+            Ok(selector) => match open_zip(&url, &args.access, listener, cancellation).await {
                 Err(e) => {
                     pb.finish();
                     eprintln!("{e}");
                 }
-                Ok(files) => {
+                Ok(zip) => {
+                    let paths = selected_paths(&zip, files, selector);
+                    show_download_bar(&pb, &zip, &paths);
+
                     let mut file_count = 0;
-                    for file in files {
-                        pb.set_message(format!("Writing to disk: {}", file.0.file_name));
-                        if let Err(e) = std::fs::write(
-                            &file
-                                .0
-                                .file_name
-                                .split("/")
-                                .last()
-                                .unwrap_or(&file.0.file_name),
-                            file.1,
-                        ) {
-                            eprintln!("Failed writing {} to disk: {e}", file.0.file_name);
-                        } else {
-                            file_count += 1;
+                    let mut failure_count = 0;
+                    let mut cancelled = false;
+                    let mut destinations = HashSet::new();
+                    for path in paths {
+                        let record = zip.records().iter().find(|x| x.file_name == path);
+                        let compressed_size = record.map_or(0, |x| x.compressed_size as u64);
+                        if record.is_some_and(|x| x.is_directory()) {
+                            pb.suspend(|| {
+                                eprintln!("{path}: skipped, directories are not extracted")
+                            });
+                            failure_count += 1;
+                            continue;
+                        }
+                        let destination = path.split("/").last().unwrap_or(&path).to_string();
+                        // Files are extracted by name only, so entries of different directories may collide
+                        if !destinations.insert(destination.clone()) {
+                            pb.suspend(|| {
+                                eprintln!(
+                                    "{path}: skipped, another entry was already extracted to {destination}"
+                                )
+                            });
+                            extracted_bytes.fetch_add(compressed_size, Ordering::Relaxed);
+                            failure_count += 1;
+                            continue;
+                        }
+
+                        pb.set_message(format!("Downloading: {path}"));
+                        let result = zip.download_to_path(&path, &destination).await;
+                        extracted_bytes.fetch_add(compressed_size, Ordering::Relaxed);
+                        match result {
+                            Ok(_) => file_count += 1,
+                            Err(Error::Cancelled(_)) => {
+                                cancelled = true;
+                                break;
+                            }
+                            Err(Error::EntryNotFound(_)) => {
+                                pb.suspend(|| eprintln!("{path}: not found in archive"));
+                                failure_count += 1;
+                            }
+                            Err(e) => {
+                                pb.suspend(|| eprintln!("{path}: {e}"));
+                                failure_count += 1;
+                            }
                         }
                     }
-                    pb.finish_with_message(format!("Downloaded {file_count} files."));
+
+                    if cancelled {
+                        pb.abandon_with_message(format!(
+                            "Cancelled after downloading {file_count} files."
+                        ));
+                    } else if failure_count > 0 {
+                        pb.finish_with_message(format!(
+                            "Downloaded {file_count} files, {failure_count} failed."
+                        ));
+                    } else {
+                        pb.finish_with_message(format!("Downloaded {file_count} files."));
+                    }
+
+                    if args.stats {
+                        print_stats(&zip.stats());
+                    }
                 }
             },
         },
-        Commands::List { url } => match RemoteZip::get(&url).await {
+        Commands::Subset {
+            url,
+            output,
+            files,
+            include,
+            exclude,
+            regex,
+        } => match build_selector(&files, include, exclude, regex) {
             Err(e) => {
                 pb.finish();
                 eprintln!("{e}");
             }
-            Ok(mut zip) => {
-                pb.set_message("Processing...");
-
-                let mut table = Table::new();
-                table
-                    .load_preset(ASCII_FULL_CONDENSED)
-                    .set_content_arrangement(ContentArrangement::Dynamic)
-                    .set_header(vec![
-                        Cell::new("Path").add_attribute(comfy_table::Attribute::Bold),
-                        Cell::new("Compressed Size").add_attribute(comfy_table::Attribute::Bold),
-                        Cell::new("Uncompressed Size").add_attribute(comfy_table::Attribute::Bold),
-                    ]);
-
-                zip.records_mut()
-                    .sort_by(|x, y| x.file_name.cmp(&y.file_name));
-                for record in zip.records() {
-                    table.add_row(vec![
-                        record.file_name.clone(),
-                        ByteSizeFormatter::format_auto(
-                            record.compressed_size as u64,
-                            System::Binary,
-                        ),
-                        ByteSizeFormatter::format_auto(
-                            record.uncompressed_size as u64,
-                            System::Binary,
-                        ),
-                    ]);
+            Ok(selector) => match open_zip(&url, &args.access, listener, cancellation).await {
+                Err(e) => {
+                    pb.finish();
+                    eprintln!("{e}");
                 }
+                Ok(zip) => {
+                    let paths = selected_paths(&zip, files, selector);
+                    show_download_bar(&pb, &zip, &paths);
+                    pb.set_message(format!("Copying {} files", paths.len()));
 
-                pb.finish_and_clear();
-                println!("{table}");
-            }
+                    match zip.subset_to_path(paths, &output).await {
+                        Ok(records) => pb.finish_with_message(format!(
+                            "Copied {} files to {}.",
+                            records.len(),
+                            output.display()
+                        )),
+                        Err(Error::Cancelled(_)) => {
+                            pb.abandon_with_message("Cancelled, no archive was written.")
+                        }
+                        Err(e) => {
+                            pb.abandon();
+                            eprintln!("{e}");
+                        }
+                    }
+
+                    if args.stats {
+                        print_stats(&zip.stats());
+                    }
+                }
+            },
         },
+        Commands::List {
+            stream: true, url, ..
+        } => {
+            let result = match zip_builder(&url, &args.access, listener, cancellation) {
+                Ok(builder) => list_streaming(builder, &pb).await,
+                Err(e) => Err(e),
+            };
+            pb.finish_and_clear();
+            if let Err(e) = result {
+                eprintln!("{e}");
+            }
+        }
+        Commands::List { url, tree, .. } => {
+            match open_zip(&url, &args.access, listener, cancellation).await {
+                Err(e) => {
+                    pb.finish();
+                    eprintln!("{e}");
+                }
+                Ok(mut zip) => {
+                    pb.set_message("Processing...");
+
+                    let mut table = Table::new();
+                    table
+                        .load_preset(ASCII_FULL_CONDENSED)
+                        .set_content_arrangement(ContentArrangement::Dynamic)
+                        .set_header(vec![
+                            Cell::new("Path").add_attribute(comfy_table::Attribute::Bold),
+                            Cell::new("Compressed Size")
+                                .add_attribute(comfy_table::Attribute::Bold),
+                            Cell::new("Uncompressed Size")
+                                .add_attribute(comfy_table::Attribute::Bold),
+                        ]);
+
+                    if tree {
+                        add_tree_rows(&mut table, &zip.tree(), 0);
+                    } else {
+                        zip.records_mut()
+                            .sort_by(|x, y| x.file_name.cmp(&y.file_name));
+                        for record in zip.records() {
+                            table.add_row(vec![
+                                record.file_name.clone(),
+                                ByteSizeFormatter::format_auto(
+                                    record.compressed_size as u64,
+                                    System::Binary,
+                                ),
+                                ByteSizeFormatter::format_auto(
+                                    record.uncompressed_size as u64,
+                                    System::Binary,
+                                ),
+                            ]);
+                        }
+                    }
+
+                    pb.finish_and_clear();
+                    println!("{table}");
+
+                    if args.stats {
+                        print_stats(&zip.stats());
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Logs netzip's events to stderr, at debug level for one `-v` and trace level for more.
+fn init_logging(verbose: u8) {
+    let level = match verbose {
+        0 => return,
+        1 => LevelFilter::DEBUG,
+        _ => LevelFilter::TRACE,
+    };
+
+    tracing_subscriber::registry()
+        .with(tracing_subscriber::fmt::layer().with_writer(std::io::stderr))
+        .with(Targets::new().with_target("netzip", level))
+        .init();
+}
+
+/// Shows directory fetching in the spinner's message and entry downloads as the bar's position.
+fn progress_listener(pb: ProgressBar, extracted_bytes: Arc<AtomicU64>) -> impl ProgressListener {
+    move |progress: &Progress| match progress.stage {
+        ProgressStage::Directory => pb.set_message(format!(
+            "Fetching directory: {} of {}",
+            ByteSizeFormatter::format_auto(progress.bytes_downloaded, System::Binary),
+            ByteSizeFormatter::format_auto(progress.bytes_total, System::Binary)
+        )),
+        ProgressStage::Entries => {
+            pb.set_position(extracted_bytes.load(Ordering::Relaxed) + progress.bytes_downloaded)
+        }
     }
 }
+
+/// Cancels running operations on the first Ctrl-C, so downloads stop without leaving partial files behind,
+/// and exits immediately on the second.
+async fn cancel_on_interrupt(cancellation: CancellationToken) {
+    if tokio::signal::ctrl_c().await.is_ok() {
+        cancellation.cancel();
+        if tokio::signal::ctrl_c().await.is_ok() {
+            std::process::exit(130);
+        }
+    }
+}
+
+/// Opens the remote archive with the given access options.
+async fn open_zip(
+    url: &str,
+    access: &AccessOptions,
+    listener: impl ProgressListener + 'static,
+    cancellation: CancellationToken,
+) -> Result<RemoteZip, Error> {
+    zip_builder(url, access, listener, cancellation)?
+        .build()
+        .await
+}
+
+/// Prints each entry as soon as its directory record has been parsed, without holding the directory in memory.
+async fn list_streaming(builder: RemoteZipBuilder, pb: &ProgressBar) -> Result<(), Error> {
+    let mut records = builder.stream_records().await?;
+    while let Some(record) = records.next().await {
+        let record = record?;
+        pb.suspend(|| {
+            println!(
+                "{}\t{}\t{}",
+                record.file_name,
+                ByteSizeFormatter::format_auto(record.compressed_size as u64, System::Binary),
+                ByteSizeFormatter::format_auto(record.uncompressed_size as u64, System::Binary)
+            )
+        });
+    }
+
+    Ok(())
+}
+
+/// Configures a builder for the remote archive with its segments and mirrors, using the on-disk range cache if a cache
+/// directory was given and limiting the download rate if requested.
+fn zip_builder(
+    url: &str,
+    access: &AccessOptions,
+    listener: impl ProgressListener + 'static,
+    cancellation: CancellationToken,
+) -> Result<RemoteZipBuilder, Error> {
+    let mut builder = RemoteZip::builder(url)
+        .segments(access.segments.iter().cloned())
+        .mirrors(access.mirrors.iter().cloned())
+        .progress(listener)
+        .cancellation_token(cancellation);
+    if let Some(cache_dir) = &access.cache_dir {
+        builder = builder.cache(RangeCache::open(cache_dir, access.cache_size)?);
+    }
+    if let Some(limit_rate) = access.limit_rate {
+        builder = builder.rate_limit(RateLimiter::new(limit_rate));
+    }
+
+    Ok(builder)
+}
+
+/// Prints transfer statistics to stderr, keeping stdout free for the command's output.
+fn print_stats(stats: &TransferStats) {
+    let format = |bytes| ByteSizeFormatter::format_auto(bytes, System::Binary);
+
+    eprintln!(
+        "{} requests, {} requested, {} received, {} wasted, {:.2?} waiting for data",
+        stats.requests,
+        format(stats.bytes_requested),
+        format(stats.bytes_received),
+        format(stats.bytes_wasted),
+        stats.elapsed
+    );
+}
+
+/// Parses a byte size with an optional binary K, M, G or T suffix.
+fn parse_size(value: &str) -> Result<u64, String> {
+    let value = value.trim();
+    let (number, multiplier) = match value.char_indices().last() {
+        Some((index, suffix)) if suffix.is_ascii_alphabetic() => {
+            let multiplier = match suffix.to_ascii_uppercase() {
+                'K' => 1 << 10,
+                'M' => 1 << 20,
+                'G' => 1 << 30,
+                'T' => 1 << 40,
+                _ => return Err(format!("unknown size suffix '{suffix}'")),
+            };
+            (&value[..index], multiplier)
+        }
+        _ => (value, 1),
+    };
+
+    number
+        .trim()
+        .parse::<u64>()
+        .ok()
+        .and_then(|x| x.checked_mul(multiplier))
+        .ok_or_else(|| format!("invalid size '{value}'"))
+}
+
+/// Adds one row per child of `node`, recursing into subdirectories with increasing indentation.
+fn add_tree_rows(table: &mut Table, node: &DirectoryNode, depth: usize) {
+    let indent = "  ".repeat(depth);
+    for child in node.children() {
+        let (name, compressed_size, uncompressed_size) = match child {
+            TreeEntry::Directory(directory) => (
+                format!("{}/", directory.name()),
+                directory.compressed_size(),
+                directory.uncompressed_size(),
+            ),
+            TreeEntry::File(record) => (
+                record
+                    .file_name
+                    .rsplit('/')
+                    .next()
+                    .unwrap_or_default()
+                    .into(),
+                record.compressed_size as u64,
+                record.uncompressed_size as u64,
+            ),
+        };
+        table.add_row(vec![
+            format!("{indent}{name}"),
+            ByteSizeFormatter::format_auto(compressed_size, System::Binary),
+            ByteSizeFormatter::format_auto(uncompressed_size, System::Binary),
+        ]);
+
+        if let TreeEntry::Directory(directory) = child {
+            add_tree_rows(table, directory, depth + 1);
+        }
+    }
+}
+
+/// Returns the entries matching the selector in archive order, followed by the named files the selector does
+/// not return, which are either not in the archive or directories. They are passed on so they are reported
+/// rather than silently skipped, unless they are excluded.
+fn selected_paths(zip: &RemoteZip, files: Vec<String>, selector: EntrySelector) -> Vec<String> {
+    let mut paths: Vec<String> = zip
+        .select(&selector)
+        .into_iter()
+        .map(|x| x.file_name.clone())
+        .collect();
+    for file in files {
+        let record = zip.records().iter().find(|x| x.file_name == file);
+        let directory = record.is_some_and(|x| x.is_directory()) && selector.matches(&file);
+        if (record.is_none() || directory) && !paths.contains(&file) {
+            paths.push(file);
+        }
+    }
+
+    paths
+}
+
+/// Turns the spinner into a bar tracking the compressed bytes of the given entries.
+fn show_download_bar(pb: &ProgressBar, zip: &RemoteZip, paths: &[String]) {
+    let total_bytes = zip
+        .records()
+        .iter()
+        .filter(|x| paths.contains(&x.file_name))
+        .map(|x| x.compressed_size as u64)
+        .sum();
+    pb.set_length(total_bytes);
+    pb.set_style(
+        ProgressStyle::with_template("{spinner} [{bar:30}] {bytes}/{total_bytes} ({eta}) {msg}")
+            .expect("The progress bar template should be valid")
+            .progress_chars("=> "),
+    );
+}
+
+/// Builds the entry selector for the extract and subset commands. Explicitly named files are matched literally.
+fn build_selector(
+    files: &[String],
+    include: Vec<String>,
+    exclude: Vec<String>,
+    regex: bool,
+) -> Result<EntrySelector, Error> {
+    let compile = if regex { Pattern::regex } else { Pattern::glob };
+    let mut selector = EntrySelector::new();
+    for file in files {
+        selector = selector.include(Pattern::literal(file));
+    }
+    for pattern in include {
+        selector = selector.include(compile(&pattern)?);
+    }
+    for pattern in exclude {
+        selector = selector.exclude(compile(&pattern)?);
+    }
+
+    Ok(selector)
+}
@@ -0,0 +1,16 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use netzip_parser::{CentralDirectoryRecord, CentralDirectoryRecordRef};
+
+fuzz_target!(|data: &[u8]| {
+    if let Ok(length) = CentralDirectoryRecord::record_length(data) {
+        assert!(length >= netzip_parser::CDR_MIN_SIZE);
+    }
+
+    for allow_extraneous in [false, true] {
+        let owned = CentralDirectoryRecord::parse(data, allow_extraneous);
+        let borrowed = CentralDirectoryRecordRef::parse(data, allow_extraneous);
+        assert_eq!(owned, borrowed.map(Into::into));
+    }
+});
@@ -0,0 +1,32 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use netzip_parser::{
+    CentralDirectoryEnd, CentralDirectoryRecord, CentralDirectoryRecordRef, ZipError,
+};
+
+fuzz_target!(|data: &[u8]| {
+    let Ok(end) = CentralDirectoryEnd::find_and_parse(data) else {
+        return;
+    };
+    let start = end.central_directory_offset as usize;
+    let Some(directory) = start
+        .checked_add(end.directory_size as usize)
+        .and_then(|stop| data.get(start..stop))
+    else {
+        return;
+    };
+
+    if let Ok(records) = CentralDirectoryRecord::parse_directory(directory, &end) {
+        assert!(end.check_record_count(records.len()).is_ok());
+    }
+
+    // Classify the bytes after the last record that parses, like a parser receiving the directory in chunks
+    let mut found = 0;
+    let mut tail = directory;
+    while let Ok(record) = CentralDirectoryRecordRef::parse(tail, true) {
+        tail = &tail[record.as_bytes().len()..];
+        found += 1;
+    }
+    let _ = end.directory_tail_error(found, tail, ZipError::MissingData("Central Directory"));
+});
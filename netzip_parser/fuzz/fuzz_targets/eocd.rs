@@ -0,0 +1,9 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use netzip_parser::CentralDirectoryEnd;
+
+fuzz_target!(|data: &[u8]| {
+    let _ = CentralDirectoryEnd::parse(data);
+    let _ = CentralDirectoryEnd::find_and_parse(data);
+});
@@ -0,0 +1,12 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use netzip_parser::{LocalFile, LocalFileRef};
+
+fuzz_target!(|data: &[u8]| {
+    let _ = LocalFile::header_length(data);
+    assert_eq!(
+        LocalFile::parse(data),
+        LocalFileRef::parse(data).map(Into::into)
+    );
+});
@@ -0,0 +1,14 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use netzip_parser::{CentralDirectoryRecord, CentralDirectoryRecordRef};
+
+fuzz_target!(|data: &[u8]| {
+    if let Ok(records) = CentralDirectoryRecord::parse_many(data) {
+        let length: usize = CentralDirectoryRecordRef::iter_many(data)
+            .map(|x| x.unwrap().as_bytes().len())
+            .sum();
+        assert_eq!(length, data.len());
+        assert!(records.len() <= data.len() / netzip_parser::CDR_MIN_SIZE);
+    }
+});
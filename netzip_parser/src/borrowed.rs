@@ -0,0 +1,303 @@
+use std::borrow::Cow;
+
+use crate::*;
+
+/// A central directory record borrowed from the buffer it was parsed from.
+///
+/// Parsing only validates the magic and lengths, every field is decoded when it is accessed. File names and
+/// comments are only copied if they are not valid UTF-8, which makes scanning large directories for a few
+/// records considerably cheaper than parsing them into [CentralDirectoryRecord]s.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub struct CentralDirectoryRecordRef<'a> {
+    buf: &'a [u8],
+}
+
+impl<'a> CentralDirectoryRecordRef<'a> {
+    /// Parses the record at the start of the buffer, which must not contain anything past the record unless
+    /// `allow_extraneous` is set.
+    pub fn parse(record_buf: &'a [u8], allow_extraneous: bool) -> Result<Self> {
+        let required_length = CentralDirectoryRecord::record_length(record_buf)?;
+
+        if record_buf.len() < required_length {
+            return Err(ZipError::MissingData("CDR Variable Length Fields"));
+        } else if record_buf.len() > required_length && !allow_extraneous {
+            return Err(ZipError::ExtraneousData("CDR"));
+        }
+
+        Ok(Self {
+            buf: &record_buf[..required_length],
+        })
+    }
+
+    /// Returns an iterator over the records in a buffer containing nothing but consecutive records, like a
+    /// complete central directory. The iterator ends after the first error.
+    pub fn iter_many(records_buf: &'a [u8]) -> CentralDirectoryRecordRefs<'a> {
+        CentralDirectoryRecordRefs {
+            remaining: records_buf,
+        }
+    }
+
+    /// Returns the raw bytes of the record.
+    pub fn as_bytes(&self) -> &'a [u8] {
+        self.buf
+    }
+
+    pub fn zip_version_created(&self) -> u16 {
+        read_u16(self.buf, CDR_VERSION_CREATED_OFFSET)
+    }
+
+    pub fn zip_version_required(&self) -> u16 {
+        read_u16(self.buf, CDR_VERSION_REQUIRED_OFFSET)
+    }
+
+    pub fn gp_bit_flag(&self) -> u16 {
+        read_u16(self.buf, CDR_BIT_FLAG_OFFSET)
+    }
+
+    pub fn compression_method(&self) -> CompressionMethod {
+        CompressionMethod::from(read_u16(self.buf, CDR_COMPRESSION_METHOD_OFFSET))
+    }
+
+    pub fn last_modification_time(&self) -> u16 {
+        read_u16(self.buf, CDR_MOD_TIME_OFFSET)
+    }
+
+    pub fn last_modification_date(&self) -> u16 {
+        read_u16(self.buf, CDR_MOD_DATE_OFFSET)
+    }
+
+    pub fn crc32(&self) -> u32 {
+        read_u32(self.buf, CDR_CRC32_OFFSET)
+    }
+
+    pub fn compressed_size(&self) -> u32 {
+        read_u32(self.buf, CDR_COMPRESSED_SIZE_OFFSET)
+    }
+
+    pub fn uncompressed_size(&self) -> u32 {
+        read_u32(self.buf, CDR_UNCOMPRESSED_SIZE_OFFSET)
+    }
+
+    pub fn file_name_length(&self) -> u16 {
+        read_u16(self.buf, CDR_FILE_NAME_LENGTH_OFFSET)
+    }
+
+    pub fn extra_field_length(&self) -> u16 {
+        read_u16(self.buf, CDR_EXTRA_FIELD_LENGTH_OFFSET)
+    }
+
+    pub fn file_comment_length(&self) -> u16 {
+        read_u16(self.buf, CDR_COMMENT_LENGTH_OFFSET)
+    }
+
+    pub fn disk_number(&self) -> u16 {
+        read_u16(self.buf, CDR_DISK_NUMBER_OFFSET)
+    }
+
+    pub fn file_attributes_internal(&self) -> u16 {
+        read_u16(self.buf, CDR_INTERNAL_ATTRS_OFFSET)
+    }
+
+    pub fn file_attributes_external(&self) -> u32 {
+        read_u32(self.buf, CDR_EXTERNAL_ATTRS_OFFSET)
+    }
+
+    pub fn file_header_offset(&self) -> u32 {
+        read_u32(self.buf, CDR_FILE_HEADER_OFFSET)
+    }
+
+    /// Returns the file name as stored in the archive.
+    pub fn file_name_bytes(&self) -> &'a [u8] {
+        let start = CDR_FILE_NAME_START;
+        &self.buf[start..start + self.file_name_length() as usize]
+    }
+
+    /// Returns the file name, replacing invalid UTF-8 sequences like [CentralDirectoryRecord::parse] does.
+    pub fn file_name(&self) -> Cow<'a, str> {
+        String::from_utf8_lossy(self.file_name_bytes())
+    }
+
+    pub fn extra_bytes(&self) -> Option<&'a [u8]> {
+        let start = CDR_FILE_NAME_START + self.file_name_length() as usize;
+        let length = self.extra_field_length() as usize;
+        (length > 0).then(|| &self.buf[start..start + length])
+    }
+
+    pub fn comment(&self) -> Option<Cow<'a, str>> {
+        let start = CDR_FILE_NAME_START
+            + self.file_name_length() as usize
+            + self.extra_field_length() as usize;
+        let length = self.file_comment_length() as usize;
+        (length > 0).then(|| String::from_utf8_lossy(&self.buf[start..start + length]))
+    }
+
+    /// Returns true if the record describes a directory rather than a file.
+    pub fn is_directory(&self) -> bool {
+        self.file_name_bytes().ends_with(b"/")
+    }
+
+    /// Decodes all fields into an owned record.
+    pub fn into_owned(self) -> CentralDirectoryRecord {
+        CentralDirectoryRecord {
+            zip_version_created: self.zip_version_created(),
+            zip_version_required: self.zip_version_required(),
+            gp_bit_flag: self.gp_bit_flag(),
+            compression_method: self.compression_method(),
+            last_modification_time: self.last_modification_time(),
+            last_modification_date: self.last_modification_date(),
+            crc32: self.crc32(),
+            compressed_size: self.compressed_size(),
+            uncompressed_size: self.uncompressed_size(),
+            file_name_length: self.file_name_length(),
+            extra_field_length: self.extra_field_length(),
+            file_comment_length: self.file_comment_length(),
+            disk_number: self.disk_number(),
+            file_attributes_internal: self.file_attributes_internal(),
+            file_attributes_external: self.file_attributes_external(),
+            file_header_offset: self.file_header_offset(),
+            file_name: self.file_name().into_owned(),
+            extra_bytes: self.extra_bytes().map(<[u8]>::to_vec),
+            comment: self.comment().map(Cow::into_owned),
+        }
+    }
+}
+
+impl From<CentralDirectoryRecordRef<'_>> for CentralDirectoryRecord {
+    fn from(record: CentralDirectoryRecordRef<'_>) -> Self {
+        record.into_owned()
+    }
+}
+
+/// Iterator over consecutive central directory records, created by [CentralDirectoryRecordRef::iter_many].
+#[derive(Debug, Clone)]
+pub struct CentralDirectoryRecordRefs<'a> {
+    remaining: &'a [u8],
+}
+
+impl<'a> Iterator for CentralDirectoryRecordRefs<'a> {
+    type Item = Result<CentralDirectoryRecordRef<'a>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.remaining.is_empty() {
+            return None;
+        }
+
+        match CentralDirectoryRecordRef::parse(self.remaining, true) {
+            Ok(record) => {
+                self.remaining = &self.remaining[record.buf.len()..];
+                Some(Ok(record))
+            }
+            Err(e) => {
+                self.remaining = &[];
+                Some(Err(e))
+            }
+        }
+    }
+}
+
+/// A local file header borrowed from the buffer it was parsed from, decoding fields when they are accessed.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub struct LocalFileRef<'a> {
+    buf: &'a [u8],
+}
+
+impl<'a> LocalFileRef<'a> {
+    /// Parses the header at the start of the buffer. Anything following the header, like the file data, is
+    /// ignored.
+    pub fn parse(file_buf: &'a [u8]) -> Result<Self> {
+        let header_length = LocalFile::header_length(file_buf)?;
+        if header_length > file_buf.len() {
+            return Err(ZipError::MissingData("Local File Variable Length Fields"));
+        }
+
+        Ok(Self {
+            buf: &file_buf[..header_length],
+        })
+    }
+
+    /// Returns the raw bytes of the header.
+    pub fn as_bytes(&self) -> &'a [u8] {
+        self.buf
+    }
+
+    pub fn zip_version(&self) -> u16 {
+        read_u16(self.buf, LFH_VERSION_OFFSET)
+    }
+
+    pub fn gp_bit_flag(&self) -> u16 {
+        read_u16(self.buf, LFH_BIT_FLAG_OFFSET)
+    }
+
+    pub fn compression_method(&self) -> CompressionMethod {
+        CompressionMethod::from(read_u16(self.buf, LFH_COMPRESSION_METHOD_OFFSET))
+    }
+
+    pub fn last_modification_time(&self) -> u16 {
+        read_u16(self.buf, LFH_MOD_TIME_OFFSET)
+    }
+
+    pub fn last_modification_date(&self) -> u16 {
+        read_u16(self.buf, LFH_MOD_DATE_OFFSET)
+    }
+
+    pub fn crc32(&self) -> u32 {
+        read_u32(self.buf, LFH_CRC32_OFFSET)
+    }
+
+    pub fn compressed_size(&self) -> u32 {
+        read_u32(self.buf, LFH_COMPRESSED_SIZE_OFFSET)
+    }
+
+    pub fn uncompressed_size(&self) -> u32 {
+        read_u32(self.buf, LFH_UNCOMPRESSED_SIZE_OFFSET)
+    }
+
+    pub fn file_name_length(&self) -> u16 {
+        read_u16(self.buf, LFH_FILE_NAME_LENGTH_OFFSET)
+    }
+
+    pub fn extra_field_length(&self) -> u16 {
+        read_u16(self.buf, LFH_EXTRA_FIELD_LENGTH_OFFSET)
+    }
+
+    /// Returns the file name as stored in the archive.
+    pub fn file_name_bytes(&self) -> &'a [u8] {
+        let start = LFH_FILE_NAME_START;
+        &self.buf[start..start + self.file_name_length() as usize]
+    }
+
+    /// Returns the file name, replacing invalid UTF-8 sequences like [LocalFile::parse] does.
+    pub fn file_name(&self) -> Cow<'a, str> {
+        String::from_utf8_lossy(self.file_name_bytes())
+    }
+
+    pub fn extra_bytes(&self) -> Option<&'a [u8]> {
+        let start = LFH_FILE_NAME_START + self.file_name_length() as usize;
+        let length = self.extra_field_length() as usize;
+        (length > 0).then(|| &self.buf[start..start + length])
+    }
+
+    /// Decodes all fields into an owned header.
+    pub fn into_owned(self) -> LocalFile {
+        LocalFile {
+            zip_version: self.zip_version(),
+            gp_bit_flag: self.gp_bit_flag(),
+            compression_method: self.compression_method(),
+            last_modification_time: self.last_modification_time(),
+            last_modification_date: self.last_modification_date(),
+            crc32: self.crc32(),
+            compressed_size: self.compressed_size(),
+            uncompressed_size: self.uncompressed_size(),
+            file_name_length: self.file_name_length(),
+            file_name: self.file_name().into_owned(),
+            extra_field_length: self.extra_field_length(),
+            extra_bytes: self.extra_bytes().map(<[u8]>::to_vec),
+        }
+    }
+}
+
+impl From<LocalFileRef<'_>> for LocalFile {
+    fn from(local_file: LocalFileRef<'_>) -> Self {
+        local_file.into_owned()
+    }
+}
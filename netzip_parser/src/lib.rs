@@ -1,11 +1,27 @@
 use thiserror::Error;
 
+mod borrowed;
+mod serialize;
+#[cfg(feature = "writer")]
+mod writer;
+mod zip64;
+
+pub use borrowed::{CentralDirectoryRecordRef, CentralDirectoryRecordRefs, LocalFileRef};
+#[cfg(feature = "writer")]
+pub use writer::{EntryOptions, ZipWriter};
+pub use zip64::{
+    ZIP64_EOCD_LOCATOR_SIZE, ZIP64_EOCD_MIN_SIZE, ZIP64_EXTRA_FIELD_ID, ZIP64_PLACEHOLDER_U16,
+    ZIP64_PLACEHOLDER_U32, Zip64CentralDirectoryEnd, Zip64CentralDirectoryEndLocator,
+    Zip64ExtraField,
+};
+
 const MAGIC_CENTRAL_DIRECTORY_END: [u8; 4] = [0x50, 0x4B, 0x05, 0x06];
 const MAGIC_CENTRAL_DIRECTORY_RECORD: [u8; 4] = [0x50, 0x4B, 0x01, 0x02];
 const MAGIC_LOCAL_FILE: [u8; 4] = [0x50, 0x4B, 0x03, 0x04];
 
 pub const EOCD_MIN_SIZE: usize = 22;
 const EOCD_BASE_OFFSET: usize = MAGIC_CENTRAL_DIRECTORY_END.len();
+#[allow(clippy::identity_op)]
 const EOCD_DISK_NUMBER_OFFSET: usize = EOCD_BASE_OFFSET + 0;
 const EOCD_DISK_START_OFFSET: usize = EOCD_BASE_OFFSET + 2;
 const EOCD_RECORD_COUNT_DISK_OFFSET: usize = EOCD_BASE_OFFSET + 4;
@@ -17,6 +33,7 @@ const EOCD_COMMENT_START: usize = EOCD_BASE_OFFSET + 18;
 
 pub const CDR_MIN_SIZE: usize = 46;
 const CDR_BASE_OFFSET: usize = MAGIC_CENTRAL_DIRECTORY_RECORD.len();
+#[allow(clippy::identity_op)]
 const CDR_VERSION_CREATED_OFFSET: usize = CDR_BASE_OFFSET + 0;
 const CDR_VERSION_REQUIRED_OFFSET: usize = CDR_BASE_OFFSET + 2;
 const CDR_BIT_FLAG_OFFSET: usize = CDR_BASE_OFFSET + 4;
@@ -37,6 +54,7 @@ const CDR_FILE_NAME_START: usize = CDR_BASE_OFFSET + 42;
 
 pub const LFH_MIN_SIZE: usize = 30;
 const LFH_BASE_OFFSET: usize = MAGIC_LOCAL_FILE.len();
+#[allow(clippy::identity_op)]
 const LFH_VERSION_OFFSET: usize = LFH_BASE_OFFSET + 0;
 const LFH_BIT_FLAG_OFFSET: usize = LFH_BASE_OFFSET + 2;
 const LFH_COMPRESSION_METHOD_OFFSET: usize = LFH_BASE_OFFSET + 4;
@@ -51,7 +69,34 @@ const LFH_FILE_NAME_START: usize = LFH_BASE_OFFSET + 26;
 
 type Result<R> = std::result::Result<R, ZipError>;
 
-#[derive(Debug, Eq, PartialEq)]
+fn read_u16(buf: &[u8], offset: usize) -> u16 {
+    u16::from_le_bytes([buf[offset], buf[offset + 1]])
+}
+
+fn read_u32(buf: &[u8], offset: usize) -> u32 {
+    u32::from_le_bytes([
+        buf[offset],
+        buf[offset + 1],
+        buf[offset + 2],
+        buf[offset + 3],
+    ])
+}
+
+fn read_u64(buf: &[u8], offset: usize) -> u64 {
+    u64::from_le_bytes([
+        buf[offset],
+        buf[offset + 1],
+        buf[offset + 2],
+        buf[offset + 3],
+        buf[offset + 4],
+        buf[offset + 5],
+        buf[offset + 6],
+        buf[offset + 7],
+    ])
+}
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct CentralDirectoryEnd {
     pub disk_number: u16,
     pub disk_start: u16,
@@ -65,7 +110,8 @@ pub struct CentralDirectoryEnd {
     pub comment: Option<String>,
 }
 
-#[derive(Debug, Eq, PartialEq)]
+#[derive(Debug, Clone, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct CentralDirectoryRecord {
     pub zip_version_created: u16,
     pub zip_version_required: u16,
@@ -88,7 +134,8 @@ pub struct CentralDirectoryRecord {
     pub comment: Option<String>,
 }
 
-#[derive(Debug, Eq, PartialEq)]
+#[derive(Debug, Clone, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct LocalFile {
     pub zip_version: u16,
     pub gp_bit_flag: u16,
@@ -105,7 +152,8 @@ pub struct LocalFile {
 }
 
 #[repr(u16)]
-#[derive(Debug, Eq, PartialEq)]
+#[derive(Debug, Clone, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum CompressionMethod {
     Stored = 0,
     Deflate = 8,
@@ -113,6 +161,29 @@ pub enum CompressionMethod {
     Unsupported(u16),
 }
 
+impl CompressionMethod {
+    /// Returns the method's identifier as stored in headers.
+    pub fn id(&self) -> u16 {
+        match self {
+            CompressionMethod::Stored => 0,
+            CompressionMethod::Deflate => 8,
+            CompressionMethod::Deflate64 => 9,
+            CompressionMethod::Unsupported(x) => *x,
+        }
+    }
+}
+
+impl From<u16> for CompressionMethod {
+    fn from(id: u16) -> Self {
+        match id {
+            0 => CompressionMethod::Stored,
+            8 => CompressionMethod::Deflate,
+            9 => CompressionMethod::Deflate64,
+            x => CompressionMethod::Unsupported(x),
+        }
+    }
+}
+
 #[non_exhaustive]
 #[derive(Error, Debug, Eq, PartialEq)]
 pub enum ZipError {
@@ -122,6 +193,12 @@ pub enum ZipError {
     MalformedData(&'static str),
     #[error("Encountered extraneous data while parsing {0}.")]
     ExtraneousData(&'static str),
+    #[error("Central directory is {1} bytes long, but the end of central directory declares {0}.")]
+    DirectorySizeMismatch(u32, usize),
+    #[error(
+        "Central directory contains {1} records, but the end of central directory declares {0}."
+    )]
+    RecordCountMismatch(u16, usize),
 }
 
 impl CentralDirectoryEnd {
@@ -209,25 +286,97 @@ impl CentralDirectoryEnd {
 
         Err(ZipError::MissingData("EOCD Magic"))
     }
+
+    /// Classifies a failure to parse a central directory record after `found` records were parsed. Bytes that
+    /// do not start like a record, or that follow all declared records, are reported as extraneous data such as
+    /// padding, and anything else as the damaged record's `error`.
+    ///
+    /// # Arguments
+    ///
+    /// * `found` - The number of records parsed before the failing one
+    /// * `tail` - The remaining bytes of the directory, starting at the failing record
+    /// * `error` - The error parsing the record failed with
+    ///
+    /// # Returns
+    ///
+    /// The error to report for the directory
+    pub fn directory_tail_error(&self, found: usize, tail: &[u8], error: ZipError) -> ZipError {
+        let prefix = tail.len().min(MAGIC_CENTRAL_DIRECTORY_RECORD.len());
+        let past_declared = self.record_count_total != ZIP64_PLACEHOLDER_U16
+            && found >= self.record_count_total as usize;
+        if past_declared || tail[..prefix] != MAGIC_CENTRAL_DIRECTORY_RECORD[..prefix] {
+            return ZipError::ExtraneousData("Central Directory");
+        }
+
+        error
+    }
+
+    /// Checks the number of records found in the central directory against the declared one. Any number
+    /// is accepted for ZIP64 archives declaring more records than fit into the end of central directory.
+    pub fn check_record_count(&self, found: usize) -> Result<()> {
+        if self.record_count_total != ZIP64_PLACEHOLDER_U16
+            && found != self.record_count_total as usize
+        {
+            return Err(ZipError::RecordCountMismatch(
+                self.record_count_total,
+                found,
+            ));
+        }
+
+        Ok(())
+    }
 }
 
 impl CentralDirectoryRecord {
+    /// Returns true if the record describes a directory rather than a file.
+    pub fn is_directory(&self) -> bool {
+        self.file_name.ends_with('/')
+    }
+
+    /// Parses a buffer containing nothing but consecutive records, like a complete central directory.
     pub fn parse_many(records_buf: &[u8]) -> Result<Vec<Self>> {
+        CentralDirectoryRecordRef::iter_many(records_buf)
+            .map(|x| x.map(Into::into))
+            .collect()
+    }
+
+    /// Parses a complete central directory, checking it against the size and number of records declared by
+    /// the end of central directory record.
+    ///
+    /// Directories of ZIP64 archives with too many records for the end of central directory are only checked
+    /// for their size, as their record count is stored in the ZIP64 end of central directory.
+    pub fn parse_directory(directory_buf: &[u8], end: &CentralDirectoryEnd) -> Result<Vec<Self>> {
+        if directory_buf.len() != end.directory_size as usize {
+            return Err(ZipError::DirectorySizeMismatch(
+                end.directory_size,
+                directory_buf.len(),
+            ));
+        }
+
+        let expected = end.record_count_total;
+        let zip64 = expected == ZIP64_PLACEHOLDER_U16;
         let mut out = Vec::new();
-        let mut cursor = 0;
-        while cursor < records_buf.len() - 1 {
-            let current_record = Self::parse(&records_buf[cursor..], true)?;
-            cursor += CDR_MIN_SIZE
-                + current_record.file_comment_length as usize
-                + current_record.file_name_length as usize
-                + current_record.extra_field_length as usize;
-            out.push(current_record);
+        // Records past the declared count are only counted, to report how many there are
+        let mut extra = 0;
+        let mut remaining = directory_buf;
+        while !remaining.is_empty() {
+            let record = CentralDirectoryRecordRef::parse(remaining, true)
+                .map_err(|e| end.directory_tail_error(out.len() + extra, remaining, e))?;
+            remaining = &remaining[record.as_bytes().len()..];
+            if zip64 || out.len() < expected as usize {
+                out.push(record.into_owned());
+            } else {
+                extra += 1;
+            }
         }
+        end.check_record_count(out.len() + extra)?;
 
         Ok(out)
     }
 
-    pub fn parse(record_buf: &[u8], allow_extraneous: bool) -> Result<Self> {
+    /// Returns the length of the central directory record at the start of the buffer, including the variable
+    /// length file name, extra field and comment. Only the fixed size part of the record needs to be present.
+    pub fn record_length(record_buf: &[u8]) -> Result<usize> {
         if record_buf.len() < CDR_MIN_SIZE {
             return Err(ZipError::MissingData("CDR (Initial Length Check)"));
         }
@@ -238,142 +387,27 @@ impl CentralDirectoryRecord {
             }
         }
 
-        let file_name_length = u16::from_le_bytes([
-            record_buf[CDR_FILE_NAME_LENGTH_OFFSET],
-            record_buf[CDR_FILE_NAME_LENGTH_OFFSET + 1],
-        ]);
-
-        let extra_field_length = u16::from_le_bytes([
-            record_buf[CDR_EXTRA_FIELD_LENGTH_OFFSET],
-            record_buf[CDR_EXTRA_FIELD_LENGTH_OFFSET + 1],
-        ]);
-
-        let file_comment_length = u16::from_le_bytes([
-            record_buf[CDR_COMMENT_LENGTH_OFFSET],
-            record_buf[CDR_COMMENT_LENGTH_OFFSET + 1],
-        ]);
-
-        let required_length = CDR_FILE_NAME_START
-            + file_name_length as usize
-            + extra_field_length as usize
-            + file_comment_length as usize;
-
-        if record_buf.len() < required_length {
-            return Err(ZipError::MissingData("CDR Variable Length Fields"));
-        } else if record_buf.len() > required_length && !allow_extraneous {
-            return Err(ZipError::ExtraneousData("CDR"));
-        }
-
-        let compression_method_raw = u16::from_le_bytes([
-            record_buf[CDR_COMPRESSION_METHOD_OFFSET],
-            record_buf[CDR_COMPRESSION_METHOD_OFFSET + 1],
-        ]);
-
-        let compression_method = match compression_method_raw {
-            0 => CompressionMethod::Stored,
-            8 => CompressionMethod::Deflate,
-            9 => CompressionMethod::Deflate64,
-            x => CompressionMethod::Unsupported(x),
-        };
-
-        let mut record = CentralDirectoryRecord {
-            zip_version_created: u16::from_le_bytes([
-                record_buf[CDR_VERSION_CREATED_OFFSET],
-                record_buf[CDR_VERSION_CREATED_OFFSET + 1],
-            ]),
-            zip_version_required: u16::from_le_bytes([
-                record_buf[CDR_VERSION_REQUIRED_OFFSET],
-                record_buf[CDR_VERSION_REQUIRED_OFFSET + 1],
-            ]),
-            gp_bit_flag: u16::from_le_bytes([
-                record_buf[CDR_BIT_FLAG_OFFSET],
-                record_buf[CDR_BIT_FLAG_OFFSET + 1],
-            ]),
-            compression_method,
-            last_modification_time: u16::from_le_bytes([
-                record_buf[CDR_MOD_TIME_OFFSET],
-                record_buf[CDR_MOD_TIME_OFFSET + 1],
-            ]),
-            last_modification_date: u16::from_le_bytes([
-                record_buf[CDR_MOD_DATE_OFFSET],
-                record_buf[CDR_MOD_DATE_OFFSET + 1],
-            ]),
-            crc32: u32::from_le_bytes([
-                record_buf[CDR_CRC32_OFFSET],
-                record_buf[CDR_CRC32_OFFSET + 1],
-                record_buf[CDR_CRC32_OFFSET + 2],
-                record_buf[CDR_CRC32_OFFSET + 3],
-            ]),
-            compressed_size: u32::from_le_bytes([
-                record_buf[CDR_COMPRESSED_SIZE_OFFSET],
-                record_buf[CDR_COMPRESSED_SIZE_OFFSET + 1],
-                record_buf[CDR_COMPRESSED_SIZE_OFFSET + 2],
-                record_buf[CDR_COMPRESSED_SIZE_OFFSET + 3],
-            ]),
-            uncompressed_size: u32::from_le_bytes([
-                record_buf[CDR_UNCOMPRESSED_SIZE_OFFSET],
-                record_buf[CDR_UNCOMPRESSED_SIZE_OFFSET + 1],
-                record_buf[CDR_UNCOMPRESSED_SIZE_OFFSET + 2],
-                record_buf[CDR_UNCOMPRESSED_SIZE_OFFSET + 3],
-            ]),
-            file_name_length,
-            extra_field_length,
-            file_comment_length,
-            disk_number: u16::from_le_bytes([
-                record_buf[CDR_DISK_NUMBER_OFFSET],
-                record_buf[CDR_DISK_NUMBER_OFFSET + 1],
-            ]),
-            file_attributes_internal: u16::from_le_bytes([
-                record_buf[CDR_INTERNAL_ATTRS_OFFSET],
-                record_buf[CDR_INTERNAL_ATTRS_OFFSET + 1],
-            ]),
-            file_attributes_external: u32::from_le_bytes([
-                record_buf[CDR_EXTERNAL_ATTRS_OFFSET],
-                record_buf[CDR_EXTERNAL_ATTRS_OFFSET + 1],
-                record_buf[CDR_EXTERNAL_ATTRS_OFFSET + 2],
-                record_buf[CDR_EXTERNAL_ATTRS_OFFSET + 3],
-            ]),
-            file_header_offset: u32::from_le_bytes([
-                record_buf[CDR_FILE_HEADER_OFFSET],
-                record_buf[CDR_FILE_HEADER_OFFSET + 1],
-                record_buf[CDR_FILE_HEADER_OFFSET + 2],
-                record_buf[CDR_FILE_HEADER_OFFSET + 3],
-            ]),
-            file_name: String::new(),
-            extra_bytes: None,
-            comment: None,
-        };
-
-        let mut current_offset = CDR_FILE_NAME_START;
-
-        record.file_name = String::from_utf8_lossy(
-            &record_buf[current_offset..current_offset + file_name_length as usize],
-        )
-        .into_owned();
-        current_offset += file_name_length as usize;
-
-        if extra_field_length > 0 {
-            record.extra_bytes = Some(
-                record_buf[current_offset..current_offset + extra_field_length as usize].to_vec(),
-            );
-            current_offset += extra_field_length as usize;
-        }
+        let variable_length = [
+            CDR_FILE_NAME_LENGTH_OFFSET,
+            CDR_EXTRA_FIELD_LENGTH_OFFSET,
+            CDR_COMMENT_LENGTH_OFFSET,
+        ]
+        .into_iter()
+        .map(|x| u16::from_le_bytes([record_buf[x], record_buf[x + 1]]) as usize)
+        .sum::<usize>();
 
-        if file_comment_length > 0 {
-            record.comment = Some(
-                String::from_utf8_lossy(
-                    &record_buf[current_offset..current_offset + file_comment_length as usize],
-                )
-                .into_owned(),
-            );
-        }
+        Ok(CDR_FILE_NAME_START + variable_length)
+    }
 
-        Ok(record)
+    pub fn parse(record_buf: &[u8], allow_extraneous: bool) -> Result<Self> {
+        CentralDirectoryRecordRef::parse(record_buf, allow_extraneous).map(Into::into)
     }
 }
 
 impl LocalFile {
-    pub fn parse(file_buf: &[u8]) -> Result<Self> {
+    /// Returns the length of the local file header at the start of the buffer, including the variable
+    /// length file name and extra field. Only the fixed size part of the header needs to be present.
+    pub fn header_length(file_buf: &[u8]) -> Result<usize> {
         if file_buf.len() < LFH_MIN_SIZE {
             return Err(ZipError::MissingData("Local File (Initial Length Check)"));
         }
@@ -394,81 +428,18 @@ impl LocalFile {
             file_buf[LFH_EXTRA_FIELD_LENGTH_OFFSET + 1],
         ]);
 
-        let compressed_size = u32::from_le_bytes([
-            file_buf[LFH_COMPRESSED_SIZE_OFFSET],
-            file_buf[LFH_COMPRESSED_SIZE_OFFSET + 1],
-            file_buf[LFH_COMPRESSED_SIZE_OFFSET + 2],
-            file_buf[LFH_COMPRESSED_SIZE_OFFSET + 3],
-        ]);
-
-        let compression_method_raw = u16::from_le_bytes([
-            file_buf[LFH_COMPRESSION_METHOD_OFFSET],
-            file_buf[LFH_COMPRESSION_METHOD_OFFSET + 1],
-        ]);
-
-        let compression_method = match compression_method_raw {
-            0 => CompressionMethod::Stored,
-            8 => CompressionMethod::Deflate,
-            9 => CompressionMethod::Deflate64,
-            x => CompressionMethod::Unsupported(x),
-        };
-
-        let mut local_file = LocalFile {
-            zip_version: u16::from_le_bytes([
-                file_buf[LFH_VERSION_OFFSET],
-                file_buf[LFH_VERSION_OFFSET + 1],
-            ]),
-            gp_bit_flag: u16::from_le_bytes([
-                file_buf[LFH_BIT_FLAG_OFFSET],
-                file_buf[LFH_BIT_FLAG_OFFSET + 1],
-            ]),
-            compression_method,
-            last_modification_time: u16::from_le_bytes([
-                file_buf[LFH_MOD_TIME_OFFSET],
-                file_buf[LFH_MOD_TIME_OFFSET + 1],
-            ]),
-            last_modification_date: u16::from_le_bytes([
-                file_buf[LFH_MOD_DATE_OFFSET],
-                file_buf[LFH_MOD_DATE_OFFSET + 1],
-            ]),
-            crc32: u32::from_le_bytes([
-                file_buf[LFH_CRC32_OFFSET],
-                file_buf[LFH_CRC32_OFFSET + 1],
-                file_buf[LFH_CRC32_OFFSET + 2],
-                file_buf[LFH_CRC32_OFFSET + 3],
-            ]),
-            compressed_size,
-            uncompressed_size: u32::from_le_bytes([
-                file_buf[LFH_UNCOMPRESSED_SIZE_OFFSET],
-                file_buf[LFH_UNCOMPRESSED_SIZE_OFFSET + 1],
-                file_buf[LFH_UNCOMPRESSED_SIZE_OFFSET + 2],
-                file_buf[LFH_UNCOMPRESSED_SIZE_OFFSET + 3],
-            ]),
-            file_name: String::new(),
-            file_name_length,
-            extra_field_length,
-            extra_bytes: None,
-        };
-
-        let mut current_offset = LFH_FILE_NAME_START;
-        local_file.file_name = String::from_utf8_lossy(
-            &file_buf[current_offset..current_offset + file_name_length as usize],
-        )
-        .into_owned();
-        current_offset += file_name_length as usize;
-
-        if extra_field_length > 0 {
-            local_file.extra_bytes = Some(
-                file_buf[current_offset..current_offset + extra_field_length as usize].to_vec(),
-            );
-        }
+        Ok(LFH_FILE_NAME_START + file_name_length as usize + extra_field_length as usize)
+    }
 
-        Ok(local_file)
+    pub fn parse(file_buf: &[u8]) -> Result<Self> {
+        LocalFileRef::parse(file_buf).map(Into::into)
     }
 }
 
 #[cfg(test)]
 mod tests {
+    use std::borrow::Cow;
+
     use crate::*;
 
     #[test]
@@ -479,6 +450,7 @@ mod tests {
         ];
 
         let dir_end = CentralDirectoryEnd::parse(&valid_input).unwrap();
+        assert_eq!(dir_end.to_bytes().unwrap(), valid_input);
 
         assert_eq!(dir_end.central_directory_offset, 65312933);
         assert_eq!(dir_end.comment, None);
@@ -497,6 +469,7 @@ mod tests {
             0x00, 0x00, 0xA5, 0x98, 0xE4, 0x03, 0x03, 0x00, 0x41, 0x42, 0x43,
         ];
         let dir_end = CentralDirectoryEnd::parse(&valid_input).unwrap();
+        assert_eq!(dir_end.to_bytes().unwrap(), valid_input);
 
         assert_eq!(dir_end.central_directory_offset, 65312933);
         assert_eq!(dir_end.comment, Some("ABC".into()));
@@ -537,7 +510,12 @@ mod tests {
             0x81, 0x00, 0x00, 0x00, 0x00, 0x4A, 0x43, 0x35, 0x4D, 0x37, 0x53, 0x4D, 0x56, 0x42,
             0x4B,
         ];
+        assert_eq!(
+            CentralDirectoryRecord::record_length(&input[1..CDR_MIN_SIZE + 1]),
+            Ok(0x38)
+        );
         let record = CentralDirectoryRecord::parse(&input[1..], false).unwrap();
+        assert_eq!(record.to_bytes().unwrap(), input[1..]);
 
         assert_eq!(record.zip_version_created, 788);
         assert_eq!(record.zip_version_required, 20);
@@ -560,6 +538,163 @@ mod tests {
         assert_eq!(record.comment, None);
     }
 
+    #[test]
+    fn test_cdr_ref_parse() {
+        let record: [u8; 0x38] = [
+            0x50, 0x4B, 0x01, 0x02, 0x14, 0x03, 0x14, 0x00, 0x00, 0x00, 0x08, 0x00, 0x44, 0x20,
+            0x65, 0x59, 0x41, 0x83, 0x0E, 0x26, 0x72, 0x01, 0x00, 0x00, 0x1E, 0x02, 0x00, 0x00,
+            0x0A, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0xA4, 0x81,
+            0x00, 0x00, 0x00, 0x00, 0x4A, 0x43, 0x35, 0x4D, 0x37, 0x53, 0x4D, 0x56, 0x42, 0x4B,
+        ];
+        let directory = [record, record].concat();
+
+        let borrowed = CentralDirectoryRecordRef::parse(&record, false).unwrap();
+        assert_eq!(borrowed.compressed_size(), 370);
+        assert!(matches!(borrowed.file_name(), Cow::Borrowed("JC5M7SMVBK")));
+        assert_eq!(borrowed.extra_bytes(), None);
+        assert_eq!(
+            borrowed.into_owned(),
+            CentralDirectoryRecord::parse(&record, false).unwrap()
+        );
+
+        let records: Vec<_> = CentralDirectoryRecordRef::iter_many(&directory)
+            .collect::<Result<_>>()
+            .unwrap();
+        assert_eq!(records, vec![borrowed, borrowed]);
+        assert_eq!(
+            CentralDirectoryRecordRef::iter_many(&directory[..0x40])
+                .map(|x| x.map(|x| x.file_header_offset()))
+                .collect::<Vec<_>>(),
+            vec![
+                Ok(0),
+                Err(ZipError::MissingData("CDR (Initial Length Check)"))
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parse_directory() {
+        let record: [u8; 0x38] = [
+            0x50, 0x4B, 0x01, 0x02, 0x14, 0x03, 0x14, 0x00, 0x00, 0x00, 0x08, 0x00, 0x44, 0x20,
+            0x65, 0x59, 0x41, 0x83, 0x0E, 0x26, 0x72, 0x01, 0x00, 0x00, 0x1E, 0x02, 0x00, 0x00,
+            0x0A, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0xA4, 0x81,
+            0x00, 0x00, 0x00, 0x00, 0x4A, 0x43, 0x35, 0x4D, 0x37, 0x53, 0x4D, 0x56, 0x42, 0x4B,
+        ];
+        let directory = [record, record].concat();
+        let end = |record_count: u16, directory_size: usize| CentralDirectoryEnd {
+            disk_number: 0,
+            disk_start: 0,
+            record_count_disk: record_count,
+            record_count_total: record_count,
+            directory_size: directory_size as u32,
+            central_directory_offset: 0,
+            comment_length: 0,
+            comment: None,
+        };
+
+        assert_eq!(
+            CentralDirectoryRecord::parse_directory(&directory, &end(2, 0x70))
+                .unwrap()
+                .len(),
+            2
+        );
+        assert_eq!(
+            CentralDirectoryRecord::parse_directory(&[], &end(0, 0)),
+            Ok(Vec::new())
+        );
+        assert_eq!(
+            CentralDirectoryRecord::parse_directory(&directory, &end(0xFFFF, 0x70))
+                .unwrap()
+                .len(),
+            2
+        );
+        assert_eq!(
+            CentralDirectoryRecord::parse_directory(&directory, &end(2, 0x80)),
+            Err(ZipError::DirectorySizeMismatch(0x80, 0x70))
+        );
+        assert_eq!(
+            CentralDirectoryRecord::parse_directory(&directory, &end(3, 0x70)),
+            Err(ZipError::RecordCountMismatch(3, 2))
+        );
+        assert_eq!(
+            CentralDirectoryRecord::parse_directory(&directory, &end(1, 0x70)),
+            Err(ZipError::RecordCountMismatch(1, 2))
+        );
+        assert_eq!(
+            CentralDirectoryRecord::parse_directory(&directory[..0x40], &end(2, 0x40)),
+            Err(ZipError::MissingData("CDR (Initial Length Check)"))
+        );
+
+        let padded = [&record[..], &[0; 8]].concat();
+        assert_eq!(
+            CentralDirectoryRecord::parse_directory(&padded, &end(1, 0x40)),
+            Err(ZipError::ExtraneousData("Central Directory"))
+        );
+
+        // Fewer records than declared followed by padding, shorter and longer than a record's fixed part
+        assert_eq!(
+            CentralDirectoryRecord::parse_directory(&padded, &end(2, 0x40)),
+            Err(ZipError::ExtraneousData("Central Directory"))
+        );
+        let padded = [&record[..], &[0; 0x38]].concat();
+        assert_eq!(
+            CentralDirectoryRecord::parse_directory(&padded, &end(2, 0x70)),
+            Err(ZipError::ExtraneousData("Central Directory"))
+        );
+    }
+
+    #[test]
+    fn test_parse_many_empty() {
+        assert_eq!(CentralDirectoryRecord::parse_many(&[]), Ok(Vec::new()));
+        assert_eq!(
+            CentralDirectoryRecord::parse_many(&[0x50]),
+            Err(ZipError::MissingData("CDR (Initial Length Check)"))
+        );
+    }
+
+    /// Runs the checked-in fuzzing corpus through every parser entry point, none of which may panic.
+    #[test]
+    fn fuzz_corpus_regressions() {
+        let corpus = std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("fuzz/corpus");
+        let mut parsed_directories = 0;
+        for target in std::fs::read_dir(corpus).unwrap() {
+            for input in std::fs::read_dir(target.unwrap().path()).unwrap() {
+                let data = std::fs::read(input.unwrap().path()).unwrap();
+                let _ = CentralDirectoryEnd::parse(&data);
+                if let Ok(end) = CentralDirectoryEnd::find_and_parse(&data) {
+                    let start = end.central_directory_offset as usize;
+                    if let Some(directory) = start
+                        .checked_add(end.directory_size as usize)
+                        .and_then(|stop| data.get(start..stop))
+                    {
+                        if CentralDirectoryRecord::parse_directory(directory, &end).is_ok() {
+                            parsed_directories += 1;
+                        }
+
+                        let mut found = 0;
+                        let mut tail = directory;
+                        while let Ok(record) = CentralDirectoryRecordRef::parse(tail, true) {
+                            tail = &tail[record.as_bytes().len()..];
+                            found += 1;
+                        }
+                        let _ = end.directory_tail_error(
+                            found,
+                            tail,
+                            ZipError::MissingData("Central Directory"),
+                        );
+                    }
+                }
+                let _ = CentralDirectoryRecord::record_length(&data);
+                let _ = CentralDirectoryRecord::parse(&data, false);
+                let _ = CentralDirectoryRecord::parse(&data, true);
+                let _ = CentralDirectoryRecord::parse_many(&data);
+                let _ = LocalFile::header_length(&data);
+                let _ = LocalFile::parse(&data);
+            }
+        }
+        assert!(parsed_directories > 0);
+    }
+
     #[test]
     fn test_lfh_parse() {
         let input: [u8; 0x48] = [
@@ -572,6 +707,7 @@ mod tests {
         ];
 
         let local_file = LocalFile::parse(&input[..]).unwrap();
+        assert_eq!(local_file.to_bytes().unwrap(), input[..0x42]);
 
         assert_eq!(local_file.zip_version, 10);
         assert_eq!(local_file.gp_bit_flag, 0);
@@ -584,5 +720,15 @@ mod tests {
         assert_eq!(local_file.file_name, "test.txt");
         assert_eq!(local_file.file_name_length, 8);
         assert_eq!(local_file.extra_field_length, 28);
+        assert_eq!(LocalFile::header_length(&input[..]).unwrap(), 0x42);
+        assert_eq!(
+            LocalFile::parse(&input[..0x41]),
+            Err(ZipError::MissingData("Local File Variable Length Fields"))
+        );
+
+        let borrowed = LocalFileRef::parse(&input[..]).unwrap();
+        assert_eq!(borrowed.as_bytes().len(), 0x42);
+        assert_eq!(borrowed.extra_bytes(), Some(&input[0x26..0x42]));
+        assert_eq!(borrowed.into_owned(), local_file);
     }
 }
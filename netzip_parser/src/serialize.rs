@@ -0,0 +1,129 @@
+use std::io::{Error, ErrorKind, Write};
+
+use crate::*;
+
+/// Returns the length of a variable length field, which has to fit into its 16 bit length field.
+fn field_length(field: &[u8], name: &str) -> std::io::Result<u16> {
+    u16::try_from(field.len()).map_err(|_| {
+        Error::new(
+            ErrorKind::InvalidInput,
+            format!("{name} is longer than 65535 bytes"),
+        )
+    })
+}
+
+fn vec_writer(write: impl FnOnce(&mut Vec<u8>) -> std::io::Result<()>) -> std::io::Result<Vec<u8>> {
+    let mut out = Vec::new();
+    write(&mut out)?;
+    Ok(out)
+}
+
+impl CentralDirectoryEnd {
+    /// Writes the record, taking the comment length from the comment rather than `comment_length`.
+    ///
+    /// # Arguments
+    ///
+    /// * `writer` - The writer to serialize the record to
+    ///
+    /// # Returns
+    ///
+    /// A Result indicating whether the record was written successfully
+    pub fn write_to<W: Write>(&self, mut writer: W) -> std::io::Result<()> {
+        let comment = self.comment.as_deref().unwrap_or_default().as_bytes();
+
+        writer.write_all(&MAGIC_CENTRAL_DIRECTORY_END)?;
+        writer.write_all(&self.disk_number.to_le_bytes())?;
+        writer.write_all(&self.disk_start.to_le_bytes())?;
+        writer.write_all(&self.record_count_disk.to_le_bytes())?;
+        writer.write_all(&self.record_count_total.to_le_bytes())?;
+        writer.write_all(&self.directory_size.to_le_bytes())?;
+        writer.write_all(&self.central_directory_offset.to_le_bytes())?;
+        writer.write_all(&field_length(comment, "Archive comment")?.to_le_bytes())?;
+        writer.write_all(comment)
+    }
+
+    /// Serializes the record into a new buffer, see [CentralDirectoryEnd::write_to].
+    pub fn to_bytes(&self) -> std::io::Result<Vec<u8>> {
+        vec_writer(|out| self.write_to(out))
+    }
+}
+
+impl CentralDirectoryRecord {
+    /// Writes the record, taking the lengths of the file name, extra field and comment from their contents
+    /// rather than the length fields.
+    ///
+    /// # Arguments
+    ///
+    /// * `writer` - The writer to serialize the record to
+    ///
+    /// # Returns
+    ///
+    /// A Result indicating whether the record was written successfully
+    pub fn write_to<W: Write>(&self, mut writer: W) -> std::io::Result<()> {
+        let file_name = self.file_name.as_bytes();
+        let extra_bytes = self.extra_bytes.as_deref().unwrap_or_default();
+        let comment = self.comment.as_deref().unwrap_or_default().as_bytes();
+
+        writer.write_all(&MAGIC_CENTRAL_DIRECTORY_RECORD)?;
+        writer.write_all(&self.zip_version_created.to_le_bytes())?;
+        writer.write_all(&self.zip_version_required.to_le_bytes())?;
+        writer.write_all(&self.gp_bit_flag.to_le_bytes())?;
+        writer.write_all(&self.compression_method.id().to_le_bytes())?;
+        writer.write_all(&self.last_modification_time.to_le_bytes())?;
+        writer.write_all(&self.last_modification_date.to_le_bytes())?;
+        writer.write_all(&self.crc32.to_le_bytes())?;
+        writer.write_all(&self.compressed_size.to_le_bytes())?;
+        writer.write_all(&self.uncompressed_size.to_le_bytes())?;
+        writer.write_all(&field_length(file_name, "File name")?.to_le_bytes())?;
+        writer.write_all(&field_length(extra_bytes, "Extra field")?.to_le_bytes())?;
+        writer.write_all(&field_length(comment, "File comment")?.to_le_bytes())?;
+        writer.write_all(&self.disk_number.to_le_bytes())?;
+        writer.write_all(&self.file_attributes_internal.to_le_bytes())?;
+        writer.write_all(&self.file_attributes_external.to_le_bytes())?;
+        writer.write_all(&self.file_header_offset.to_le_bytes())?;
+        writer.write_all(file_name)?;
+        writer.write_all(extra_bytes)?;
+        writer.write_all(comment)
+    }
+
+    /// Serializes the record into a new buffer, see [CentralDirectoryRecord::write_to].
+    pub fn to_bytes(&self) -> std::io::Result<Vec<u8>> {
+        vec_writer(|out| self.write_to(out))
+    }
+}
+
+impl LocalFile {
+    /// Writes the header, taking the lengths of the file name and extra field from their contents rather
+    /// than the length fields.
+    ///
+    /// # Arguments
+    ///
+    /// * `writer` - The writer to serialize the header to
+    ///
+    /// # Returns
+    ///
+    /// A Result indicating whether the header was written successfully
+    pub fn write_to<W: Write>(&self, mut writer: W) -> std::io::Result<()> {
+        let file_name = self.file_name.as_bytes();
+        let extra_bytes = self.extra_bytes.as_deref().unwrap_or_default();
+
+        writer.write_all(&MAGIC_LOCAL_FILE)?;
+        writer.write_all(&self.zip_version.to_le_bytes())?;
+        writer.write_all(&self.gp_bit_flag.to_le_bytes())?;
+        writer.write_all(&self.compression_method.id().to_le_bytes())?;
+        writer.write_all(&self.last_modification_time.to_le_bytes())?;
+        writer.write_all(&self.last_modification_date.to_le_bytes())?;
+        writer.write_all(&self.crc32.to_le_bytes())?;
+        writer.write_all(&self.compressed_size.to_le_bytes())?;
+        writer.write_all(&self.uncompressed_size.to_le_bytes())?;
+        writer.write_all(&field_length(file_name, "File name")?.to_le_bytes())?;
+        writer.write_all(&field_length(extra_bytes, "Extra field")?.to_le_bytes())?;
+        writer.write_all(file_name)?;
+        writer.write_all(extra_bytes)
+    }
+
+    /// Serializes the header into a new buffer, see [LocalFile::write_to].
+    pub fn to_bytes(&self) -> std::io::Result<Vec<u8>> {
+        vec_writer(|out| self.write_to(out))
+    }
+}
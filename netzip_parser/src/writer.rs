@@ -0,0 +1,869 @@
+use std::io::{Error, ErrorKind, Write};
+
+use flate2::{Compression, Crc, write::DeflateEncoder};
+
+use crate::*;
+
+const MAGIC_DATA_DESCRIPTOR: [u8; 4] = [0x50, 0x4B, 0x07, 0x08];
+
+/// The entry is encrypted.
+const FLAG_ENCRYPTED: u16 = 1;
+/// Sizes and CRC-32 follow the data in a data descriptor instead of being stored in the local header.
+const FLAG_DATA_DESCRIPTOR: u16 = 1 << 3;
+/// File name and comment are encoded as UTF-8.
+const FLAG_UTF8: u16 = 1 << 11;
+
+const VERSION_DEFAULT: u16 = 20;
+const VERSION_DEFLATE64: u16 = 21;
+const VERSION_ZIP64: u16 = 45;
+const VERSION_BZIP2: u16 = 46;
+const VERSION_LZMA: u16 = 63;
+const METHOD_BZIP2: u16 = 12;
+const METHOD_LZMA: u16 = 14;
+/// Upper byte of the creating version, telling readers that external attributes hold UNIX permissions.
+const HOST_UNIX: u16 = 3 << 8;
+
+const DEFAULT_FILE_MODE: u32 = 0o100644;
+const DEFAULT_DIRECTORY_MODE: u32 = 0o040755;
+/// MS-DOS directory attribute, set alongside the UNIX mode for readers that ignore the host.
+const DOS_DIRECTORY_ATTRIBUTE: u32 = 0x10;
+
+/// 1980-01-01, the earliest date representable in an MS-DOS timestamp.
+const DEFAULT_MODIFICATION_DATE: u16 = (1 << 5) | 1;
+
+/// Options for an entry written by a [ZipWriter].
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct EntryOptions {
+    /// How the entry is compressed. Only Stored and Deflate are supported for data compressed by the writer,
+    /// raw entries may use any method.
+    pub compression_method: CompressionMethod,
+    /// The Deflate compression level from 0 to 9
+    pub compression_level: u32,
+    /// Modification time in MS-DOS format
+    pub last_modification_time: u16,
+    /// Modification date in MS-DOS format
+    pub last_modification_date: u16,
+    /// Version of the ZIP specification and host system that created the entry
+    pub zip_version_created: u16,
+    /// General purpose flags to set in addition to those the writer sets itself, like the Deflate level hints
+    pub gp_bit_flag: u16,
+    /// Host system specific attributes, UNIX permissions matching the entry type if not set
+    pub file_attributes_external: Option<u32>,
+    pub comment: Option<String>,
+    /// Whether the contents may reach 4 GiB, reserving room for 64 bit sizes in the local header of an entry
+    /// compressed by the writer. Finishing such an entry fails if it reaches 4 GiB without this.
+    pub large_file: bool,
+}
+
+impl Default for EntryOptions {
+    fn default() -> Self {
+        Self {
+            compression_method: CompressionMethod::Deflate,
+            compression_level: 6,
+            last_modification_time: 0,
+            last_modification_date: DEFAULT_MODIFICATION_DATE,
+            zip_version_created: HOST_UNIX | VERSION_ZIP64,
+            gp_bit_flag: 0,
+            file_attributes_external: None,
+            comment: None,
+            large_file: false,
+        }
+    }
+}
+
+impl EntryOptions {
+    /// Creates options reproducing an entry of another archive, for copying it with
+    /// [ZipWriter::start_raw_entry].
+    ///
+    /// # Arguments
+    ///
+    /// * `record` - The central directory record of the entry to copy
+    ///
+    /// # Returns
+    ///
+    /// The EntryOptions
+    pub fn from_record(record: &CentralDirectoryRecord) -> Self {
+        // Traditional PKWARE encryption checks passwords against the high byte of the CRC-32, or of the
+        // modification time if a data descriptor is used, so encrypted entries have to keep the flag
+        let kept_flags = if record.gp_bit_flag & FLAG_ENCRYPTED != 0 {
+            !FLAG_UTF8
+        } else {
+            !(FLAG_DATA_DESCRIPTOR | FLAG_UTF8)
+        };
+
+        Self {
+            compression_method: record.compression_method.clone(),
+            compression_level: Self::default().compression_level,
+            last_modification_time: record.last_modification_time,
+            last_modification_date: record.last_modification_date,
+            zip_version_created: record.zip_version_created,
+            gp_bit_flag: record.gp_bit_flag & kept_flags,
+            file_attributes_external: Some(record.file_attributes_external),
+            comment: record.comment.clone(),
+            large_file: false,
+        }
+    }
+}
+
+/// Writes a ZIP archive to any writer, without seeking.
+///
+/// Entries are started with [ZipWriter::start_entry] and their contents written through the [Write]
+/// implementation, compressing them on the fly. As sizes and CRC-32 are only known after the contents, they
+/// follow each such entry in a data descriptor, while raw entries store them in their local header. ZIP64 structures are used for entries, offsets and record counts
+/// exceeding the limits of the regular ones.
+///
+/// [ZipWriter::finish] must be called to write the central directory, dropping the writer leaves an
+/// incomplete archive.
+#[derive(Debug)]
+pub struct ZipWriter<W: Write> {
+    inner: W,
+    offset: u64,
+    records: Vec<CentralDirectoryRecord>,
+    current: Option<OpenEntry>,
+    comment: Option<String>,
+}
+
+#[derive(Debug)]
+struct OpenEntry {
+    record: CentralDirectoryRecord,
+    header_offset: u64,
+    /// Whether CRC-32 and sizes follow the data instead of being stored in the local header
+    descriptor: bool,
+    /// Whether the local header has a ZIP64 extra field, allowing sizes of 4 GiB and more
+    zip64_sizes: bool,
+    /// Compresses the data, unless it is stored or already compressed
+    encoder: Option<DeflateEncoder<Vec<u8>>>,
+    /// Checksum of the uncompressed data, unless it was provided along with raw data
+    crc: Option<Crc>,
+    /// Size of the raw data, which was stored in the local header already
+    declared_compressed_size: Option<u64>,
+    crc32: u32,
+    compressed_size: u64,
+    uncompressed_size: u64,
+}
+
+impl<W: Write> ZipWriter<W> {
+    /// Creates a writer producing an archive.
+    ///
+    /// # Arguments
+    ///
+    /// * `inner` - The writer receiving the archive
+    ///
+    /// # Returns
+    ///
+    /// The ZipWriter
+    pub fn new(inner: W) -> Self {
+        Self {
+            inner,
+            offset: 0,
+            records: Vec::new(),
+            current: None,
+            comment: None,
+        }
+    }
+
+    /// Sets the archive comment stored in the end of central directory record.
+    pub fn set_comment(&mut self, comment: impl Into<String>) {
+        self.comment = Some(comment.into());
+    }
+
+    /// Returns a mutable reference to the inner writer, for example to take the output written to a buffer.
+    /// Writing to it directly corrupts the archive.
+    pub fn get_mut(&mut self) -> &mut W {
+        &mut self.inner
+    }
+
+    /// Returns the central directory records of the entries finished so far.
+    pub fn records(&self) -> &[CentralDirectoryRecord] {
+        &self.records
+    }
+
+    /// Finishes the current entry and starts a new one, compressing everything written to the ZipWriter until
+    /// the next entry is started. Directories are entries whose name ends with a slash and that have no
+    /// contents.
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - The path of the entry within the archive, using slashes as separators
+    /// * `options` - How to compress the entry and its metadata
+    ///
+    /// # Returns
+    ///
+    /// A Result indicating whether the entry was started successfully
+    pub fn start_entry(&mut self, name: &str, options: EntryOptions) -> std::io::Result<()> {
+        let encoder = match options.compression_method {
+            CompressionMethod::Stored => None,
+            CompressionMethod::Deflate => Some(DeflateEncoder::new(
+                Vec::new(),
+                Compression::new(options.compression_level),
+            )),
+            _ => {
+                return Err(Error::new(
+                    ErrorKind::InvalidInput,
+                    "Only Stored and Deflate entries can be compressed by the writer",
+                ));
+            }
+        };
+
+        self.open_entry(name, options, encoder, None)
+    }
+
+    /// Finishes the current entry and starts a new one whose contents are already compressed, like an entry
+    /// copied from another archive. Everything written to the ZipWriter until the next entry is started is
+    /// stored as is, and must amount to the given compressed size. As CRC-32 and sizes are known up front,
+    /// they are stored in the local header and no data descriptor is written, unless the options ask for one.
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - The path of the entry within the archive, using slashes as separators
+    /// * `options` - The compression method of the data and the entry's metadata
+    /// * `crc32` - The CRC-32 of the uncompressed contents
+    /// * `compressed_size` - The size of the compressed contents
+    /// * `uncompressed_size` - The size of the uncompressed contents
+    ///
+    /// # Returns
+    ///
+    /// A Result indicating whether the entry was started successfully
+    pub fn start_raw_entry(
+        &mut self,
+        name: &str,
+        options: EntryOptions,
+        crc32: u32,
+        compressed_size: u64,
+        uncompressed_size: u64,
+    ) -> std::io::Result<()> {
+        self.open_entry(
+            name,
+            options,
+            None,
+            Some((crc32, compressed_size, uncompressed_size)),
+        )
+    }
+
+    /// Finishes the last entry and writes the central directory.
+    ///
+    /// # Returns
+    ///
+    /// A Result containing either the inner writer or an Error if writing failed
+    pub fn finish(mut self) -> std::io::Result<W> {
+        self.finish_entry()?;
+
+        let directory_offset = self.offset;
+        for record in &self.records {
+            let bytes = record.to_bytes()?;
+            self.inner.write_all(&bytes)?;
+            self.offset += bytes.len() as u64;
+        }
+        let directory_size = self.offset - directory_offset;
+        let record_count = self.records.len() as u64;
+
+        let zip64 = record_count >= ZIP64_PLACEHOLDER_U16 as u64
+            || directory_size >= ZIP64_PLACEHOLDER_U32 as u64
+            || directory_offset >= ZIP64_PLACEHOLDER_U32 as u64;
+        if zip64 {
+            Zip64CentralDirectoryEnd {
+                zip_version_created: HOST_UNIX | VERSION_ZIP64,
+                zip_version_required: VERSION_ZIP64,
+                disk_number: 0,
+                disk_start: 0,
+                record_count_disk: record_count,
+                record_count_total: record_count,
+                directory_size,
+                central_directory_offset: directory_offset,
+                extensible_data: None,
+            }
+            .write_to(&mut self.inner)?;
+            Zip64CentralDirectoryEndLocator {
+                disk_number: 0,
+                end_offset: self.offset,
+                disk_count: 1,
+            }
+            .write_to(&mut self.inner)?;
+        }
+
+        let record_count = record_count.min(ZIP64_PLACEHOLDER_U16 as u64) as u16;
+        CentralDirectoryEnd {
+            disk_number: 0,
+            disk_start: 0,
+            record_count_disk: record_count,
+            record_count_total: record_count,
+            directory_size: directory_size.min(ZIP64_PLACEHOLDER_U32 as u64) as u32,
+            central_directory_offset: directory_offset.min(ZIP64_PLACEHOLDER_U32 as u64) as u32,
+            comment_length: 0,
+            comment: self.comment.take(),
+        }
+        .write_to(&mut self.inner)?;
+
+        self.inner.flush()?;
+        Ok(self.inner)
+    }
+
+    fn open_entry(
+        &mut self,
+        name: &str,
+        options: EntryOptions,
+        encoder: Option<DeflateEncoder<Vec<u8>>>,
+        raw: Option<(u32, u64, u64)>,
+    ) -> std::io::Result<()> {
+        self.finish_entry()?;
+
+        let utf8 = !name.is_ascii() || options.comment.as_ref().is_some_and(|x| !x.is_ascii());
+        let descriptor = raw.is_none() || options.gp_bit_flag & FLAG_DATA_DESCRIPTOR != 0;
+        let gp_bit_flag = options.gp_bit_flag
+            | if descriptor { FLAG_DATA_DESCRIPTOR } else { 0 }
+            | if utf8 { FLAG_UTF8 } else { 0 };
+        let file_attributes_external =
+            options
+                .file_attributes_external
+                .unwrap_or(if name.ends_with('/') {
+                    (DEFAULT_DIRECTORY_MODE << 16) | DOS_DIRECTORY_ATTRIBUTE
+                } else {
+                    DEFAULT_FILE_MODE << 16
+                });
+
+        // Sizes of entries compressed by the writer are only known once they are finished, so their local
+        // header only reserves a ZIP64 extra field if the options announce a large file
+        let (crc32, compressed_size, uncompressed_size) = raw.unwrap_or_default();
+        let zip64_sizes = options.large_file
+            || compressed_size >= ZIP64_PLACEHOLDER_U32 as u64
+            || uncompressed_size >= ZIP64_PLACEHOLDER_U32 as u64;
+        let zip_version = version_needed(
+            &options.compression_method,
+            zip64_sizes || self.offset >= ZIP64_PLACEHOLDER_U32 as u64,
+        );
+
+        let mut local_file = LocalFile {
+            zip_version,
+            gp_bit_flag,
+            compression_method: options.compression_method.clone(),
+            last_modification_time: options.last_modification_time,
+            last_modification_date: options.last_modification_date,
+            crc32: 0,
+            compressed_size: 0,
+            uncompressed_size: 0,
+            file_name_length: 0,
+            file_name: name.into(),
+            extra_field_length: 0,
+            extra_bytes: None,
+        };
+        if !descriptor {
+            local_file.crc32 = crc32;
+        }
+        if zip64_sizes {
+            // A ZIP64 extra field in a local header holds both sizes, left zero if they follow in the
+            // data descriptor, which then holds 64 bit sizes as well
+            let (compressed_size, uncompressed_size) = if descriptor {
+                (0, 0)
+            } else {
+                (compressed_size, uncompressed_size)
+            };
+            local_file.compressed_size = ZIP64_PLACEHOLDER_U32;
+            local_file.uncompressed_size = ZIP64_PLACEHOLDER_U32;
+            local_file.extra_bytes = Some(
+                Zip64ExtraField {
+                    uncompressed_size: Some(uncompressed_size),
+                    compressed_size: Some(compressed_size),
+                    ..Zip64ExtraField::default()
+                }
+                .to_bytes(),
+            );
+        } else if !descriptor {
+            local_file.compressed_size = compressed_size as u32;
+            local_file.uncompressed_size = uncompressed_size as u32;
+        }
+        let header = local_file.to_bytes()?;
+        let header_offset = self.offset;
+        self.inner.write_all(&header)?;
+        self.offset += header.len() as u64;
+
+        self.current = Some(OpenEntry {
+            record: CentralDirectoryRecord {
+                zip_version_created: options.zip_version_created,
+                zip_version_required: zip_version,
+                gp_bit_flag,
+                compression_method: options.compression_method,
+                last_modification_time: options.last_modification_time,
+                last_modification_date: options.last_modification_date,
+                crc32: 0,
+                compressed_size: 0,
+                uncompressed_size: 0,
+                file_name_length: local_file.file_name.len() as u16,
+                extra_field_length: 0,
+                file_comment_length: 0,
+                disk_number: 0,
+                file_attributes_internal: 0,
+                file_attributes_external,
+                file_header_offset: 0,
+                file_name: local_file.file_name,
+                extra_bytes: None,
+                comment: options.comment,
+            },
+            header_offset,
+            descriptor,
+            zip64_sizes,
+            encoder,
+            crc: raw.is_none().then(Crc::new),
+            declared_compressed_size: raw.map(|(_, compressed_size, _)| compressed_size),
+            crc32,
+            compressed_size: 0,
+            uncompressed_size,
+        });
+
+        Ok(())
+    }
+
+    /// Writes the compressed data buffered by the encoder of the current entry.
+    fn drain_encoder(&mut self) -> std::io::Result<()> {
+        let Some(entry) = self.current.as_mut() else {
+            return Ok(());
+        };
+        let Some(encoder) = entry.encoder.as_mut() else {
+            return Ok(());
+        };
+
+        let compressed = std::mem::take(encoder.get_mut());
+        self.inner.write_all(&compressed)?;
+        self.offset += compressed.len() as u64;
+        entry.compressed_size += compressed.len() as u64;
+        Ok(())
+    }
+
+    /// Finishes the current entry, if any, writing its data descriptor if it has one and recording it for the
+    /// directory.
+    /// Starting a new entry or finishing the archive does this implicitly.
+    ///
+    /// # Returns
+    ///
+    /// A Result indicating whether the entry was finished successfully
+    pub fn finish_entry(&mut self) -> std::io::Result<()> {
+        if let Some(encoder) = self.current.as_mut().and_then(|x| x.encoder.as_mut()) {
+            encoder.try_finish()?;
+        }
+        self.drain_encoder()?;
+        let Some(mut entry) = self.current.take() else {
+            return Ok(());
+        };
+
+        if let Some(crc) = &entry.crc {
+            entry.crc32 = crc.sum();
+        }
+        if entry
+            .declared_compressed_size
+            .is_some_and(|x| x != entry.compressed_size)
+        {
+            return Err(Error::new(
+                ErrorKind::InvalidData,
+                "Raw data does not match the declared compressed size",
+            ));
+        }
+
+        if !entry.zip64_sizes
+            && (entry.compressed_size >= ZIP64_PLACEHOLDER_U32 as u64
+                || entry.uncompressed_size >= ZIP64_PLACEHOLDER_U32 as u64)
+        {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                "Entry reached 4 GiB without being started as a large file",
+            ));
+        }
+
+        if entry.descriptor {
+            let mut descriptor = MAGIC_DATA_DESCRIPTOR.to_vec();
+            descriptor.extend_from_slice(&entry.crc32.to_le_bytes());
+            if entry.zip64_sizes {
+                descriptor.extend_from_slice(&entry.compressed_size.to_le_bytes());
+                descriptor.extend_from_slice(&entry.uncompressed_size.to_le_bytes());
+            } else {
+                descriptor.extend_from_slice(&(entry.compressed_size as u32).to_le_bytes());
+                descriptor.extend_from_slice(&(entry.uncompressed_size as u32).to_le_bytes());
+            }
+            self.inner.write_all(&descriptor)?;
+            self.offset += descriptor.len() as u64;
+        }
+
+        let mut zip64 = Zip64ExtraField::default();
+        let mut record = entry.record;
+        record.crc32 = entry.crc32;
+        record.uncompressed_size =
+            zip64_field(entry.uncompressed_size, &mut zip64.uncompressed_size);
+        record.compressed_size = zip64_field(entry.compressed_size, &mut zip64.compressed_size);
+        record.file_header_offset = zip64_field(entry.header_offset, &mut zip64.file_header_offset);
+        if !zip64.is_empty() {
+            let extra_bytes = zip64.to_bytes();
+            record.zip_version_required = version_needed(&record.compression_method, true);
+            record.extra_field_length = extra_bytes.len() as u16;
+            record.extra_bytes = Some(extra_bytes);
+        }
+        record.file_comment_length = record.comment.as_ref().map_or(0, |x| x.len() as u16);
+
+        self.records.push(record);
+        Ok(())
+    }
+}
+
+/// Returns the version needed to extract an entry compressed with the given method, which is at least the
+/// version introducing ZIP64 if the entry uses ZIP64 structures.
+fn version_needed(method: &CompressionMethod, zip64: bool) -> u16 {
+    let version = match method {
+        CompressionMethod::Deflate64 => VERSION_DEFLATE64,
+        CompressionMethod::Unsupported(METHOD_BZIP2) => VERSION_BZIP2,
+        CompressionMethod::Unsupported(METHOD_LZMA) => VERSION_LZMA,
+        _ => VERSION_DEFAULT,
+    };
+    if zip64 {
+        version.max(VERSION_ZIP64)
+    } else {
+        version
+    }
+}
+
+/// Returns the value for a 32 bit field, moving it into the ZIP64 extra field if it does not fit.
+fn zip64_field(value: u64, zip64: &mut Option<u64>) -> u32 {
+    if value >= ZIP64_PLACEHOLDER_U32 as u64 {
+        *zip64 = Some(value);
+        ZIP64_PLACEHOLDER_U32
+    } else {
+        value as u32
+    }
+}
+
+impl<W: Write> Write for ZipWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        let Some(entry) = self.current.as_mut() else {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                "No entry has been started",
+            ));
+        };
+
+        if let Some(crc) = entry.crc.as_mut() {
+            crc.update(buf);
+            entry.uncompressed_size += buf.len() as u64;
+        }
+        match entry.encoder.as_mut() {
+            Some(encoder) => {
+                encoder.write_all(buf)?;
+                self.drain_encoder()?;
+            }
+            None => {
+                self.inner.write_all(buf)?;
+                self.offset += buf.len() as u64;
+                entry.compressed_size += buf.len() as u64;
+            }
+        }
+
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Read;
+
+    use flate2::read::DeflateDecoder;
+
+    use super::*;
+
+    /// Parses an archive written without ZIP64 structures and returns its records with their contents.
+    fn read_archive(archive: &[u8]) -> Vec<(CentralDirectoryRecord, Vec<u8>)> {
+        let end = CentralDirectoryEnd::find_and_parse(archive).unwrap();
+        let start = end.central_directory_offset as usize;
+        let directory = &archive[start..start + end.directory_size as usize];
+
+        CentralDirectoryRecord::parse_directory(directory, &end)
+            .unwrap()
+            .into_iter()
+            .map(|record| {
+                let header = &archive[record.file_header_offset as usize..];
+                let local_file = LocalFile::parse(header).unwrap();
+                assert_eq!(local_file.file_name, record.file_name);
+
+                let data_start = LocalFile::header_length(header).unwrap();
+                let data = &header[data_start..data_start + record.compressed_size as usize];
+                if record.gp_bit_flag & FLAG_DATA_DESCRIPTOR != 0 {
+                    let descriptor = &header[data_start + data.len()..][..16];
+                    assert_eq!(descriptor[..4], MAGIC_DATA_DESCRIPTOR);
+                    assert_eq!(descriptor[4..8], record.crc32.to_le_bytes());
+                } else {
+                    assert_eq!(local_file.crc32, record.crc32);
+                    assert_eq!(local_file.compressed_size, record.compressed_size);
+                    assert_eq!(local_file.uncompressed_size, record.uncompressed_size);
+                }
+
+                let contents = match record.compression_method {
+                    CompressionMethod::Stored => data.to_vec(),
+                    _ => {
+                        let mut contents = Vec::new();
+                        DeflateDecoder::new(data)
+                            .read_to_end(&mut contents)
+                            .unwrap();
+                        contents
+                    }
+                };
+                let mut crc = Crc::new();
+                crc.update(&contents);
+                assert_eq!(crc.sum(), record.crc32);
+                assert_eq!(contents.len(), record.uncompressed_size as usize);
+
+                (record, contents)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn round_trips_entries() {
+        let text = "netzip ".repeat(1000);
+        let mut precompressed = DeflateEncoder::new(Vec::new(), Compression::best());
+        precompressed.write_all(text.as_bytes()).unwrap();
+        let precompressed = precompressed.finish().unwrap();
+        let mut crc = Crc::new();
+        crc.update(text.as_bytes());
+
+        let mut writer = ZipWriter::new(Vec::new());
+        writer.set_comment("archive comment");
+        let stored = EntryOptions {
+            compression_method: CompressionMethod::Stored,
+            ..EntryOptions::default()
+        };
+        writer.start_entry("stored.txt", stored.clone()).unwrap();
+        writer.write_all(b"stored contents").unwrap();
+        writer.start_entry("dir/", stored).unwrap();
+        writer
+            .start_entry(
+                "dir/deflated.txt",
+                EntryOptions {
+                    comment: Some("ünïcode".into()),
+                    ..EntryOptions::default()
+                },
+            )
+            .unwrap();
+        for chunk in text.as_bytes().chunks(100) {
+            writer.write_all(chunk).unwrap();
+        }
+        writer
+            .start_raw_entry(
+                "raw.txt",
+                EntryOptions::default(),
+                crc.sum(),
+                precompressed.len() as u64,
+                text.len() as u64,
+            )
+            .unwrap();
+        writer.write_all(&precompressed).unwrap();
+        let archive = writer.finish().unwrap();
+
+        let entries = read_archive(&archive);
+        let names: Vec<_> = entries.iter().map(|x| x.0.file_name.as_str()).collect();
+        assert_eq!(names, ["stored.txt", "dir/", "dir/deflated.txt", "raw.txt"]);
+        assert_eq!(entries[0].1, b"stored contents");
+        assert!(entries[1].0.is_directory());
+        assert_eq!(entries[1].0.file_attributes_external & 0x10, 0x10);
+        assert_eq!(entries[2].1, text.as_bytes());
+        assert!(entries[2].0.compressed_size < 100);
+        assert_eq!(entries[2].0.gp_bit_flag, FLAG_DATA_DESCRIPTOR | FLAG_UTF8);
+        assert_eq!(entries[2].0.comment.as_deref(), Some("ünïcode"));
+        assert_eq!(entries[3].0.compressed_size as usize, precompressed.len());
+        assert_eq!(entries[3].1, text.as_bytes());
+        assert_eq!(entries[3].0.gp_bit_flag & FLAG_DATA_DESCRIPTOR, 0);
+        assert_eq!(
+            CentralDirectoryEnd::find_and_parse(&archive)
+                .unwrap()
+                .comment
+                .as_deref(),
+            Some("archive comment")
+        );
+    }
+
+    #[test]
+    fn keeps_data_descriptor_of_encrypted_entries() {
+        let mut writer = ZipWriter::new(Vec::new());
+        writer
+            .start_entry("plain.txt", EntryOptions::default())
+            .unwrap();
+        writer.write_all(b"contents").unwrap();
+        writer.finish_entry().unwrap();
+        let plain = writer.records()[0].clone();
+        let encrypted = CentralDirectoryRecord {
+            gp_bit_flag: plain.gp_bit_flag | FLAG_ENCRYPTED,
+            ..plain.clone()
+        };
+        assert_eq!(EntryOptions::from_record(&plain).gp_bit_flag, 0);
+        assert_eq!(
+            EntryOptions::from_record(&encrypted).gp_bit_flag,
+            FLAG_ENCRYPTED | FLAG_DATA_DESCRIPTOR
+        );
+
+        let mut writer = ZipWriter::new(Vec::new());
+        writer
+            .start_raw_entry(
+                "secret.txt",
+                EntryOptions::from_record(&encrypted),
+                encrypted.crc32,
+                4,
+                8,
+            )
+            .unwrap();
+        writer.write_all(b"data").unwrap();
+        writer.finish_entry().unwrap();
+        let record = &writer.records()[0];
+        assert_eq!(record.gp_bit_flag, FLAG_ENCRYPTED | FLAG_DATA_DESCRIPTOR);
+        let archive = writer.get_mut();
+        let data_start = LocalFile::header_length(archive).unwrap();
+        assert_eq!(archive[data_start + 4..][..4], MAGIC_DATA_DESCRIPTOR);
+
+        writer
+            .start_raw_entry("short.txt", EntryOptions::default(), 0, 5, 5)
+            .unwrap();
+        writer.write_all(b"data").unwrap();
+        assert_eq!(
+            writer.finish_entry().unwrap_err().kind(),
+            ErrorKind::InvalidData
+        );
+    }
+
+    #[test]
+    fn writes_zip64_end_for_many_entries() {
+        let count = ZIP64_PLACEHOLDER_U16 as usize + 10;
+        let stored = EntryOptions {
+            compression_method: CompressionMethod::Stored,
+            ..EntryOptions::default()
+        };
+        let mut writer = ZipWriter::new(Vec::new());
+        for i in 0..count {
+            writer.start_entry(&format!("{i}"), stored.clone()).unwrap();
+        }
+        let archive = writer.finish().unwrap();
+
+        let end_start = archive.len() - EOCD_MIN_SIZE;
+        let end = CentralDirectoryEnd::parse(&archive[end_start..]).unwrap();
+        assert_eq!(end.record_count_total, ZIP64_PLACEHOLDER_U16);
+
+        let locator = Zip64CentralDirectoryEndLocator::parse(
+            &archive[end_start - ZIP64_EOCD_LOCATOR_SIZE..end_start],
+        )
+        .unwrap();
+        let zip64_end = Zip64CentralDirectoryEnd::parse(
+            &archive[locator.end_offset as usize..end_start - ZIP64_EOCD_LOCATOR_SIZE],
+        )
+        .unwrap();
+        assert_eq!(zip64_end.record_count_total, count as u64);
+        assert_eq!(
+            zip64_end.central_directory_offset,
+            end.central_directory_offset as u64
+        );
+
+        let start = end.central_directory_offset as usize;
+        let directory = &archive[start..start + end.directory_size as usize];
+        assert_eq!(
+            CentralDirectoryRecord::parse_directory(directory, &end)
+                .unwrap()
+                .len(),
+            count
+        );
+    }
+
+    #[test]
+    fn derives_version_needed_from_method_and_zip64() {
+        let mut writer = ZipWriter::new(Vec::new());
+        for (name, method, size) in [
+            ("stored", CompressionMethod::Stored, 0),
+            ("deflate64", CompressionMethod::Deflate64, 0),
+            ("bzip2", CompressionMethod::Unsupported(METHOD_BZIP2), 0),
+            ("lzma", CompressionMethod::Unsupported(METHOD_LZMA), 0),
+            ("large", CompressionMethod::Deflate, 0x1_0000_0000),
+        ] {
+            let options = EntryOptions {
+                compression_method: method,
+                ..EntryOptions::default()
+            };
+            writer.start_raw_entry(name, options, 0, 0, size).unwrap();
+        }
+        let archive = writer.finish().unwrap();
+
+        let end = CentralDirectoryEnd::find_and_parse(&archive).unwrap();
+        let start = end.central_directory_offset as usize;
+        let directory = &archive[start..start + end.directory_size as usize];
+        let versions: Vec<_> = CentralDirectoryRecord::parse_directory(directory, &end)
+            .unwrap()
+            .into_iter()
+            .map(|record| {
+                let local_file =
+                    LocalFile::parse(&archive[record.file_header_offset as usize..]).unwrap();
+                assert_eq!(local_file.zip_version, record.zip_version_required);
+                record.zip_version_required
+            })
+            .collect();
+        assert_eq!(
+            versions,
+            [
+                VERSION_DEFAULT,
+                VERSION_DEFLATE64,
+                VERSION_BZIP2,
+                VERSION_LZMA,
+                VERSION_ZIP64
+            ]
+        );
+    }
+
+    #[test]
+    fn moves_large_values_into_extra_field() {
+        let mut writer = ZipWriter::new(Vec::new());
+        // Pretend that more than 4 GiB have been written already
+        writer.offset = 0x1_0000_0000;
+        let options = EntryOptions {
+            compression_method: CompressionMethod::Stored,
+            large_file: true,
+            ..EntryOptions::default()
+        };
+        writer.start_entry("late.txt", options).unwrap();
+        writer.write_all(b"contents").unwrap();
+        writer.finish_entry().unwrap();
+
+        let archive = writer.get_mut();
+        let local_file = LocalFile::parse(archive).unwrap();
+        assert_eq!(local_file.zip_version, VERSION_ZIP64);
+        assert_eq!(local_file.compressed_size, ZIP64_PLACEHOLDER_U32);
+        assert_eq!(local_file.uncompressed_size, ZIP64_PLACEHOLDER_U32);
+        assert_eq!(
+            local_file.extra_bytes,
+            Some(
+                Zip64ExtraField {
+                    uncompressed_size: Some(0),
+                    compressed_size: Some(0),
+                    ..Zip64ExtraField::default()
+                }
+                .to_bytes()
+            )
+        );
+        let data_start = LocalFile::header_length(archive).unwrap();
+        let descriptor = &archive[data_start + b"contents".len()..];
+        assert_eq!(descriptor.len(), 24);
+        assert_eq!(descriptor[8..16], 8u64.to_le_bytes());
+        assert_eq!(descriptor[16..], 8u64.to_le_bytes());
+
+        let record = &writer.records()[0];
+        assert_eq!(record.file_header_offset, ZIP64_PLACEHOLDER_U32);
+        assert_eq!(record.zip_version_required, VERSION_ZIP64);
+        assert_eq!(
+            Zip64ExtraField::from_record(record),
+            Ok(Some(Zip64ExtraField {
+                file_header_offset: Some(0x1_0000_0000),
+                ..Zip64ExtraField::default()
+            }))
+        );
+
+        // Without a ZIP64 extra field in the local header, the data descriptor cannot hold the sizes
+        writer
+            .start_entry("huge.txt", EntryOptions::default())
+            .unwrap();
+        writer.current.as_mut().unwrap().uncompressed_size = 0x1_0000_0000;
+        assert_eq!(
+            writer.finish_entry().unwrap_err().kind(),
+            ErrorKind::InvalidInput
+        );
+    }
+}
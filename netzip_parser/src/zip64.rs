@@ -0,0 +1,373 @@
+use std::io::Write;
+
+use crate::*;
+
+const MAGIC_ZIP64_CENTRAL_DIRECTORY_END: [u8; 4] = [0x50, 0x4B, 0x06, 0x06];
+const MAGIC_ZIP64_CENTRAL_DIRECTORY_END_LOCATOR: [u8; 4] = [0x50, 0x4B, 0x06, 0x07];
+
+/// Header ID of the extra field holding the 64 bit sizes and offset of an entry.
+pub const ZIP64_EXTRA_FIELD_ID: u16 = 0x0001;
+
+pub const ZIP64_EOCD_MIN_SIZE: usize = 56;
+const ZIP64_EOCD_BASE_OFFSET: usize = MAGIC_ZIP64_CENTRAL_DIRECTORY_END.len();
+#[allow(clippy::identity_op)]
+const ZIP64_EOCD_RECORD_SIZE_OFFSET: usize = ZIP64_EOCD_BASE_OFFSET + 0;
+const ZIP64_EOCD_VERSION_CREATED_OFFSET: usize = ZIP64_EOCD_BASE_OFFSET + 8;
+const ZIP64_EOCD_VERSION_REQUIRED_OFFSET: usize = ZIP64_EOCD_BASE_OFFSET + 10;
+const ZIP64_EOCD_DISK_NUMBER_OFFSET: usize = ZIP64_EOCD_BASE_OFFSET + 12;
+const ZIP64_EOCD_DISK_START_OFFSET: usize = ZIP64_EOCD_BASE_OFFSET + 16;
+const ZIP64_EOCD_RECORD_COUNT_DISK_OFFSET: usize = ZIP64_EOCD_BASE_OFFSET + 20;
+const ZIP64_EOCD_RECORD_COUNT_TOTAL_OFFSET: usize = ZIP64_EOCD_BASE_OFFSET + 28;
+const ZIP64_EOCD_DIRECTORY_SIZE_OFFSET: usize = ZIP64_EOCD_BASE_OFFSET + 36;
+const ZIP64_EOCD_CENTRAL_DIRECTORY_OFFSET: usize = ZIP64_EOCD_BASE_OFFSET + 44;
+/// The record size field counts neither itself nor the magic.
+const ZIP64_EOCD_RECORD_SIZE_EXCLUDED: usize = ZIP64_EOCD_BASE_OFFSET + 8;
+
+pub const ZIP64_EOCD_LOCATOR_SIZE: usize = 20;
+const ZIP64_LOCATOR_BASE_OFFSET: usize = MAGIC_ZIP64_CENTRAL_DIRECTORY_END_LOCATOR.len();
+#[allow(clippy::identity_op)]
+const ZIP64_LOCATOR_DISK_OFFSET: usize = ZIP64_LOCATOR_BASE_OFFSET + 0;
+const ZIP64_LOCATOR_END_OFFSET: usize = ZIP64_LOCATOR_BASE_OFFSET + 4;
+const ZIP64_LOCATOR_DISK_COUNT_OFFSET: usize = ZIP64_LOCATOR_BASE_OFFSET + 12;
+
+/// Value of 32 bit size and offset fields whose real value is stored in the ZIP64 extra field.
+pub const ZIP64_PLACEHOLDER_U32: u32 = 0xFFFF_FFFF;
+/// Value of 16 bit count fields whose real value is stored in the ZIP64 end of central directory.
+pub const ZIP64_PLACEHOLDER_U16: u16 = 0xFFFF;
+
+/// The ZIP64 end of central directory record, which precedes the regular one in archives whose directory
+/// location, size or record count do not fit into it.
+#[derive(Debug, Clone, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Zip64CentralDirectoryEnd {
+    pub zip_version_created: u16,
+    pub zip_version_required: u16,
+    pub disk_number: u32,
+    pub disk_start: u32,
+    pub record_count_disk: u64,
+    pub record_count_total: u64,
+    /// Size in bytes
+    pub directory_size: u64,
+    /// Offset from the start of the archive
+    pub central_directory_offset: u64,
+    pub extensible_data: Option<Vec<u8>>,
+}
+
+/// Points to the ZIP64 end of central directory record, directly preceding the regular one.
+#[derive(Debug, Clone, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Zip64CentralDirectoryEndLocator {
+    pub disk_number: u32,
+    /// Offset of the ZIP64 end of central directory record from the start of the archive
+    pub end_offset: u64,
+    pub disk_count: u32,
+}
+
+/// The ZIP64 extended information extra field of an entry. Only the values whose regular field is set to
+/// the placeholder are present, in the order of the fields below.
+#[derive(Debug, Clone, Default, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Zip64ExtraField {
+    pub uncompressed_size: Option<u64>,
+    pub compressed_size: Option<u64>,
+    pub file_header_offset: Option<u64>,
+    pub disk_number: Option<u32>,
+}
+
+impl Zip64CentralDirectoryEnd {
+    /// Parses the record, including its extensible data.
+    ///
+    /// # Arguments
+    ///
+    /// * `end_buf` - A buffer containing exactly the record, as located by the
+    ///   [Zip64CentralDirectoryEndLocator]
+    ///
+    /// # Returns
+    ///
+    /// A Result containing either the record or a ZipError if the buffer is shorter or longer than the size
+    /// the record declares
+    pub fn parse(end_buf: &[u8]) -> Result<Self> {
+        if end_buf.len() < ZIP64_EOCD_MIN_SIZE {
+            return Err(ZipError::MissingData("ZIP64 EOCD (Initial Length Check)"));
+        }
+        if end_buf[..4] != MAGIC_ZIP64_CENTRAL_DIRECTORY_END {
+            return Err(ZipError::MissingData("ZIP64 EOCD Magic"));
+        }
+
+        let record_size = read_u64(end_buf, ZIP64_EOCD_RECORD_SIZE_OFFSET);
+        let declared_length = record_size.saturating_add(ZIP64_EOCD_RECORD_SIZE_EXCLUDED as u64);
+        if declared_length < ZIP64_EOCD_MIN_SIZE as u64 {
+            return Err(ZipError::MalformedData("ZIP64 EOCD Record Size"));
+        } else if declared_length > end_buf.len() as u64 {
+            return Err(ZipError::MissingData("ZIP64 EOCD Extensible Data"));
+        } else if declared_length < end_buf.len() as u64 {
+            return Err(ZipError::ExtraneousData("ZIP64 EOCD"));
+        }
+
+        Ok(Self {
+            zip_version_created: read_u16(end_buf, ZIP64_EOCD_VERSION_CREATED_OFFSET),
+            zip_version_required: read_u16(end_buf, ZIP64_EOCD_VERSION_REQUIRED_OFFSET),
+            disk_number: read_u32(end_buf, ZIP64_EOCD_DISK_NUMBER_OFFSET),
+            disk_start: read_u32(end_buf, ZIP64_EOCD_DISK_START_OFFSET),
+            record_count_disk: read_u64(end_buf, ZIP64_EOCD_RECORD_COUNT_DISK_OFFSET),
+            record_count_total: read_u64(end_buf, ZIP64_EOCD_RECORD_COUNT_TOTAL_OFFSET),
+            directory_size: read_u64(end_buf, ZIP64_EOCD_DIRECTORY_SIZE_OFFSET),
+            central_directory_offset: read_u64(end_buf, ZIP64_EOCD_CENTRAL_DIRECTORY_OFFSET),
+            extensible_data: (end_buf.len() > ZIP64_EOCD_MIN_SIZE)
+                .then(|| end_buf[ZIP64_EOCD_MIN_SIZE..].to_vec()),
+        })
+    }
+
+    /// Writes the record, computing the record size from the extensible data.
+    ///
+    /// # Arguments
+    ///
+    /// * `writer` - The writer to serialize the record to
+    ///
+    /// # Returns
+    ///
+    /// A Result indicating whether the record was written successfully
+    pub fn write_to<W: Write>(&self, mut writer: W) -> std::io::Result<()> {
+        let extensible_data = self.extensible_data.as_deref().unwrap_or_default();
+        let record_size =
+            ZIP64_EOCD_MIN_SIZE - ZIP64_EOCD_RECORD_SIZE_EXCLUDED + extensible_data.len();
+
+        writer.write_all(&MAGIC_ZIP64_CENTRAL_DIRECTORY_END)?;
+        writer.write_all(&(record_size as u64).to_le_bytes())?;
+        writer.write_all(&self.zip_version_created.to_le_bytes())?;
+        writer.write_all(&self.zip_version_required.to_le_bytes())?;
+        writer.write_all(&self.disk_number.to_le_bytes())?;
+        writer.write_all(&self.disk_start.to_le_bytes())?;
+        writer.write_all(&self.record_count_disk.to_le_bytes())?;
+        writer.write_all(&self.record_count_total.to_le_bytes())?;
+        writer.write_all(&self.directory_size.to_le_bytes())?;
+        writer.write_all(&self.central_directory_offset.to_le_bytes())?;
+        writer.write_all(extensible_data)
+    }
+}
+
+impl Zip64CentralDirectoryEndLocator {
+    /// Parses the locator.
+    ///
+    /// # Arguments
+    ///
+    /// * `locator_buf` - A buffer containing exactly the locator, which directly precedes the end of central
+    ///   directory record
+    ///
+    /// # Returns
+    ///
+    /// A Result containing either the locator or a ZipError if the buffer does not hold a locator
+    pub fn parse(locator_buf: &[u8]) -> Result<Self> {
+        if locator_buf.len() < ZIP64_EOCD_LOCATOR_SIZE {
+            return Err(ZipError::MissingData("ZIP64 EOCD Locator"));
+        } else if locator_buf.len() > ZIP64_EOCD_LOCATOR_SIZE {
+            return Err(ZipError::ExtraneousData("ZIP64 EOCD Locator"));
+        }
+        if locator_buf[..4] != MAGIC_ZIP64_CENTRAL_DIRECTORY_END_LOCATOR {
+            return Err(ZipError::MissingData("ZIP64 EOCD Locator Magic"));
+        }
+
+        Ok(Self {
+            disk_number: read_u32(locator_buf, ZIP64_LOCATOR_DISK_OFFSET),
+            end_offset: read_u64(locator_buf, ZIP64_LOCATOR_END_OFFSET),
+            disk_count: read_u32(locator_buf, ZIP64_LOCATOR_DISK_COUNT_OFFSET),
+        })
+    }
+
+    /// Writes the locator.
+    ///
+    /// # Arguments
+    ///
+    /// * `writer` - The writer to serialize the locator to
+    ///
+    /// # Returns
+    ///
+    /// A Result indicating whether the locator was written successfully
+    pub fn write_to<W: Write>(&self, mut writer: W) -> std::io::Result<()> {
+        writer.write_all(&MAGIC_ZIP64_CENTRAL_DIRECTORY_END_LOCATOR)?;
+        writer.write_all(&self.disk_number.to_le_bytes())?;
+        writer.write_all(&self.end_offset.to_le_bytes())?;
+        writer.write_all(&self.disk_count.to_le_bytes())
+    }
+}
+
+impl Zip64ExtraField {
+    /// Finds and parses the ZIP64 extra field of a central directory record, reading the values whose
+    /// regular fields hold the placeholder.
+    ///
+    /// # Arguments
+    ///
+    /// * `record` - The record whose extra bytes to search
+    ///
+    /// # Returns
+    ///
+    /// A Result containing either the field, None if the record has no such field, or a ZipError if the
+    /// field is too short for the values the record's placeholders call for
+    pub fn from_record(record: &CentralDirectoryRecord) -> Result<Option<Self>> {
+        let Some(data) = find_extra_field(record.extra_bytes.as_deref(), ZIP64_EXTRA_FIELD_ID)?
+        else {
+            return Ok(None);
+        };
+
+        let mut cursor = 0;
+        let mut next = |size: usize| -> Result<u64> {
+            if cursor + size > data.len() {
+                return Err(ZipError::MissingData("ZIP64 Extra Field"));
+            }
+            let mut bytes = [0; 8];
+            bytes[..size].copy_from_slice(&data[cursor..cursor + size]);
+            cursor += size;
+            Ok(u64::from_le_bytes(bytes))
+        };
+
+        let mut field = Self::default();
+        if record.uncompressed_size == ZIP64_PLACEHOLDER_U32 {
+            field.uncompressed_size = Some(next(8)?);
+        }
+        if record.compressed_size == ZIP64_PLACEHOLDER_U32 {
+            field.compressed_size = Some(next(8)?);
+        }
+        if record.file_header_offset == ZIP64_PLACEHOLDER_U32 {
+            field.file_header_offset = Some(next(8)?);
+        }
+        if record.disk_number == ZIP64_PLACEHOLDER_U16 {
+            field.disk_number = Some(next(4)? as u32);
+        }
+
+        Ok(Some(field))
+    }
+
+    /// Returns true if none of the values are present.
+    pub fn is_empty(&self) -> bool {
+        *self == Self::default()
+    }
+
+    /// Writes the field including its header ID and length, followed by the values that are present.
+    ///
+    /// # Arguments
+    ///
+    /// * `writer` - The writer to serialize the field to
+    ///
+    /// # Returns
+    ///
+    /// A Result indicating whether the field was written successfully
+    pub fn write_to<W: Write>(&self, mut writer: W) -> std::io::Result<()> {
+        let values = [
+            self.uncompressed_size,
+            self.compressed_size,
+            self.file_header_offset,
+        ];
+        let length = values.iter().flatten().count() * 8 + self.disk_number.map_or(0, |_| 4);
+
+        writer.write_all(&ZIP64_EXTRA_FIELD_ID.to_le_bytes())?;
+        writer.write_all(&(length as u16).to_le_bytes())?;
+        for value in values.into_iter().flatten() {
+            writer.write_all(&value.to_le_bytes())?;
+        }
+        if let Some(disk_number) = self.disk_number {
+            writer.write_all(&disk_number.to_le_bytes())?;
+        }
+
+        Ok(())
+    }
+
+    /// Serializes the field into a new buffer, see [Zip64ExtraField::write_to]. Unlike the records, the field
+    /// has no lengths that could overflow, so this cannot fail.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut out = Vec::new();
+        self.write_to(&mut out)
+            .expect("Writing to a Vec should not fail");
+        out
+    }
+}
+
+/// Returns the data of the first extra field with the given header ID.
+fn find_extra_field(extra_bytes: Option<&[u8]>, id: u16) -> Result<Option<&[u8]>> {
+    let mut remaining = extra_bytes.unwrap_or_default();
+    while !remaining.is_empty() {
+        if remaining.len() < 4 {
+            return Err(ZipError::MissingData("Extra Field Header"));
+        }
+
+        let length = read_u16(remaining, 2) as usize;
+        if remaining.len() < 4 + length {
+            return Err(ZipError::MissingData("Extra Field Data"));
+        }
+        if read_u16(remaining, 0) == id {
+            return Ok(Some(&remaining[4..4 + length]));
+        }
+        remaining = &remaining[4 + length..];
+    }
+
+    Ok(None)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn round_trips_zip64_records() {
+        let end = Zip64CentralDirectoryEnd {
+            zip_version_created: 0x032D,
+            zip_version_required: 45,
+            disk_number: 0,
+            disk_start: 0,
+            record_count_disk: 70000,
+            record_count_total: 70000,
+            directory_size: 0x1_2345_6789,
+            central_directory_offset: 0x2_0000_0000,
+            extensible_data: Some(vec![1, 2, 3]),
+        };
+        let mut bytes = Vec::new();
+        end.write_to(&mut bytes).unwrap();
+        assert_eq!(bytes.len(), ZIP64_EOCD_MIN_SIZE + 3);
+        assert_eq!(Zip64CentralDirectoryEnd::parse(&bytes), Ok(end));
+        assert_eq!(
+            Zip64CentralDirectoryEnd::parse(&bytes[..bytes.len() - 1]),
+            Err(ZipError::MissingData("ZIP64 EOCD Extensible Data"))
+        );
+
+        let locator = Zip64CentralDirectoryEndLocator {
+            disk_number: 0,
+            end_offset: 0x3_0000_0000,
+            disk_count: 1,
+        };
+        let mut bytes = Vec::new();
+        locator.write_to(&mut bytes).unwrap();
+        assert_eq!(bytes.len(), ZIP64_EOCD_LOCATOR_SIZE);
+        assert_eq!(Zip64CentralDirectoryEndLocator::parse(&bytes), Ok(locator));
+    }
+
+    #[test]
+    fn reads_extra_field_values_marked_in_record() {
+        let field = Zip64ExtraField {
+            uncompressed_size: Some(0x1_0000_0000),
+            compressed_size: None,
+            file_header_offset: Some(0x2_0000_0000),
+            disk_number: None,
+        };
+        // Another extra field preceding the ZIP64 one has to be skipped
+        let extra_bytes = [&[0x55, 0x54, 0x01, 0x00, 0x00][..], &field.to_bytes()].concat();
+        let mut record = CentralDirectoryRecord::parse(
+            &[
+                0x50, 0x4B, 0x01, 0x02, 0x2D, 0x03, 0x2D, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+                0x21, 0x00, 0x00, 0x00, 0x00, 0x00, 0x10, 0x00, 0x00, 0x00, 0xFF, 0xFF, 0xFF, 0xFF,
+                0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+                0xFF, 0xFF, 0xFF, 0xFF,
+            ],
+            false,
+        )
+        .unwrap();
+
+        assert_eq!(Zip64ExtraField::from_record(&record), Ok(None));
+        record.extra_bytes = Some(extra_bytes);
+        assert_eq!(Zip64ExtraField::from_record(&record), Ok(Some(field)));
+
+        record.compressed_size = ZIP64_PLACEHOLDER_U32;
+        assert_eq!(
+            Zip64ExtraField::from_record(&record),
+            Err(ZipError::MissingData("ZIP64 Extra Field"))
+        );
+    }
+}